@@ -6,7 +6,12 @@ use std::{
 use anyhow::{bail, Result};
 use calamine::Reader;
 
-use crate::{data::data_table::DataTable, error, utils};
+use crate::{
+    data::data_table::{DataTable, ListViewCfg},
+    error,
+    marco::{check_if, check_some},
+    utils,
+};
 
 pub type DataAction = Box<dyn Action<Target = HashMap<String, DataTable>, Output = String>>;
 
@@ -288,6 +293,63 @@ impl Action for UpdateAction {
     }
 }
 
+// 多个操作合并为一次撤销/重做, 撤销时按相反顺序执行
+pub struct CompoundAction {
+    pub desc: String,
+    pub actions: Vec<DataAction>,
+}
+
+impl CompoundAction {
+    pub fn new(desc: String, actions: Vec<DataAction>) -> Option<DataAction> {
+        if actions.is_empty() {
+            return None;
+        }
+        Some(Box::new(Self { desc, actions }))
+    }
+
+    // 批量修改单元格, list 为 (表格, 行, 字段, 新值), 不存在的单元格按空字符串处理
+    pub fn update(
+        target: &HashMap<String, DataTable>,
+        desc: String,
+        list: Vec<(String, usize, String, String)>,
+    ) -> Option<DataAction> {
+        let mut actions: Vec<DataAction> = Vec::new();
+        for (table_name, row_idx, key, new) in list {
+            let table = check_some!(target.get(&table_name), continue);
+            let row = check_some!(table.data.get(row_idx), continue);
+            let old = utils::map_get_string(row, &key, "");
+            check_if!(old == new, continue);
+            actions.push(Box::new(UpdateAction {
+                table_name,
+                row_idx,
+                key,
+                old,
+                new,
+            }));
+        }
+        return CompoundAction::new(desc, actions);
+    }
+}
+
+impl Action for CompoundAction {
+    type Target = HashMap<String, DataTable>;
+    type Output = String;
+
+    fn redo(&mut self, target: &mut HashMap<String, DataTable>) -> String {
+        for one in self.actions.iter_mut() {
+            one.redo(target);
+        }
+        return format!("重做: {}", self.desc);
+    }
+
+    fn undo(&mut self, target: &mut HashMap<String, DataTable>) -> String {
+        for one in self.actions.iter_mut().rev() {
+            one.undo(target);
+        }
+        return format!("撤销: {}", self.desc);
+    }
+}
+
 pub struct ImportAction {
     pub table_name: String,
     pub data: Vec<HashMap<String, String>>,
@@ -423,6 +485,7 @@ impl CopyAction {
         data: HashMap<String, String>,
         child: Vec<String>,
         copy_master_val: String,
+        err: &mut Vec<String>,
     ) -> Option<DataAction> {
         let table = target.get(table_name);
         if table.is_none() {
@@ -447,6 +510,7 @@ impl CopyAction {
                 &copy_master_val,
                 false,
                 &"".to_string(),
+                &ListViewCfg::default(),
             );
             let mut data = Vec::new();
             for (_, one) in copy_list {
@@ -457,8 +521,14 @@ impl CopyAction {
                             &cur_master_val,
                             data.len() as i32,
                         );
-                        if row.is_some() {
-                            data.push(row.unwrap());
+                        // 子表的行无法分配主键时不复制
+                        match row {
+                            Ok(Some(row)) => data.push(row),
+                            Ok(None) => {}
+                            Err(e) => {
+                                err.push(e.to_string());
+                                return None;
+                            }
                         }
                     }
                 }
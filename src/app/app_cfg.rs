@@ -3,8 +3,30 @@ use eframe::{
     epaint::Color32,
 };
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
-use crate::app::theme;
+use crate::{
+    app::theme,
+    data::data_table::{self, ListViewCfg},
+};
+
+// 本地用户配置, 多人同时新增数据时各自使用不同的主键段, 避免合并时冲突
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct UserProfile {
+    pub name: String,
+    pub key_start: i32,
+    pub key_end: i32,
+}
+
+impl UserProfile {
+    pub fn key_range(&self) -> Option<(i32, i32)> {
+        if self.key_end <= 0 || self.key_end < self.key_start {
+            return None;
+        }
+        return Some((self.key_start, self.key_end));
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct AppCfg {
@@ -13,6 +35,14 @@ pub struct AppCfg {
     base_theme: i32,
     custom_theme: theme::Theme,
     pub show_undo: bool,
+    // 表格名 -> 保存的筛选 (名称, 筛选条件)
+    #[serde(default)]
+    pub saved_query: HashMap<String, Vec<(String, String)>>,
+    // 表格名 -> 列表的分组和排序方式
+    #[serde(default)]
+    pub list_view: HashMap<String, ListViewCfg>,
+    #[serde(default)]
+    pub user: UserProfile,
 }
 
 impl Default for AppCfg {
@@ -23,6 +53,9 @@ impl Default for AppCfg {
             base_theme: 0,
             custom_theme: theme::MOCHA,
             show_undo: false,
+            saved_query: HashMap::new(),
+            list_view: HashMap::new(),
+            user: UserProfile::default(),
         }
     }
 }
@@ -125,6 +158,22 @@ impl AppCfg {
                             ui.checkbox(&mut self.show_undo, "显示");
                         });
                         ui.end_row();
+
+                        ui.add(egui::Label::new("用户名"));
+                        ui.text_edit_singleline(&mut self.user.name);
+                        ui.end_row();
+
+                        ui.add(egui::Label::new("主键段")).on_hover_text(
+                            "表格配置为 user 分配方式时, 新增数据的主键在此范围内分配",
+                        );
+                        ui.horizontal(|ui| {
+                            ui.add(DragValue::new(&mut self.user.key_start).prefix("从:"));
+                            ui.add(DragValue::new(&mut self.user.key_end).prefix("到:"));
+                            if self.user.key_range().is_none() {
+                                ui.label(egui::RichText::new("未设置").color(Color32::GRAY));
+                            }
+                        });
+                        ui.end_row();
                     });
             });
 
@@ -162,6 +211,7 @@ impl AppCfg {
     }
 
     pub fn update_cfg(&self, ctx: &egui::Context) {
+        data_table::set_user_key_range(self.user.key_range());
         AppCfg::update_theme(self.base_theme, self.custom_theme.clone(), ctx);
     }
 }
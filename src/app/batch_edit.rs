@@ -0,0 +1,282 @@
+use eframe::{egui, epaint::Color32};
+use regex::RegexBuilder;
+use std::collections::HashMap;
+
+use crate::{
+    app::syntax_highlight::code_editor_ui,
+    data::{data_expr, data_field::FieldInfo, data_table::DataTable},
+    marco::{check_if, check_some},
+    utils,
+};
+
+// 预览最多显示的修改数
+const MAX_PREVIEW_SHOW: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum EBatchMode {
+    #[default]
+    Set,
+    Replace,
+    Lua,
+}
+
+// 一处修改: (行, 字段, 旧值, 新值)
+type BatchChange = (usize, String, String, String);
+
+#[derive(Default)]
+pub struct BatchEditor {
+    show: bool,
+    table: String,
+    mode: EBatchMode,
+    // 为空时查找替换作用于所有字段
+    field: String,
+    value: String,
+    find: String,
+    replace: String,
+    regex: bool,
+    ignore_case: bool,
+    code: String,
+
+    preview: Vec<BatchChange>,
+    errors: Vec<String>,
+}
+
+impl BatchEditor {
+    pub fn show(&mut self, table: &String) {
+        if self.table != *table {
+            self.table = table.clone();
+            self.field.clear();
+            self.value.clear();
+        }
+        self.preview.clear();
+        self.errors.clear();
+        self.show = true;
+    }
+
+    fn calc(&self, data_table: &DataTable) -> (Vec<BatchChange>, Vec<String>) {
+        let mut ret = Vec::new();
+        let mut errors = Vec::new();
+        let rows = data_table.select.get_rows(data_table.cur_row);
+        let fields: Vec<&FieldInfo> = data_table
+            .info
+            .iter()
+            .filter(|a| {
+                a.name == self.field || (self.field.is_empty() && self.mode == EBatchMode::Replace)
+            })
+            .collect();
+        check_if!(
+            fields.is_empty(),
+            return (ret, vec!["请选择字段".to_string()])
+        );
+
+        let re = match self.mode {
+            EBatchMode::Replace => {
+                check_if!(
+                    self.find.is_empty(),
+                    return (ret, vec!["查找内容为空".to_string()])
+                );
+                let pattern = if self.regex {
+                    self.find.clone()
+                } else {
+                    regex::escape(&self.find)
+                };
+                match RegexBuilder::new(&pattern)
+                    .case_insensitive(self.ignore_case)
+                    .build()
+                {
+                    Ok(re) => Some(re),
+                    Err(e) => return (ret, vec![format!("正则表达式错误: {}", e)]),
+                }
+            }
+            _ => None,
+        };
+
+        for row in rows {
+            let map = check_some!(data_table.data.get(row), continue);
+            let key = data_table.get_row_key(map);
+            for field in &fields {
+                check_if!(
+                    field.is_key && self.mode != EBatchMode::Set && self.field.is_empty(),
+                    continue
+                );
+                let old = utils::map_get_string(map, &field.name, "");
+                let new = match self.mode {
+                    EBatchMode::Set => self.value.clone(),
+                    EBatchMode::Replace => {
+                        let re = re.as_ref().unwrap();
+                        if self.regex {
+                            re.replace_all(&old, self.replace.as_str()).to_string()
+                        } else {
+                            re.replace_all(&old, regex::NoExpand(&self.replace))
+                                .to_string()
+                        }
+                    }
+                    EBatchMode::Lua => match data_expr::run_transform(&self.code, field, map) {
+                        Ok(v) => v,
+                        Err(e) => {
+                            errors.push(format!("[{}] {}", key, e));
+                            continue;
+                        }
+                    },
+                };
+                check_if!(new == old, continue);
+                let (err, msg) = field.check_data(&new);
+                if err {
+                    errors.push(format!("[{}] {}: {}", key, field.title, msg));
+                }
+                ret.push((row, field.name.clone(), old, new));
+            }
+        }
+        // 修改主键时不能产生重复的主键
+        let changes = ret
+            .iter()
+            .map(|(row, k, _, v)| (*row, k.clone(), v.clone()))
+            .collect();
+        errors.extend(data_table.check_key_change(&changes));
+        return (ret, errors);
+    }
+
+    fn draw_input(&mut self, ui: &mut egui::Ui, data_table: &DataTable) {
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut self.mode, EBatchMode::Set, "设置值");
+            ui.radio_value(&mut self.mode, EBatchMode::Replace, "查找替换");
+            ui.radio_value(&mut self.mode, EBatchMode::Lua, "lua变换");
+        });
+
+        let field = data_table.info.iter().find(|a| a.name == self.field);
+        let title = match field {
+            Some(f) => format!("{}({})", f.title, f.name),
+            None if self.mode == EBatchMode::Replace => "全部字段".to_string(),
+            None => "选择字段".to_string(),
+        };
+        let mut select = self.field.clone();
+        ui.horizontal(|ui| {
+            ui.label("字段:");
+            egui::ComboBox::from_id_source("batch_edit_field")
+                .selected_text(title)
+                .width(240.0)
+                .show_ui(ui, |ui| {
+                    if self.mode == EBatchMode::Replace {
+                        ui.selectable_value(&mut select, String::new(), "全部字段");
+                    }
+                    for one in &data_table.info {
+                        let show = format!("{}({})", one.title, one.name);
+                        ui.selectable_value(&mut select, one.name.clone(), show);
+                    }
+                });
+        });
+        if select != self.field {
+            self.field = select;
+            if let Some(f) = data_table.info.iter().find(|a| a.name == self.field) {
+                self.value = f.default_val.clone();
+            }
+        }
+
+        match self.mode {
+            EBatchMode::Set => {
+                let field = check_some!(
+                    data_table.info.iter().find(|a| a.name == self.field),
+                    return
+                );
+                field.create_ui(&mut self.value, ui, false, &String::new(), -1);
+            }
+            EBatchMode::Replace => {
+                egui::Grid::new("batch_edit_replace")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("查找");
+                        ui.text_edit_singleline(&mut self.find);
+                        ui.end_row();
+                        ui.label("替换为");
+                        ui.text_edit_singleline(&mut self.replace);
+                        ui.end_row();
+                    });
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.regex, "正则表达式")
+                        .on_hover_text("替换内容中可以用 $1 引用分组");
+                    ui.checkbox(&mut self.ignore_case, "忽略大小写");
+                });
+            }
+            EBatchMode::Lua => {
+                ui.label("v 为当前值, row 为整行数据, 如: v * 2 或 row.name .. \"_new\"");
+                let id = ui.id().with("batch_edit_lua");
+                let completion = vec![
+                    ("v".to_string(), "当前值".to_string()),
+                    ("row".to_string(), "整行数据".to_string()),
+                ];
+                code_editor_ui(ui, id, &mut self.code, "lua", &[], &completion);
+            }
+        }
+    }
+
+    // 返回 (描述, 修改列表), 由调用方合并为一次撤销
+    pub fn ui(
+        &mut self,
+        ctx: &egui::Context,
+        data_table: &HashMap<String, DataTable>,
+    ) -> Option<(String, Vec<(String, usize, String, String)>)> {
+        let mut ret = None;
+        let table = check_some!(data_table.get(&self.table), return None);
+        let mut show = self.show;
+        let rows = table.select.get_rows(table.cur_row).len();
+        egui::Window::new(format!("✏批量编辑 - {}", table.show_name))
+            .id(egui::Id::new("batch_edit_window"))
+            .open(&mut show)
+            .resizable(true)
+            .default_width(480.0)
+            .show(ctx, |ui| {
+                ui.label(format!("已选择{}行", rows));
+                ui.separator();
+                self.draw_input(ui, table);
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("预览").clicked() {
+                        (self.preview, self.errors) = self.calc(table);
+                    }
+                    let apply = ui.button("应用").on_hover_text("有错误时不能应用");
+                    if apply.clicked() {
+                        let (list, errors) = self.calc(table);
+                        self.errors = errors;
+                        // 有错误时只显示预览, 不修改数据
+                        if !self.errors.is_empty() {
+                            self.preview = list;
+                            return;
+                        }
+                        let rows: std::collections::HashSet<usize> =
+                            list.iter().map(|a| a.0).collect();
+                        let desc = format!("批量编辑{}行{}处", rows.len(), list.len());
+                        let list = list
+                            .into_iter()
+                            .map(|(row, k, _, v)| (self.table.clone(), row, k, v))
+                            .collect();
+                        ret = Some((desc, list));
+                        self.preview.clear();
+                    }
+                });
+                for one in &self.errors {
+                    ui.label(egui::RichText::new(one).color(Color32::RED));
+                }
+                check_if!(self.preview.is_empty(), return);
+                ui.label(format!("共{}处修改", self.preview.len()));
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for (row, k, old, new) in self.preview.iter().take(MAX_PREVIEW_SHOW) {
+                            let map = check_some!(table.data.get(*row), continue);
+                            let key = table.get_row_key(map);
+                            ui.horizontal(|ui| {
+                                ui.label(format!("[{}] {}:", key, k));
+                                ui.label(egui::RichText::new(old).color(Color32::RED));
+                                ui.label("->");
+                                ui.label(egui::RichText::new(new).color(Color32::GREEN));
+                            });
+                        }
+                        if self.preview.len() > MAX_PREVIEW_SHOW {
+                            ui.label(format!("只显示前{}处", MAX_PREVIEW_SHOW));
+                        }
+                    });
+            });
+        self.show = show;
+        return ret;
+    }
+}
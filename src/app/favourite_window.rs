@@ -0,0 +1,86 @@
+use eframe::egui;
+use itertools::Itertools;
+use std::collections::HashMap;
+
+use crate::{
+    data::{data_table::DataTable, row_meta},
+    marco::{check_if, check_some},
+    utils,
+};
+
+// 收藏夹, 所有表格中收藏的行放在一起显示
+#[derive(Default)]
+pub struct FavouriteWindow {
+    show: bool,
+    search: String,
+}
+
+impl FavouriteWindow {
+    pub fn show(&mut self) {
+        self.show = true;
+    }
+
+    fn get_rows(&self, table: &DataTable) -> Vec<(usize, String)> {
+        let mut ret = Vec::new();
+        for (idx, row) in table.data.iter().enumerate() {
+            check_if!(!row_meta::is_favourite(row), continue);
+            let name = check_some!(table.get_one_show_name(row), continue);
+            check_if!(
+                !self.search.is_empty() && !utils::fuzzy_match(&name, &self.search),
+                continue
+            );
+            ret.push((idx, name));
+        }
+        return ret;
+    }
+
+    // 返回点击的 (表格, 行)
+    pub fn ui(
+        &mut self,
+        ctx: &egui::Context,
+        data_table: &HashMap<String, DataTable>,
+    ) -> Option<(String, usize)> {
+        let mut ret = None;
+        let mut show = self.show;
+        egui::Window::new("⭐收藏夹")
+            .id(egui::Id::new("favourite_window"))
+            .open(&mut show)
+            .resizable(true)
+            .default_width(320.0)
+            .default_height(400.0)
+            .show(ctx, |ui| {
+                let txt = egui::TextEdit::singleline(&mut self.search)
+                    .hint_text("筛选")
+                    .desired_width(f32::INFINITY);
+                ui.add(txt);
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .id_source("favourite_window_list")
+                    .auto_shrink([false; 2])
+                    .show(ui, |ui| {
+                        let mut empty = true;
+                        for (name, table) in data_table.iter().sorted_by_key(|a| a.0) {
+                            let rows = self.get_rows(table);
+                            check_if!(rows.is_empty(), continue);
+                            empty = false;
+                            let title = format!("{}({})", table.show_name, rows.len());
+                            egui::CollapsingHeader::new(title)
+                                .id_source(("favourite_window", name))
+                                .default_open(true)
+                                .show(ui, |ui| {
+                                    for (idx, show) in rows {
+                                        if ui.selectable_label(false, show).clicked() {
+                                            ret = Some((name.clone(), idx));
+                                        }
+                                    }
+                                });
+                        }
+                        if empty {
+                            ui.label("没有收藏的数据, 可以在列表中右键收藏");
+                        }
+                    });
+            });
+        self.show = show;
+        return ret;
+    }
+}
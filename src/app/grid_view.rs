@@ -0,0 +1,422 @@
+use eframe::{
+    egui::{self, pos2, vec2, Rect, RichText, Sense},
+    epaint::{Color32, Stroke},
+};
+use itertools::Itertools;
+use std::collections::HashMap;
+
+use crate::{
+    app::query_bar,
+    data::{
+        data_field::{EFieldType, FieldInfo},
+        data_query,
+        data_table::{self, DataTable, ESortType, GridState, KEY_SEP},
+    },
+    marco::{check_if, check_some},
+    utils,
+};
+
+const KEY_WIDTH: f32 = 100.0;
+const SHOW_WIDTH: f32 = 160.0;
+const COL_WIDTH: f32 = 140.0;
+
+// 筛选和排序与列表使用相同的规则, 数据修改后 revision 变化时重新计算
+fn update_order(
+    grid: &mut GridState,
+    info: &Vec<FieldInfo>,
+    data: &Vec<HashMap<String, String>>,
+    revision: usize,
+) {
+    let filter: Vec<(&String, &str)> = grid
+        .filter
+        .iter()
+        .map(|(k, v)| (k, v.trim()))
+        .filter(|(k, v)| !v.is_empty() && info.iter().any(|a| a.name == **k))
+        .sorted_by_key(|(k, _)| *k)
+        .collect();
+    let filter_key = filter.iter().map(|(k, v)| format!("{}={}", k, v)).join("|");
+    let key = format!(
+        "{}|{}|{}|{}|{}|{}",
+        revision,
+        data.len(),
+        grid.sort,
+        grid.sort_desc,
+        filter_key,
+        grid.query
+    );
+    check_if!(key == grid.order_key, return);
+    let query = data_query::get_query(&grid.query);
+    let filter: Vec<data_query::Query> = filter
+        .iter()
+        .map(|(k, v)| data_query::field_query(k, v))
+        .collect();
+
+    let mut order: Vec<usize> = (0..data.len())
+        .filter(|i| {
+            let row = &data[*i];
+            check_if!(!query.matches(info, row), return false);
+            filter.iter().all(|a| a.matches(info, row))
+        })
+        .collect();
+    if let Some(field) = info.iter().find(|a| a.name == grid.sort) {
+        let empty = String::new();
+        order.sort_by(|a, b| {
+            let a = data[*a].get(&field.name).unwrap_or(&empty);
+            let b = data[*b].get(&field.name).unwrap_or(&empty);
+            data_table::compare_by_type(Some(field), ESortType::Auto, a, b)
+        });
+        if grid.sort_desc {
+            order.reverse();
+        }
+    }
+    grid.order = order;
+    grid.order_key = key;
+}
+
+fn draw_header(ui: &mut egui::Ui, rect: Rect, clip: Rect, field: &FieldInfo, grid: &mut GridState) {
+    let row_h = rect.height() / 2.0;
+    let title_rect = Rect::from_min_size(rect.min, vec2(rect.width(), row_h));
+    let mut child = ui.child_ui(
+        title_rect.shrink2(vec2(2.0, 1.0)),
+        egui::Layout::left_to_right(egui::Align::Center),
+    );
+    child.set_clip_rect(title_rect.intersect(clip));
+    let mut title = field.title.clone();
+    if grid.sort == field.name {
+        title = format!("{}{}", title, if grid.sort_desc { "⏷" } else { "⏶" });
+    }
+    let copy = grid.copy_cols.contains(&field.name);
+    if copy {
+        title = format!("📋{}", title);
+    }
+    let resp = child
+        .selectable_label(grid.sort == field.name, RichText::new(title).strong())
+        .on_hover_text(format!("{}\n{}", field.name, field.desc));
+    // 选中的列用于 ctrl+c 复制单元格
+    let resp = resp.context_menu(|ui| {
+        let txt = if copy {
+            "取消复制此列"
+        } else {
+            "复制时包含此列"
+        };
+        if ui.button(txt).clicked() {
+            if copy {
+                grid.copy_cols.retain(|a| *a != field.name);
+            } else {
+                grid.copy_cols.push(field.name.clone());
+            }
+            ui.close_menu();
+        }
+        if !grid.copy_cols.is_empty() && ui.button("复制所有列").clicked() {
+            grid.copy_cols.clear();
+            ui.close_menu();
+        }
+    });
+    // 升序 -> 降序 -> 不排序
+    if resp.clicked() {
+        if grid.sort != field.name {
+            grid.sort = field.name.clone();
+            grid.sort_desc = false;
+        } else if !grid.sort_desc {
+            grid.sort_desc = true;
+        } else {
+            grid.sort.clear();
+        }
+    }
+
+    let filter_rect = Rect::from_min_size(
+        pos2(rect.min.x, rect.min.y + row_h),
+        vec2(rect.width(), row_h),
+    );
+    let mut child = ui.child_ui(
+        filter_rect.shrink2(vec2(2.0, 1.0)),
+        egui::Layout::left_to_right(egui::Align::Center),
+    );
+    child.set_clip_rect(filter_rect.intersect(clip));
+    let filter = grid.filter.entry(field.name.clone()).or_default();
+    let hint = if field.val_type == EFieldType::Number {
+        "筛选 >5"
+    } else {
+        "筛选"
+    };
+    let txt = egui::TextEdit::singleline(filter)
+        .hint_text(hint)
+        .desired_width(f32::INFINITY);
+    child.add(txt);
+}
+
+// 返回 (是否点击, 新值)
+fn draw_cell(
+    ui: &mut egui::Ui,
+    rect: Rect,
+    clip: Rect,
+    field: &FieldInfo,
+    val: &String,
+    row: usize,
+    frozen: bool,
+    edit: &mut Option<(usize, String)>,
+) -> (bool, Option<String>) {
+    let mut click = false;
+    let mut ret = None;
+    let clip = rect.intersect(clip);
+    let mut child = ui.child_ui(
+        rect.shrink2(vec2(2.0, 1.0)),
+        egui::Layout::left_to_right(egui::Align::Center),
+    );
+    child.set_clip_rect(clip);
+    if frozen || !field.is_inline_editor() {
+        // 复杂的字段只显示第一行, 双击或点击后在弹出窗口中编辑
+        let txt = val.lines().next().unwrap_or("");
+        let resp = child.add(egui::Label::new(txt).sense(Sense::click()));
+        if resp.clicked() {
+            click = true;
+            if !frozen {
+                *edit = Some((row, field.name.clone()));
+            }
+        }
+        if resp.double_clicked() {
+            *edit = Some((row, field.name.clone()));
+        }
+    } else {
+        let mut v = val.clone();
+        let id = child.id().with(("grid_cell", row, &field.name));
+        child.push_id(id, |ui| {
+            click = field.create_cell_ui(&mut v, ui, row as i32);
+        });
+        if v != *val {
+            ret = Some(v);
+        }
+    }
+
+    let (has_err, msg) = field.check_data(val);
+    if has_err {
+        let stroke = Stroke::new(1.0, Color32::RED);
+        ui.painter()
+            .with_clip_rect(clip)
+            .rect_stroke(rect.shrink(1.0), 0.0, stroke);
+        let id = ui.id().with(("grid_err", row, &field.name));
+        ui.interact(clip, id, Sense::hover()).on_hover_text(msg);
+    }
+    return (click, ret);
+}
+
+fn draw_edit_window(
+    ctx: &egui::Context,
+    table_name: &String,
+    info: &Vec<FieldInfo>,
+    data: &Vec<HashMap<String, String>>,
+    key_fields: &Vec<String>,
+    grid: &mut GridState,
+    changed: &mut Vec<(usize, String, String)>,
+) {
+    let (row, name) = check_some!(grid.edit.clone(), return);
+    let field = check_some!(info.iter().find(|a| a.name == name), {
+        grid.edit = None;
+        return;
+    });
+    let map = check_some!(data.get(row), {
+        grid.edit = None;
+        return;
+    });
+    let old = utils::map_get_string(map, &name, "");
+    let key = key_fields
+        .iter()
+        .map(|k| utils::map_get_string(map, k, ""))
+        .join(KEY_SEP);
+    let mut open = true;
+    egui::Window::new(format!("编辑[{}] {}", key, field.title))
+        .id(egui::Id::new(("grid_edit_window", table_name)))
+        .open(&mut open)
+        .resizable(true)
+        .default_width(400.0)
+        .show(ctx, |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                let mut v = old.clone();
+                field.create_ui(&mut v, ui, false, &String::new(), row as i32);
+                if v != old {
+                    changed.push((row, name.clone(), v));
+                }
+            });
+        });
+    if !open {
+        grid.edit = None;
+    }
+}
+
+// 表格视图: 行 x 导出字段, 主键和显示字段固定在左侧, 只绘制可见的行和列
+// 返回 (点击的行, 是否切换回列表视图)
+pub fn draw_grid(
+    ctx: &egui::Context,
+    idx: i32,
+    width: f32,
+    data_table: &mut DataTable,
+    saved: &mut Vec<(String, String)>,
+    changed: &mut Vec<(usize, String, String)>,
+) -> (Option<i32>, bool, bool) {
+    let mut click = None;
+    let mut back = false;
+    let mut batch = false;
+    let DataTable {
+        table_name,
+        show_name,
+        show_field,
+        key_fields,
+        info,
+        data,
+        cur_row,
+        grid,
+        select,
+        revision,
+        ..
+    } = data_table;
+    update_order(grid, info, data, *revision);
+
+    let mut frozen = Vec::new();
+    for one in info.iter().filter(|a| a.is_key) {
+        frozen.push((one, KEY_WIDTH));
+    }
+    if let Some(one) = info.iter().find(|a| a.name == *show_field && !a.is_key) {
+        frozen.push((one, SHOW_WIDTH));
+    }
+    let cols: Vec<(&FieldInfo, f32)> = info
+        .iter()
+        .filter(|a| a.export && frozen.iter().all(|(f, _)| f.name != a.name))
+        .map(|a| (a, COL_WIDTH))
+        .collect();
+    let frozen_w: f32 = frozen.iter().map(|(_, w)| w).sum();
+    let total_w: f32 = frozen_w + cols.iter().map(|(_, w)| w).sum::<f32>();
+
+    let id = format!("list_panel_{}", idx);
+    egui::SidePanel::left(id).resizable(false).show(ctx, |ui| {
+        ui.set_width(width);
+        ui.horizontal(|ui| {
+            ui.heading(show_name.as_str());
+            if ui.button("☰").on_hover_text("列表视图").clicked() {
+                back = true;
+            }
+            ui.label(format!("{}/{}行", grid.order.len(), data.len()));
+            if ui.button("清除筛选").clicked() {
+                grid.filter.clear();
+                grid.query.clear();
+            }
+        });
+        query_bar::draw_query_bar(ui, &format!("grid_{}", table_name), &mut grid.query, saved);
+        ui.horizontal(|ui| {
+            if ui
+                .button("全选")
+                .on_hover_text("选中筛选后的所有行")
+                .clicked()
+            {
+                select.select_all(&grid.order);
+            }
+            if ui.button("取消选择").clicked() {
+                select.clear();
+            }
+            let num = select.get_rows(*cur_row).len();
+            if ui.button(format!("✏批量编辑({})", num)).clicked() {
+                batch = true;
+            }
+        });
+        ui.separator();
+
+        let row_h = ui.spacing().interact_size.y + 4.0;
+        let header_h = row_h * 2.0;
+        let rows = grid.order.len();
+        egui::ScrollArea::both()
+            .id_source(format!("grid_view_{}", table_name))
+            .auto_shrink([false; 2])
+            .show_viewport(ui, |ui, viewport| {
+                ui.set_width(total_w);
+                ui.set_height(header_h + rows as f32 * row_h);
+                let origin = ui.max_rect().min;
+                let view = Rect::from_min_size(origin + viewport.min.to_vec2(), viewport.size());
+                let split_x = view.min.x + frozen_w;
+                let split_y = view.min.y + header_h;
+                let frozen_clip =
+                    Rect::from_min_max(pos2(view.min.x, split_y), pos2(split_x, view.max.y));
+                let body_clip = Rect::from_min_max(pos2(split_x, split_y), view.max);
+
+                // 表头固定在顶部, 只绘制可见的行
+                let first = (viewport.min.y / row_h).floor().max(0.0) as usize;
+                let last = ((viewport.max.y / row_h).ceil() as usize + 1).min(rows);
+                for r in first..last {
+                    let row = grid.order[r];
+                    let y = origin.y + header_h + r as f32 * row_h;
+                    let row_rect =
+                        Rect::from_min_max(pos2(view.min.x, y), pos2(view.max.x, y + row_h));
+                    let painter = ui
+                        .painter()
+                        .with_clip_rect(Rect::from_min_max(pos2(view.min.x, split_y), view.max));
+                    if select.is_selected(row, *cur_row) {
+                        painter.rect_filled(
+                            row_rect,
+                            0.0,
+                            ui.visuals().selection.bg_fill.linear_multiply(0.4),
+                        );
+                    } else if r % 2 == 1 {
+                        painter.rect_filled(row_rect, 0.0, ui.visuals().faint_bg_color);
+                    }
+
+                    let map = &data[row];
+                    let mut x = view.min.x;
+                    for (field, w) in &frozen {
+                        let rect = Rect::from_min_size(pos2(x, y), vec2(*w, row_h));
+                        x += w;
+                        let val = utils::map_get_string(map, &field.name, "");
+                        let (c, _) = draw_cell(
+                            ui,
+                            rect,
+                            frozen_clip,
+                            field,
+                            &val,
+                            row,
+                            true,
+                            &mut grid.edit,
+                        );
+                        check_if!(c, click = Some(row as i32));
+                    }
+                    let mut x = origin.x + frozen_w;
+                    for (field, w) in &cols {
+                        let rect = Rect::from_min_size(pos2(x, y), vec2(*w, row_h));
+                        x += w;
+                        check_if!(
+                            rect.max.x < body_clip.min.x || rect.min.x > body_clip.max.x,
+                            continue
+                        );
+                        let val = utils::map_get_string(map, &field.name, "");
+                        let (c, new) =
+                            draw_cell(ui, rect, body_clip, field, &val, row, false, &mut grid.edit);
+                        check_if!(c, click = Some(row as i32));
+                        if let Some(new) = new {
+                            changed.push((row, field.name.clone(), new));
+                        }
+                    }
+                }
+
+                let header_rect = Rect::from_min_max(view.min, pos2(view.max.x, split_y));
+                ui.painter()
+                    .rect_filled(header_rect, 0.0, ui.visuals().extreme_bg_color);
+                let corner_clip = Rect::from_min_max(view.min, pos2(split_x, split_y));
+                let header_clip =
+                    Rect::from_min_max(pos2(split_x, view.min.y), pos2(view.max.x, split_y));
+                let mut x = view.min.x;
+                for (field, w) in &frozen {
+                    let rect = Rect::from_min_size(pos2(x, view.min.y), vec2(*w, header_h));
+                    x += w;
+                    draw_header(ui, rect, corner_clip, field, grid);
+                }
+                let mut x = origin.x + frozen_w;
+                for (field, w) in &cols {
+                    let rect = Rect::from_min_size(pos2(x, view.min.y), vec2(*w, header_h));
+                    x += w;
+                    check_if!(
+                        rect.max.x < header_clip.min.x || rect.min.x > header_clip.max.x,
+                        continue
+                    );
+                    draw_header(ui, rect, header_clip, field, grid);
+                }
+            });
+    });
+    draw_edit_window(ctx, table_name, info, data, key_fields, grid, changed);
+    return (click, back, batch);
+}
@@ -1,11 +1,28 @@
 pub mod action;
 pub mod app_cfg;
+pub mod batch_edit;
+pub mod favourite_window;
+pub mod grid_view;
+pub mod query_bar;
+pub mod search_window;
 pub mod syntax_highlight;
+pub mod templete_editor;
 pub mod theme;
 // mod convert;
 
-use crate::data::{data_field::FieldInfo, data_table::DataTable};
-use crate::{app::app_cfg::AppCfg, error, utils};
+use crate::data::{
+    data_check, data_expr,
+    data_field::FieldInfo,
+    data_option,
+    data_table::{DataTable, EKeyAlloc, ESortType, ListViewCfg, RowSelect},
+    row_meta,
+};
+use crate::{
+    app::app_cfg::AppCfg,
+    error,
+    marco::{check_if, check_some},
+    utils,
+};
 use anyhow::{bail, Result};
 use eframe::{
     egui::{self, RichText},
@@ -14,9 +31,18 @@ use eframe::{
 use egui_notify::Toasts;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, sync::Mutex};
+use std::{
+    collections::HashMap,
+    sync::{atomic::AtomicUsize, Mutex},
+};
 
-use self::action::{ActionList, Location};
+use self::{
+    action::{ActionList, Location},
+    batch_edit::BatchEditor,
+    favourite_window::FavouriteWindow,
+    search_window::{ESearchOp, SearchWindow},
+    templete_editor::{ETempleteOp, TempleteEditor},
+};
 
 lazy_static! {
     pub static ref TEMPLETE_MAP_EXPR: Mutex<HashMap<String, TempleteInfo>> =
@@ -25,6 +51,9 @@ lazy_static! {
         Mutex::new(HashMap::new());
 }
 
+// 模板重新读取后递增, 使按模板识别代码的缓存失效
+pub static TEMPLETE_VERSION: AtomicUsize = AtomicUsize::new(0);
+
 macro_rules! write_cfg {
     ($this:expr, $filename:expr) => {
         let mut current = std::env::current_exe()?;
@@ -141,17 +170,25 @@ pub struct SkillEditorApp {
     link_src_field: String,
 
     console_show: bool,
+    option_dirty: bool,
 
     cfg: AppCfg,
+    templete_editor: TempleteEditor,
+    batch_editor: BatchEditor,
+    search_window: SearchWindow,
+    favourite_window: FavouriteWindow,
     toasts: Toasts,
 
     hotkey_redo: bool,
     hotkey_undo: bool,
+    // ctrl+c/ctrl+v 作用的表格, 即最后点击的表格
+    focus_table: String,
 }
 
 impl SkillEditorApp {
     fn apply_action(&mut self, action: action::DataAction) {
         self.data_history.apply(action, &mut self.data_table);
+        self.bump_revision();
         let action = action::MoveLocationAction {
             old_location: self.last_location.clone(),
             new_location: self.cur_location.clone(),
@@ -164,6 +201,7 @@ impl SkillEditorApp {
 
     fn undo(&mut self) {
         let info = self.data_history.undo(&mut self.data_table);
+        self.bump_revision();
         if self.cfg.show_undo && info.is_some() {
             utils::toast(&mut self.toasts, "SHORT", info.unwrap());
         }
@@ -172,11 +210,20 @@ impl SkillEditorApp {
 
     fn redo(&mut self) {
         let info = self.data_history.redo(&mut self.data_table);
+        self.bump_revision();
         if self.cfg.show_undo && info.is_some() {
             utils::toast(&mut self.toasts, "SHORT", info.unwrap());
         }
         self.location_history.redo(&mut self.cur_location);
     }
+
+    // 操作可能修改多个表, 所有表的修改次数都增加
+    fn bump_revision(&mut self) {
+        self.option_dirty = true;
+        for data_table in self.data_table.values_mut() {
+            data_table.revision += 1;
+        }
+    }
 }
 
 impl SkillEditorApp {
@@ -291,6 +338,53 @@ impl SkillEditorApp {
         return Ok(());
     }
 
+    fn load_enum_config(&mut self) -> Result<()> {
+        #[derive(Serialize, Deserialize)]
+        struct EnumConfig {
+            name: String,
+            opt: Vec<String>,
+
+            #[serde(default)]
+            export_name: bool,
+        }
+
+        let data: Vec<EnumConfig> =
+            utils::load_dir_excel_cfg("save_data/editor_enum", data_option::ENUM_TABLE)?;
+        let list = data
+            .into_iter()
+            .map(|a| (a.name, a.opt, a.export_name))
+            .collect();
+        data_option::load_enum_def(list);
+        return Ok(());
+    }
+
+    fn load_api_config(&mut self) -> Result<()> {
+        #[derive(Serialize, Deserialize)]
+        struct ApiConfig {
+            name: String,
+
+            #[serde(default)]
+            params: String,
+            #[serde(default)]
+            desc: String,
+        }
+
+        let data: Vec<ApiConfig> =
+            utils::load_dir_excel_cfg("save_data/editor_api", data_expr::API_TABLE)?;
+        let list = data
+            .into_iter()
+            .map(|a| data_expr::ApiInfo {
+                name: a.name,
+                params: a.params,
+                desc: a.desc,
+            })
+            .collect();
+        data_expr::load_api(list);
+        data_expr::load_mock_api()?;
+        data_expr::load_test()?;
+        return Ok(());
+    }
+
     fn load_field_config(&mut self) -> Result<()> {
         #[derive(Serialize, Deserialize, Debug)]
         struct FieldConfig {
@@ -312,7 +406,7 @@ impl SkillEditorApp {
             utils::load_dir_excel_cfg("save_data/editor_field", "editor_field")?;
 
         for one in data {
-            let field = FieldInfo::parse(
+            let mut field = FieldInfo::parse(
                 one.name,
                 one.title,
                 one.desc,
@@ -325,6 +419,7 @@ impl SkillEditorApp {
                 one.export,
                 one.output_header,
             )?;
+            field.table_key = one.table_key.clone();
             if self.field_group.contains_key(&one.table_key) {
                 let group = self.field_group.get_mut(&one.table_key).unwrap();
                 group.push(field.clone());
@@ -353,6 +448,10 @@ impl SkillEditorApp {
             post_exec: String,
             #[serde(default)]
             reload_editor: bool,
+            #[serde(default)]
+            key_alloc: String,
+            #[serde(default)]
+            order_field: String,
         }
 
         #[derive(Serialize, Deserialize)]
@@ -413,10 +512,27 @@ impl SkillEditorApp {
                 let field = self.field_group.get_mut(&one.table_key).unwrap();
                 field.insert(0, sub_group_field);
                 field.insert(0, group_field);
+                field.extend(row_meta::meta_fields()?);
                 for one in field {
                     info.push(one.clone());
                 }
             }
+            // 顺序字段没有在字段配置中定义时, 作为编辑器内部字段保存, 不导出
+            if !one.order_field.is_empty() && !info.iter().any(|a| a.name == one.order_field) {
+                info.push(FieldInfo::parse(
+                    one.order_field.clone(),
+                    "顺序".to_string(),
+                    "编辑器列表顺序".to_string(),
+                    "分组".to_string(),
+                    "N".to_string(),
+                    "Text".to_string(),
+                    Vec::new(),
+                    String::new(),
+                    String::new(),
+                    false,
+                    Vec::new(),
+                )?);
+            }
             let mut templete = Vec::new();
             if self.templete.contains_key(&one.table_key) {
                 let t = self.templete.get(&one.table_key).unwrap();
@@ -438,6 +554,11 @@ impl SkillEditorApp {
                 one.post_exec,
             );
             data_table.reload_editor = one.reload_editor;
+            data_table.order_field = one.order_field;
+            data_table.key_alloc = match EKeyAlloc::parse(&one.key_alloc) {
+                Ok(alloc) => alloc,
+                Err(e) => bail!("表格[{}]: {}", one.table_key, e),
+            };
             self.data_table.insert(one.table_key.clone(), data_table);
         }
 
@@ -462,6 +583,7 @@ impl SkillEditorApp {
             templete_type: String,
         }
 
+        TEMPLETE_VERSION.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
         let mut templete_map = TEMPLETE_MAP_EXPR.lock().unwrap();
         templete_map.clear();
         let mut templete_sub_field_map = TEMPLETE_MAP_SUB_FIELD.lock().unwrap();
@@ -505,6 +627,22 @@ impl SkillEditorApp {
         return Ok(());
     }
 
+    // 模板管理窗口保存后只重新读取模板, 不影响未保存的数据
+    fn reload_templete(&mut self) {
+        self.templete.clear();
+        if let Err(e) = self.load_templete() {
+            utils::msg(format!("读取模板失败:{:?}", e), "错误".to_string());
+        }
+        for (name, data_table) in self.data_table.iter_mut() {
+            data_table.templete = match self.templete.get(name) {
+                Some(list) => list.clone(),
+                None => Vec::new(),
+            };
+            data_table.templete_idx = 0;
+        }
+        data_check::clear_cache();
+    }
+
     pub fn create_default_config(&self) -> Result<()> {
         write_cfg!(self, "save_data/editor_field/编辑器_编辑器配置.xlsx");
         write_cfg!(self, "save_data/editor_tab/编辑器_编辑器配置.xlsx");
@@ -528,6 +666,10 @@ impl SkillEditorApp {
             self.create_default_config()?;
         }
 
+        println!("读取枚举配置");
+        self.load_enum_config()?;
+        println!("读取接口配置");
+        self.load_api_config()?;
         println!("读取字段配置");
         self.load_field_config()?;
         println!("读取模板配置");
@@ -548,6 +690,9 @@ impl SkillEditorApp {
         self.inited = false;
         let ret = self._load_config();
         self.inited = true;
+        self.option_dirty = true;
+        // 字段配置可能变化, 选项不变时 refresh_table_option 不会清空检查缓存
+        data_check::clear_cache();
         match ret {
             Ok(_) => {}
             Err(e) => utils::msg(format!("读取配置失败:{:?}", e), "错误".to_string()),
@@ -557,6 +702,21 @@ impl SkillEditorApp {
 
 // UI 相关接口
 impl SkillEditorApp {
+    fn export_enum(&mut self) {
+        let path = rfd::FileDialog::new()
+            .add_filter("lua", &["lua"])
+            .save_file();
+        let path = check_some!(path, return);
+        match std::fs::write(path, data_option::gen_enum_lua()) {
+            Ok(_) => utils::toast(&mut self.toasts, "SUCC", "导出枚举定义成功"),
+            Err(e) => utils::toast(
+                &mut self.toasts,
+                "ERRO",
+                format!("导出枚举定义失败: {:?}", e),
+            ),
+        }
+    }
+
     fn switch_console(&mut self) {
         self.console_show = !self.console_show;
         utils::show_console_window(self.console_show);
@@ -570,11 +730,20 @@ impl SkillEditorApp {
                 text_button!(ui, "↩撤销(Z)", self.undo());
                 text_button!(ui, "↪重做(Y)", self.redo());
                 text_button!(ui, "🔧应用配置", self.cfg.show());
+                text_button!(ui, "📤导出枚举", self.export_enum());
+                text_button!(ui, "📝模板管理", self.templete_editor.show());
+                text_button!(ui, "🔍全局搜索", self.search_window.show());
+                text_button!(ui, "⭐收藏夹", self.favourite_window.show());
                 text_button!(ui, "🖥控制台", self.switch_console());
 
                 if ui.input(|i| i.key_pressed(egui::Key::S) && i.modifiers.ctrl) {
                     self.save_data(ui.input(|i| i.modifiers.shift));
                 }
+                if ui
+                    .input(|i| i.key_pressed(egui::Key::F) && i.modifiers.ctrl && i.modifiers.shift)
+                {
+                    self.search_window.show();
+                }
                 
                 let mut list: Vec<(String, Vec<MenuInfo>)> = Vec::new();
                 for one in &self.menus {
@@ -659,6 +828,19 @@ impl SkillEditorApp {
         });
     }
 
+    // 列表的显示顺序, 用于 shift 多选
+    fn get_list_order(
+        list: &HashMap<String, HashMap<String, Vec<(String, i32, i32, bool)>>>,
+    ) -> Vec<usize> {
+        let mut ret = Vec::new();
+        for (_, one) in list.iter().sorted_by(|a, b| utils::natural_cmp(a.0, b.0)) {
+            for (_, two) in one.iter().sorted_by(|a, b| utils::natural_cmp(a.0, b.0)) {
+                ret.extend(two.iter().map(|a| a.1 as usize));
+            }
+        }
+        return ret;
+    }
+
     fn get_list_next_idx(
         list: &HashMap<String, HashMap<String, Vec<(String, i32, i32, bool)>>>,
         cur: i32,
@@ -741,20 +923,81 @@ impl SkillEditorApp {
             if !click_table.is_empty() && click_table == tab_info.master_table {
                 data_table.update_cur_row(&cur_master_val);
             }
+
+            // 表格视图占用列表和详情两个面板的宽度
+            if data_table.grid.show {
+                let mut grid_changed = Vec::new();
+                let saved = self
+                    .cfg
+                    .saved_query
+                    .entry(tab_info.tab.clone())
+                    .or_default();
+                let (click, back, batch) = grid_view::draw_grid(
+                    ctx,
+                    idx,
+                    width + 16.0,
+                    data_table,
+                    saved,
+                    &mut grid_changed,
+                );
+                if let Some(row) = click {
+                    let m = ctx.input(|i| i.modifiers);
+                    let cur = data_table.cur_row as usize;
+                    let order = data_table.grid.order.clone();
+                    data_table
+                        .select
+                        .click(row as usize, cur, &order, m.command, m.shift);
+                    data_table.cur_row = row;
+                    click_table = data_table.table_name.clone();
+                }
+                if back {
+                    data_table.grid.show = false;
+                }
+                if batch {
+                    self.batch_editor.show(&data_table.table_name);
+                }
+                let name = data_table.table_name.clone();
+                for (row, k, v) in grid_changed {
+                    ops.push(action::UpdateAction::new(
+                        &self.data_table,
+                        &name,
+                        row,
+                        &k,
+                        &v,
+                    ));
+                }
+                continue;
+            }
+            let mut view = self
+                .cfg
+                .list_view
+                .get(&tab_info.tab)
+                .cloned()
+                .unwrap_or_default();
             let list = data_table.get_show_name_list(
                 &data_table.master_field,
                 &cur_master_val,
                 show_all_bool,
                 &data_table.search,
+                &view,
             );
-            let (click, op, create_tmp) = SkillEditorApp::draw_list(
+            let (click, op, create_tmp, group_select, reorder, meta) = SkillEditorApp::draw_list(
                 ctx,
                 idx,
                 width * 0.35,
                 &data_table.show_name,
                 &list,
+                &data_table.data,
                 data_table.cur_row,
+                &data_table.select,
+                &data_table.info,
+                &data_table.order_field,
+                &mut view,
                 &mut data_table.search,
+                self.cfg
+                    .saved_query
+                    .entry(tab_info.tab.clone())
+                    .or_default(),
                 &mut show_all,
                 &data_table.templete,
                 &mut data_table.templete_idx,
@@ -762,10 +1005,28 @@ impl SkillEditorApp {
             if show_all.is_some() {
                 data_table.show_all = show_all.unwrap();
             }
+            if self.cfg.list_view.get(&tab_info.tab) != Some(&view) {
+                self.cfg.list_view.insert(tab_info.tab.clone(), view);
+            }
             if click.is_some() {
-                data_table.cur_row = click.unwrap().clone();
+                let row = click.unwrap();
+                let m = ctx.input(|i| i.modifiers);
+                let cur = data_table.cur_row as usize;
+                let order = SkillEditorApp::get_list_order(&list);
+                data_table
+                    .select
+                    .click(row as usize, cur, &order, m.command, m.shift);
+                data_table.cur_row = row;
                 click_table = data_table.table_name.clone();
             }
+            if !group_select.is_empty() {
+                data_table.select.select_all(&group_select);
+            }
+            if op == 8 {
+                data_table
+                    .select
+                    .select_all(&SkillEditorApp::get_list_order(&list));
+            }
             let mut changed = HashMap::new();
             let link_info =
                 SkillEditorApp::draw_data(ctx, idx, data_table, width * (1.0 - 0.35), &mut changed);
@@ -784,6 +1045,26 @@ impl SkillEditorApp {
 
             let data_table = self.data_table.get(&tab_info.tab).unwrap();
 
+            if !reorder.is_empty() {
+                let name = data_table.table_name.clone();
+                let list = data_table
+                    .reorder_rows(&reorder)
+                    .into_iter()
+                    .map(|(row, k, v)| (name.clone(), row, k, v))
+                    .collect();
+                let desc = format!("调整[{}]顺序", data_table.show_name);
+                ops.push(action::CompoundAction::update(&self.data_table, desc, list));
+            }
+            if !meta.is_empty() {
+                let name = data_table.table_name.clone();
+                let list = meta
+                    .into_iter()
+                    .map(|(row, k, v)| (name.clone(), row, k, v))
+                    .collect();
+                let desc = format!("修改[{}]标记", data_table.show_name);
+                ops.push(action::CompoundAction::update(&self.data_table, desc, list));
+            }
+
             for (k, v) in changed {
                 let name = data_table.table_name.clone();
                 let row_idx = data_table.cur_row.clone() as usize;
@@ -816,12 +1097,14 @@ impl SkillEditorApp {
             }
 
             if op == 1 {
-                let data = data_table.create_row(&cur_master_val, 0);
-                ops.push(action::AddAction::new(
-                    &self.data_table,
-                    &data_table.table_name,
-                    data,
-                ));
+                match data_table.create_row(&cur_master_val, 0) {
+                    Ok(data) => ops.push(action::AddAction::new(
+                        &self.data_table,
+                        &data_table.table_name,
+                        data,
+                    )),
+                    Err(e) => utils::toast(&mut self.toasts, "ERRO", format!("新增失败: {}", e)),
+                }
             }
 
             if op == 2 {
@@ -868,21 +1151,40 @@ impl SkillEditorApp {
             }
 
             if op == 5 {
-                let copy_master_val = data_table.get_cur_key();
-                let data = data_table.copy_cur_row(&cur_master_val).unwrap();
-                let mut copy_table = Vec::new();
-                for one in &cfg.tabs {
-                    if one.master_table == tab_info.tab {
-                        copy_table.push(one.tab.clone());
+                match data_table.copy_cur_row(&cur_master_val) {
+                    Ok(Some(data)) => {
+                        let copy_master_val = data_table.get_cur_key();
+                        let mut copy_table = Vec::new();
+                        for one in &cfg.tabs {
+                            if one.master_table == tab_info.tab {
+                                copy_table.push(one.tab.clone());
+                            }
+                        }
+                        let mut err = Vec::new();
+                        ops.push(action::CopyAction::new(
+                            &self.data_table,
+                            &data_table.table_name,
+                            data,
+                            copy_table,
+                            copy_master_val,
+                            &mut err,
+                        ));
+                        for msg in err {
+                            utils::toast(&mut self.toasts, "ERRO", format!("复制失败: {}", msg));
+                        }
                     }
+                    Ok(None) => {}
+                    Err(e) => utils::toast(&mut self.toasts, "ERRO", format!("复制失败: {}", e)),
                 }
-                ops.push(action::CopyAction::new(
-                    &self.data_table,
-                    &data_table.table_name,
-                    data,
-                    copy_table,
-                    copy_master_val,
-                ));
+            }
+
+            if op == 6 {
+                let table = data_table.table_name.clone();
+                self.data_table.get_mut(&table).unwrap().grid.show = true;
+            }
+
+            if op == 7 {
+                self.batch_editor.show(&tab_info.tab);
             }
         }
 
@@ -891,6 +1193,81 @@ impl SkillEditorApp {
                 self.apply_action(op.unwrap());
             }
         }
+        if !click_table.is_empty() {
+            self.focus_table = click_table;
+        }
+    }
+
+    // 没有输入框获得焦点时, ctrl+c 复制选中的行, ctrl+v 粘贴 TSV 更新或新增行
+    fn handle_clipboard(&mut self, ctx: &egui::Context) {
+        check_if!(ctx.memory(|m| m.focus().is_some()), return);
+        let mut copy = false;
+        let mut paste = None;
+        ctx.input(|i| {
+            for one in &i.events {
+                match one {
+                    egui::Event::Copy => copy = true,
+                    egui::Event::Paste(text) => paste = Some(text.clone()),
+                    _ => {}
+                }
+            }
+        });
+        check_if!(!copy && paste.is_none(), return);
+        let table = self.focus_table.clone();
+        let data_table = check_some!(self.data_table.get(&table), return);
+
+        if copy {
+            let rows = data_table.select.get_rows(data_table.cur_row);
+            check_if!(rows.is_empty(), return);
+            let fields = if data_table.grid.show {
+                data_table.grid.copy_cols.clone()
+            } else {
+                Vec::new()
+            };
+            let text = data_table.to_tsv(&rows, &fields);
+            ctx.output_mut(|o| o.copied_text = text);
+            let msg = format!("复制了[{}]{}行", data_table.show_name, rows.len());
+            utils::toast(&mut self.toasts, "SUCC", msg);
+        }
+
+        let text = check_some!(paste, return);
+        let master_val = self
+            .tab_cfg
+            .get(self.cur_location.cur_view)
+            .and_then(|cfg| cfg.tabs.iter().find(|t| t.tab == table))
+            .and_then(|t| self.data_table.get(&t.master_table))
+            .map(|a| a.get_cur_key())
+            .unwrap_or_default();
+        let (update, add) = match data_table.parse_tsv(&text, &master_val) {
+            Ok(ret) => ret,
+            Err(e) => {
+                utils::toast(&mut self.toasts, "ERRO", format!("粘贴失败: {}", e));
+                return;
+            }
+        };
+        let update_rows = update.iter().map(|a| a.0).unique().count();
+        let desc = format!("粘贴: 更新{}行, 新增{}行", update_rows, add.len());
+        let list = update
+            .into_iter()
+            .map(|(row, k, v)| (table.clone(), row, k, v))
+            .collect();
+        let mut actions = Vec::new();
+        if let Some(one) = action::CompoundAction::update(&self.data_table, desc.clone(), list) {
+            actions.push(one);
+        }
+        for one in add {
+            actions.push(check_some!(
+                action::AddAction::new(&self.data_table, &table, one),
+                continue
+            ));
+        }
+        match action::CompoundAction::new(desc.clone(), actions) {
+            Some(one) => {
+                self.apply_action(one);
+                utils::toast(&mut self.toasts, "SUCC", desc);
+            }
+            None => utils::toast(&mut self.toasts, "INFO", "粘贴的内容与当前数据相同"),
+        }
     }
 
     fn draw_list(
@@ -899,13 +1276,32 @@ impl SkillEditorApp {
         width: f32,
         title: &str,
         list: &HashMap<String, HashMap<String, Vec<(String, i32, i32, bool)>>>,
+        data: &Vec<HashMap<String, String>>,
         cur: i32,
+        select: &RowSelect,
+        info: &Vec<FieldInfo>,
+        order_field: &String,
+        view: &mut ListViewCfg,
         search: &mut String,
+        saved: &mut Vec<(String, String)>,
         show_all: &mut Option<bool>,
         templete: &Vec<TempleteInfo>,
         tmp_idx: &mut i32,
-    ) -> (Option<i32>, i32, String) {
+    ) -> (
+        Option<i32>,
+        i32,
+        String,
+        Vec<usize>,
+        Vec<usize>,
+        Vec<(usize, String, String)>,
+    ) {
         let mut ret = None;
+        let mut group_select = Vec::new();
+        // 按手动顺序显示时才能调整顺序
+        let order = !order_field.is_empty() && view.sort.is_empty() && !view.desc;
+        let mut reorder = Vec::new();
+        // 右键修改的标记 (行, 字段, 值)
+        let mut meta = Vec::new();
         let mut op = 0;
         let id = format!("list_panel_{}", idx);
         let mut all = false;
@@ -922,6 +1318,14 @@ impl SkillEditorApp {
                 text_button!(ui, "📋", "复制配置", op = 3);
                 text_button!(ui, "📥", "导入配置", op = 4);
                 text_button!(ui, "📤", "导出配置", op = 5);
+                text_button!(ui, "▦", "表格视图", op = 6);
+                text_button!(ui, "✏", "批量编辑(ctrl/shift多选)", op = 7);
+                text_button!(ui, "☑", "选中所有筛选结果", op = 8);
+                ui.menu_button("⇅", |ui| {
+                    SkillEditorApp::draw_list_view_cfg(ui, idx, info, order_field, view);
+                })
+                .response
+                .on_hover_text("分组和排序");
 
                 if show_all.is_some() {
                     all = show_all.unwrap();
@@ -957,40 +1361,409 @@ impl SkillEditorApp {
                     }
                 });
             }
-            ui.horizontal(|ui| {
-                ui.text_edit_singleline(search);
-            });
+            query_bar::draw_query_bar(ui, &format!("list_{}", title), search, saved);
 
             ui.separator();
 
             egui::ScrollArea::vertical()
                 .auto_shrink([false; 2])
                 .show(ui, |ui| {
-                    for (group, one) in list.iter().sorted_by_key(|a| a.0) {
-                        egui::CollapsingHeader::new(group)
+                    // 收藏的行在最上面单独列一组, 原分组中仍然保留
+                    let fav = SkillEditorApp::get_favourite_rows(list, data);
+                    if !fav.is_empty() {
+                        egui::CollapsingHeader::new(format!("⭐收藏 ({})", fav.len()))
+                            .id_source(format!("{}_favourite", title))
                             .default_open(true)
                             .show(ui, |ui| {
-                                for (sub_group, two) in one.iter().sorted_by_key(|a| a.0) {
-                                    egui::CollapsingHeader::new(sub_group)
-                                        .default_open(true)
-                                        .show(ui, |ui| {
-                                            for (name, idx, _key_num, dup) in two {
-                                                let mut txt = RichText::new(name);
-                                                if *dup {
-                                                    txt = txt.color(Color32::RED);
-                                                }
-                                                if ui.selectable_label(*idx == cur, txt).clicked() {
-                                                    ret = Some(idx.clone());
-                                                }
+                                SkillEditorApp::draw_list_rows(
+                                    ui,
+                                    &fav,
+                                    data,
+                                    cur,
+                                    select,
+                                    false,
+                                    &mut ret,
+                                    &mut reorder,
+                                    &mut meta,
+                                );
+                            });
+                    }
+                    for (group, one) in list.iter().sorted_by(|a, b| utils::natural_cmp(a.0, b.0)) {
+                        // 平铺或只有一层分组时, 空的分组名不显示标题
+                        if group.is_empty() {
+                            for (_, two) in
+                                one.iter().sorted_by(|a, b| utils::natural_cmp(a.0, b.0))
+                            {
+                                SkillEditorApp::draw_list_rows(
+                                    ui,
+                                    two,
+                                    data,
+                                    cur,
+                                    select,
+                                    order,
+                                    &mut ret,
+                                    &mut reorder,
+                                    &mut meta,
+                                );
+                            }
+                            continue;
+                        }
+                        let resp =
+                            egui::CollapsingHeader::new(group)
+                                .default_open(true)
+                                .show(ui, |ui| {
+                                    for (sub_group, two) in
+                                        one.iter().sorted_by(|a, b| utils::natural_cmp(a.0, b.0))
+                                    {
+                                        if sub_group.is_empty() {
+                                            SkillEditorApp::draw_list_rows(
+                                                ui,
+                                                two,
+                                                data,
+                                                cur,
+                                                select,
+                                                order,
+                                                &mut ret,
+                                                &mut reorder,
+                                                &mut meta,
+                                            );
+                                            continue;
+                                        }
+                                        let resp = egui::CollapsingHeader::new(sub_group)
+                                            .default_open(true)
+                                            .show(ui, |ui| {
+                                                SkillEditorApp::draw_list_rows(
+                                                    ui,
+                                                    two,
+                                                    data,
+                                                    cur,
+                                                    select,
+                                                    order,
+                                                    &mut ret,
+                                                    &mut reorder,
+                                                    &mut meta,
+                                                );
+                                            });
+                                        resp.header_response.context_menu(|ui| {
+                                            if ui.button("全选本组").clicked() {
+                                                group_select =
+                                                    two.iter().map(|a| a.1 as usize).collect();
+                                                ui.close_menu();
                                             }
                                         });
-                                }
-                            });
+                                    }
+                                });
+                        resp.header_response.context_menu(|ui| {
+                            if ui.button("全选本组").clicked() {
+                                group_select = one
+                                    .values()
+                                    .flat_map(|a| a.iter().map(|b| b.1 as usize))
+                                    .collect();
+                                ui.close_menu();
+                            }
+                        });
                     }
                 });
         });
 
-        return (ret, op, create_templete);
+        return (ret, op, create_templete, group_select, reorder, meta);
+    }
+
+    // 按列表的显示顺序收集收藏的行
+    fn get_favourite_rows(
+        list: &HashMap<String, HashMap<String, Vec<(String, i32, i32, bool)>>>,
+        data: &Vec<HashMap<String, String>>,
+    ) -> Vec<(String, i32, i32, bool)> {
+        let mut ret = Vec::new();
+        for (_, one) in list.iter().sorted_by(|a, b| utils::natural_cmp(a.0, b.0)) {
+            for (_, two) in one.iter().sorted_by(|a, b| utils::natural_cmp(a.0, b.0)) {
+                for row in two {
+                    let one = check_some!(data.get(row.1 as usize), continue);
+                    check_if!(!row_meta::is_favourite(one), continue);
+                    ret.push(row.clone());
+                }
+            }
+        }
+        return ret;
+    }
+
+    fn draw_list_rows(
+        ui: &mut egui::Ui,
+        list: &Vec<(String, i32, i32, bool)>,
+        data: &Vec<HashMap<String, String>>,
+        cur: i32,
+        select: &RowSelect,
+        order: bool,
+        ret: &mut Option<i32>,
+        reorder: &mut Vec<usize>,
+        meta: &mut Vec<(usize, String, String)>,
+    ) {
+        // 拖动中的行, 松开后的下一帧清除
+        let drag_id = egui::Id::new("list_row_drag");
+        let (down, released) = ui.input(|i| (i.pointer.any_down(), i.pointer.any_released()));
+        let dragging = ui.data_mut(|d| d.get_temp::<i32>(drag_id));
+        if !down && !released {
+            ui.data_mut(|d| d.remove::<i32>(drag_id));
+        }
+
+        let mut moved = None;
+        for (pos, (name, idx, _key_num, dup)) in list.iter().enumerate() {
+            let row = check_some!(data.get(*idx as usize), continue);
+            let name = if row_meta::is_favourite(row) {
+                format!("⭐{}", name)
+            } else {
+                name.clone()
+            };
+            let mut txt = RichText::new(name);
+            if *dup {
+                txt = txt.color(Color32::RED);
+            }
+            let selected = select.is_selected(*idx as usize, cur);
+            let resp = ui
+                .horizontal(|ui| {
+                    ui.spacing_mut().item_spacing.x = 4.0;
+                    // 颜色标记画在行首
+                    let size = egui::vec2(3.0, ui.spacing().interact_size.y);
+                    let (bar, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+                    if let Some(color) = row_meta::get_color(row) {
+                        ui.painter().rect_filled(bar, 1.0, color);
+                    }
+                    let resp = ui.selectable_label(selected, txt);
+                    for tag in row_meta::get_tags(row) {
+                        SkillEditorApp::draw_tag_chip(ui, &tag);
+                    }
+                    resp
+                })
+                .inner;
+            if resp.clicked() {
+                *ret = Some(idx.clone());
+            }
+
+            let last = list.len() - 1;
+            let resp = resp.context_menu(|ui| {
+                SkillEditorApp::draw_row_meta_menu(ui, *idx as usize, row, meta);
+                check_if!(!order, return);
+                ui.separator();
+                let mut to = None;
+                if ui
+                    .add_enabled(pos > 0, egui::Button::new("⏫ 移到最前"))
+                    .clicked()
+                {
+                    to = Some(0);
+                }
+                if ui
+                    .add_enabled(pos > 0, egui::Button::new("⏶ 上移"))
+                    .clicked()
+                {
+                    to = Some(pos - 1);
+                }
+                if ui
+                    .add_enabled(pos < last, egui::Button::new("⏷ 下移"))
+                    .clicked()
+                {
+                    to = Some(pos + 1);
+                }
+                if ui
+                    .add_enabled(pos < last, egui::Button::new("⏬ 移到最后"))
+                    .clicked()
+                {
+                    to = Some(last);
+                }
+                if to.is_some() {
+                    moved = to.map(|to| (pos, to));
+                    ui.close_menu();
+                }
+            });
+            check_if!(!order, continue);
+
+            let rect = resp.rect;
+            let drag = ui.interact(rect, resp.id.with("drag"), egui::Sense::drag());
+            if drag.drag_started() {
+                ui.data_mut(|d| d.insert_temp(drag_id, *idx));
+            }
+            let from = check_some!(dragging, continue);
+            let from = check_some!(list.iter().position(|a| a.1 == from), continue);
+            check_if!(!ui.rect_contains_pointer(rect), continue);
+            ui.output_mut(|o| o.cursor_icon = egui::CursorIcon::Grabbing);
+            // 目标位置画一条线, 向下拖时放在目标行后面
+            let y = if from < pos {
+                rect.bottom()
+            } else {
+                rect.top()
+            };
+            let stroke = ui.visuals().selection.stroke;
+            ui.painter().hline(rect.x_range(), y, stroke);
+            if released && from != pos {
+                moved = Some((from, pos));
+            }
+        }
+
+        let (from, to) = check_some!(moved, return);
+        let mut rows: Vec<usize> = list.iter().map(|a| a.1 as usize).collect();
+        let one = rows.remove(from);
+        rows.insert(to, one);
+        *reorder = rows;
+    }
+
+    fn draw_tag_chip(ui: &mut egui::Ui, tag: &str) {
+        egui::Frame::none()
+            .fill(ui.visuals().widgets.inactive.bg_fill)
+            .rounding(8.0)
+            .inner_margin(egui::style::Margin::symmetric(4.0, 0.0))
+            .show(ui, |ui| {
+                ui.label(RichText::new(tag).small());
+            });
+    }
+
+    // 列表右键菜单中的收藏/颜色/标签, 修改的值放到 meta 中
+    fn draw_row_meta_menu(
+        ui: &mut egui::Ui,
+        idx: usize,
+        row: &HashMap<String, String>,
+        meta: &mut Vec<(usize, String, String)>,
+    ) {
+        let fav = row_meta::is_favourite(row);
+        let txt = if fav {
+            "☆ 取消收藏"
+        } else {
+            "⭐ 收藏"
+        };
+        if ui.button(txt).clicked() {
+            let val = if fav { "false" } else { "True" };
+            meta.push((idx, row_meta::FIELD_FAV.to_string(), val.to_string()));
+            ui.close_menu();
+        }
+        ui.menu_button("🎨 颜色", |ui| {
+            if ui.button("无").clicked() {
+                meta.push((idx, row_meta::FIELD_COLOR.to_string(), String::new()));
+                ui.close_menu();
+            }
+            for (val, show, color) in row_meta::ROW_COLORS {
+                let txt = RichText::new(format!("⏺ {}", show)).color(color);
+                if ui.button(txt).clicked() {
+                    meta.push((idx, row_meta::FIELD_COLOR.to_string(), val.to_string()));
+                    ui.close_menu();
+                }
+            }
+        });
+        ui.menu_button("🏷 标签", |ui| {
+            let mut tags = row_meta::get_tags(row);
+            let mut changed = false;
+            let mut del = None;
+            for (i, tag) in tags.iter().enumerate() {
+                if ui.button(format!("❌ {}", tag)).clicked() {
+                    del = Some(i);
+                }
+            }
+            if let Some(i) = del {
+                tags.remove(i);
+                changed = true;
+            }
+            // 输入的标签放在 egui 的 memory 中
+            let input_id = egui::Id::new("list_row_tag_input");
+            let mut input = ui.data_mut(|d| d.get_temp::<String>(input_id).unwrap_or_default());
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut input)
+                        .hint_text("新标签")
+                        .desired_width(100.0),
+                );
+                let new = row_meta::split_tags(&input);
+                if ui
+                    .add_enabled(!new.is_empty(), egui::Button::new("添加"))
+                    .clicked()
+                {
+                    for one in new {
+                        check_if!(tags.contains(&one), continue);
+                        tags.push(one);
+                    }
+                    input.clear();
+                    changed = true;
+                }
+            });
+            ui.data_mut(|d| d.insert_temp(input_id, input));
+            if changed {
+                meta.push((
+                    idx,
+                    row_meta::FIELD_TAGS.to_string(),
+                    row_meta::join_tags(&tags),
+                ));
+                ui.close_menu();
+            }
+        });
+    }
+
+    fn draw_list_view_cfg(
+        ui: &mut egui::Ui,
+        idx: i32,
+        info: &Vec<FieldInfo>,
+        order_field: &String,
+        view: &mut ListViewCfg,
+    ) {
+        let default_sort = if order_field.is_empty() {
+            "主键"
+        } else {
+            "手动顺序"
+        };
+        let field_name = |name: &String, empty: &str| -> String {
+            check_if!(name.is_empty(), return empty.to_string());
+            match info.iter().find(|a| a.name == *name) {
+                Some(f) => format!("{}({})", f.title, f.name),
+                None => name.clone(),
+            }
+        };
+        egui::Grid::new(format!("list_view_cfg_{}", idx))
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("分组");
+                ui.add_enabled_ui(!view.flatten, |ui| {
+                    egui::ComboBox::from_id_source(format!("list_view_group_{}", idx))
+                        .selected_text(field_name(&view.group, "默认分组"))
+                        .width(180.0)
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut view.group, String::new(), "默认分组");
+                            for one in info {
+                                let txt = format!("{}({})", one.title, one.name);
+                                ui.selectable_value(&mut view.group, one.name.clone(), txt);
+                            }
+                        });
+                });
+                ui.end_row();
+                ui.label("");
+                ui.checkbox(&mut view.flatten, "不分组");
+                ui.end_row();
+
+                ui.label("排序");
+                egui::ComboBox::from_id_source(format!("list_view_sort_{}", idx))
+                    .selected_text(field_name(&view.sort, default_sort))
+                    .width(180.0)
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut view.sort, String::new(), default_sort)
+                            .on_hover_text("有顺序字段时可以在列表中右键或拖动调整顺序");
+                        for one in info {
+                            let txt = format!("{}({})", one.title, one.name);
+                            ui.selectable_value(&mut view.sort, one.name.clone(), txt);
+                        }
+                    });
+                ui.end_row();
+                ui.label("方式");
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut view.sort_type, ESortType::Auto, "自动")
+                        .on_hover_text("数字字段按数值, 其他按自然排序");
+                    ui.radio_value(&mut view.sort_type, ESortType::Number, "数字");
+                    ui.radio_value(&mut view.sort_type, ESortType::Str, "字符串");
+                    ui.radio_value(&mut view.sort_type, ESortType::Natural, "自然")
+                        .on_hover_text("字符串中的数字按数值比较, 如 a2 < a10");
+                });
+                ui.end_row();
+                ui.label("");
+                ui.checkbox(&mut view.desc, "降序");
+                ui.end_row();
+            });
+        if ui.button("恢复默认").clicked() {
+            *view = ListViewCfg::default();
+            ui.close_menu();
+        }
     }
 
     fn draw_data(
@@ -1215,7 +1988,7 @@ impl SkillEditorApp {
         let mut link_idx: i32 = -1;
         let mut idx = 0;
         for row in &data_table.data {
-            let key = utils::map_get_string(row, &data_table.key_name, "");
+            let key = data_table.get_row_key(row);
             if key == link_val {
                 link_idx = idx;
                 break;
@@ -1230,7 +2003,7 @@ impl SkillEditorApp {
             )));
         }
         let map = map.unwrap();
-        let key = utils::map_get_string(map, &data_table.key_name, "");
+        let key = link_val.clone();
         let show = utils::map_get_string(map, &data_table.show_field, "");
         let title = format!("关联表:{} - [{}]{}", data_table.show_name, key, show);
         let (show, click) = SkillEditorApp::_draw_link_window(
@@ -1245,6 +2018,8 @@ impl SkillEditorApp {
         if click.is_some() {
             data_table.cur = click.unwrap();
         }
+        // 关联表窗口直接修改数据, 需要同步刷新引用选项
+        data_option::refresh_one_table_option(&self.data_table, &self.link_table);
         return Ok(());
     }
 
@@ -1274,8 +2049,14 @@ impl SkillEditorApp {
                     return;
                 }
                 let data_table = data_table.unwrap();
-                let mut data = data_table.create_row(&String::new(), 0);
-                data.insert(data_table.key_name.clone(), link_val);
+                let mut data = match data_table.create_row(&String::new(), 0) {
+                    Ok(data) => data,
+                    Err(e) => {
+                        utils::toast(&mut self.toasts, "ERRO", format!("新增失败: {}", e));
+                        return;
+                    }
+                };
+                data_table.set_row_key(&mut data, &link_val);
                 let name = data_table.table_name.clone();
                 let action = action::AddAction::new(&self.data_table, &name, data);
                 if action.is_some() {
@@ -1308,6 +2089,99 @@ impl SkillEditorApp {
         return (state, new);
     }
 
+    // 切换到表格所在的页签并选中行, 有主表时同时选中主表中对应的行
+    fn goto_row(&mut self, table: &String, row: usize) {
+        let view = self
+            .tab_cfg
+            .iter()
+            .position(|a| a.tabs.iter().any(|t| t.tab == *table));
+        let view = check_some!(view, {
+            let msg = format!("表格[{}]不在任何页签中", table);
+            utils::toast(&mut self.toasts, "INFO", msg);
+            return;
+        });
+        let cfg = &self.tab_cfg[view];
+        self.cur_location.cur_view = view;
+        self.cur_location.cur_view_group = cfg.group.clone();
+        let master_table = cfg
+            .tabs
+            .iter()
+            .find(|t| t.tab == *table)
+            .map(|t| t.master_table.clone())
+            .unwrap_or_default();
+
+        let data_table = check_some!(self.data_table.get_mut(table), return);
+        let one = check_some!(data_table.data.get(row), return);
+        let master_val = utils::map_get_string(one, &data_table.master_field, "");
+        data_table.cur_row = row as i32;
+        data_table.search.clear();
+
+        check_if!(master_table.is_empty(), return);
+        let master = check_some!(self.data_table.get_mut(&master_table), return);
+        let idx = master
+            .data
+            .iter()
+            .position(|a| master.get_row_key(a) == master_val);
+        if let Some(idx) = idx {
+            master.cur_row = idx as i32;
+        }
+    }
+
+    fn draw_templete_editor(&mut self, ctx: &egui::Context) {
+        match self
+            .templete_editor
+            .ui(ctx, &self.field_group, &self.data_table)
+        {
+            ETempleteOp::None => {}
+            ETempleteOp::Saved => {
+                self.reload_templete();
+                utils::toast(&mut self.toasts, "SUCC", "保存模板成功");
+            }
+            ETempleteOp::Goto(table, row) => self.goto_row(&table, row),
+            ETempleteOp::Regen(list) => {
+                let desc = format!("按模板重新生成{}个单元格", list.len());
+                let action = action::CompoundAction::update(&self.data_table, desc.clone(), list);
+                self.apply_action(check_some!(action, return));
+                utils::toast(&mut self.toasts, "SUCC", desc);
+            }
+        }
+    }
+
+    fn draw_batch_editor(&mut self, ctx: &egui::Context) {
+        let (desc, list) = check_some!(self.batch_editor.ui(ctx, &self.data_table), return);
+        let action = action::CompoundAction::update(&self.data_table, desc.clone(), list);
+        if action.is_none() {
+            utils::toast(&mut self.toasts, "INFO", "没有需要修改的内容");
+            return;
+        }
+        self.apply_action(action.unwrap());
+        utils::toast(&mut self.toasts, "SUCC", desc);
+    }
+
+    fn draw_favourite_window(&mut self, ctx: &egui::Context) {
+        let (table, row) = check_some!(self.favourite_window.ui(ctx, &self.data_table), return);
+        self.goto_row(&table, row);
+    }
+
+    fn draw_search_window(&mut self, ctx: &egui::Context) {
+        match self.search_window.ui(ctx, &self.data_table) {
+            ESearchOp::None => {}
+            ESearchOp::Goto(table, row) => self.goto_row(&table, row),
+            ESearchOp::Replace(desc, list) => {
+                let cnt = list.len();
+                let action = action::CompoundAction::update(&self.data_table, desc, list);
+                match action {
+                    Some(one) => {
+                        self.apply_action(one);
+                        let msg = format!("替换了{}个单元格", cnt);
+                        utils::toast(&mut self.toasts, "SUCC", msg);
+                    }
+                    None => utils::toast(&mut self.toasts, "INFO", "没有需要替换的内容"),
+                }
+            }
+        }
+    }
+
     fn draw_templete(&mut self, ctx: &egui::Context) {
         let mut create = false;
         egui::Window::new("关联表")
@@ -1343,7 +2217,13 @@ impl SkillEditorApp {
         if create {
             let cur_master_val = String::new();
             let data_table = self.data_table.get_mut(&self.templete_target).unwrap();
-            let mut data = data_table.create_row(&cur_master_val, 0);
+            let mut data = match data_table.create_row(&cur_master_val, 0) {
+                Ok(data) => data,
+                Err(e) => {
+                    utils::toast(&mut self.toasts, "ERRO", format!("新增失败: {}", e));
+                    return;
+                }
+            };
             for (k, v) in &self.templete_content {
                 let mut val = v.clone();
                 for (kk, vv) in &self.templete_data {
@@ -1386,10 +2266,16 @@ impl Default for SkillEditorApp {
             link_src_field: String::new(),
             menus: Vec::new(),
             console_show: false,
+            option_dirty: true,
             cfg: AppCfg::default(),
+            templete_editor: TempleteEditor::default(),
+            batch_editor: BatchEditor::default(),
+            search_window: SearchWindow::default(),
+            favourite_window: FavouriteWindow::default(),
             toasts: Toasts::default().with_anchor(egui_notify::Anchor::BottomRight),
             hotkey_redo: false,
             hotkey_undo: false,
+            focus_table: String::new(),
         }
     }
 }
@@ -1459,10 +2345,22 @@ impl eframe::App for SkillEditorApp {
         (self.hotkey_undo, self.hotkey_redo) = disable_default_hotkey(ctx);
 
         self.load_config(false);
+        if self.option_dirty {
+            data_option::refresh_table_option(&self.data_table);
+            for data_table in self.data_table.values_mut() {
+                data_table.select.validate(data_table.data.len());
+            }
+            self.option_dirty = false;
+        }
         self.draw_menu(ctx);
+        self.handle_clipboard(ctx);
         self.draw_view(ctx);
         self.draw_link_window(ctx);
         self.draw_templete(ctx);
+        self.draw_templete_editor(ctx);
+        self.draw_batch_editor(ctx);
+        self.draw_search_window(ctx);
+        self.draw_favourite_window(ctx);
         self.cfg.ui(ctx);
 
         self.toasts.show(ctx);
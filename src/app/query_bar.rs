@@ -0,0 +1,96 @@
+use eframe::{egui, epaint::Color32};
+
+use crate::{data::data_query, marco::check_if};
+
+const QUERY_HELP: &str = "筛选语法:
+火球  任意字段包含
+name:火球  字段包含, 可以写字段名或标题
+cooldown>5  数字比较, 支持 = != > >= < <=
+type=Enum(\"Fire\")  按枚举的显示名匹配
+name~^fire_\\d+  正则, 也可以写 /^fire/
+has:name  字段不为空
+has:error  有检查错误, has:broken 关联表缺失, has:skill-broken 指定关联表
+has:fav 已收藏, tag:WIP 有指定标签, color:红 颜色标记
+a AND b, a OR b, NOT a, -a, (a OR b) c  空格分隔默认为 AND";
+
+// 筛选输入框, 带语法提示和按表格保存的筛选, 返回筛选是否变化
+pub fn draw_query_bar(
+    ui: &mut egui::Ui,
+    id: &str,
+    query: &mut String,
+    saved: &mut Vec<(String, String)>,
+) -> bool {
+    let old = query.clone();
+    ui.horizontal(|ui| {
+        let txt = egui::TextEdit::singleline(query)
+            .hint_text("筛选, 如 name:火 cooldown>5")
+            .desired_width(ui.available_width() - 48.0);
+        ui.add(txt).on_hover_text(QUERY_HELP);
+        ui.menu_button("⭐", |ui| {
+            draw_saved_menu(ui, id, query, saved);
+        })
+        .response
+        .on_hover_text("保存的筛选");
+        if !query.is_empty() && ui.small_button("✖").clicked() {
+            query.clear();
+        }
+    });
+    if let Some(err) = data_query::check_query(query) {
+        ui.label(egui::RichText::new(err).small().color(Color32::RED));
+    }
+    return *query != old;
+}
+
+fn draw_saved_menu(
+    ui: &mut egui::Ui,
+    id: &str,
+    query: &mut String,
+    saved: &mut Vec<(String, String)>,
+) {
+    let mut del = None;
+    for (idx, (name, one)) in saved.iter().enumerate() {
+        ui.horizontal(|ui| {
+            if ui.button(name).on_hover_text(one).clicked() {
+                *query = one.clone();
+                ui.close_menu();
+            }
+            if ui.small_button("❌").clicked() {
+                del = Some(idx);
+            }
+        });
+    }
+    if let Some(idx) = del {
+        saved.remove(idx);
+    }
+    if !saved.is_empty() {
+        ui.separator();
+    }
+
+    // 保存时输入的名称放在 egui 的 memory 中
+    let name_id = egui::Id::new(("query_save_name", id));
+    let mut name = ui.data_mut(|d| d.get_temp::<String>(name_id).unwrap_or_default());
+    ui.horizontal(|ui| {
+        ui.add(
+            egui::TextEdit::singleline(&mut name)
+                .hint_text("名称")
+                .desired_width(100.0),
+        );
+        let enable = !name.trim().is_empty() && !query.trim().is_empty();
+        if ui
+            .add_enabled(enable, egui::Button::new("保存当前筛选"))
+            .clicked()
+        {
+            let key = name.trim().to_string();
+            match saved.iter_mut().find(|a| a.0 == key) {
+                Some(one) => one.1 = query.clone(),
+                None => saved.push((key, query.clone())),
+            }
+            name.clear();
+            ui.close_menu();
+        }
+    });
+    check_if!(saved.iter().any(|a| a.0 == name.trim()), {
+        ui.label(egui::RichText::new("同名的筛选会被覆盖").small());
+    });
+    ui.data_mut(|d| d.insert_temp(name_id, name));
+}
@@ -0,0 +1,396 @@
+use eframe::{egui, epaint::Color32};
+use itertools::Itertools;
+use regex::{NoExpand, Regex, RegexBuilder};
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    data::{data_field::EFieldType, data_table::DataTable},
+    marco::{check_if, check_some},
+    utils,
+};
+
+// 结果太多时只显示前面的部分, 替换不受影响
+const MAX_RESULT_SHOW: usize = 5000;
+
+const FIELD_TYPES: [(EFieldType, &str); 5] = [
+    (EFieldType::Bool, "布尔"),
+    (EFieldType::Number, "数字"),
+    (EFieldType::Str, "字符串"),
+    (EFieldType::Expr, "表达式"),
+    (EFieldType::Table, "表"),
+];
+
+pub enum ESearchOp {
+    None,
+    Goto(String, usize),
+    Replace(String, Vec<(String, usize, String, String)>),
+}
+
+struct SearchHit {
+    table: String,
+    row: usize,
+    key: String,
+    field: String,
+    title: String,
+    val: String,
+    is_key: bool,
+    // 预览替换后的值, 以及新值的检查错误
+    new: Option<String>,
+    error: String,
+}
+
+#[derive(Default)]
+pub struct SearchWindow {
+    show: bool,
+    search: String,
+    replace: String,
+    regex: bool,
+    match_case: bool,
+    // 为空时不限制
+    field_types: HashSet<usize>,
+    tables: HashSet<String>,
+    table_search: String,
+
+    result: Vec<SearchHit>,
+    cur: usize,
+    error: String,
+    // 预览时的 (查找, 替换, 正则, 区分大小写), 和当前输入一致时才能全部替换
+    preview_key: Option<(String, String, bool, bool)>,
+}
+
+impl SearchWindow {
+    pub fn show(&mut self) {
+        self.show = true;
+    }
+
+    fn build_regex(&self) -> Result<Regex, regex::Error> {
+        let pattern = if self.regex {
+            self.search.clone()
+        } else {
+            regex::escape(&self.search)
+        };
+        return RegexBuilder::new(&pattern)
+            .case_insensitive(!self.match_case)
+            .build();
+    }
+
+    fn replace_key(&self) -> (String, String, bool, bool) {
+        return (
+            self.search.clone(),
+            self.replace.clone(),
+            self.regex,
+            self.match_case,
+        );
+    }
+
+    fn replace_one(&self, re: &Regex, val: &str) -> String {
+        if self.regex {
+            return re.replace_all(val, self.replace.as_str()).to_string();
+        }
+        return re.replace_all(val, NoExpand(&self.replace)).to_string();
+    }
+
+    fn do_search(&mut self, data_table: &HashMap<String, DataTable>) {
+        self.result.clear();
+        self.cur = 0;
+        self.error.clear();
+        self.preview_key = None;
+        check_if!(self.search.is_empty(), return);
+        let re = match self.build_regex() {
+            Ok(re) => re,
+            Err(e) => {
+                self.error = format!("正则表达式错误: {}", e);
+                return;
+            }
+        };
+        for (name, table) in data_table.iter().sorted_by_key(|a| a.0) {
+            check_if!(
+                !self.tables.is_empty() && !self.tables.contains(name),
+                continue
+            );
+            for (row, map) in table.data.iter().enumerate() {
+                let key = table.get_row_key(map);
+                for field in &table.info {
+                    let type_idx = FIELD_TYPES.iter().position(|a| a.0 == field.val_type);
+                    check_if!(
+                        !self.field_types.is_empty()
+                            && !type_idx.map_or(false, |i| self.field_types.contains(&i)),
+                        continue
+                    );
+                    let val = check_some!(map.get(&field.name), continue);
+                    check_if!(!re.is_match(val), continue);
+                    self.result.push(SearchHit {
+                        table: name.clone(),
+                        row,
+                        key: key.clone(),
+                        field: field.name.clone(),
+                        title: field.title.clone(),
+                        val: val.clone(),
+                        is_key: field.is_key,
+                        new: None,
+                        error: String::new(),
+                    });
+                }
+            }
+        }
+    }
+
+    // 主键字段不替换, 新值有检查错误时不能全部替换
+    fn preview(&mut self, data_table: &HashMap<String, DataTable>) {
+        let re = check_some!(self.build_regex().ok(), return);
+        for i in 0..self.result.len() {
+            let new = self.replace_one(&re, &self.result[i].val);
+            let one = &mut self.result[i];
+            one.error.clear();
+            if one.is_key {
+                one.error = "主键字段不替换".to_string();
+            } else if let Some(table) = data_table.get(&one.table) {
+                if let Some(field) = table.info.iter().find(|a| a.name == one.field) {
+                    let (err, msg) = field.check_data(&new);
+                    check_if!(err, one.error = msg);
+                }
+            }
+            one.new = Some(new);
+        }
+        self.preview_key = Some(self.replace_key());
+    }
+
+    fn error_count(&self) -> usize {
+        return self
+            .result
+            .iter()
+            .filter(|a| !a.is_key && !a.error.is_empty())
+            .count();
+    }
+
+    // 只替换预览过的内容, 预览后被修改过的单元格跳过
+    fn replace_all(
+        &mut self,
+        data_table: &HashMap<String, DataTable>,
+    ) -> Vec<(String, usize, String, String)> {
+        let mut ret = Vec::new();
+        let mut stale = 0;
+        for one in &self.result {
+            check_if!(one.is_key, continue);
+            let new = check_some!(&one.new, continue);
+            let cur = data_table
+                .get(&one.table)
+                .and_then(|a| a.data.get(one.row))
+                .and_then(|a| a.get(&one.field));
+            if cur != Some(&one.val) {
+                stale += 1;
+                continue;
+            }
+            ret.push((one.table.clone(), one.row, one.field.clone(), new.clone()));
+        }
+        self.result.clear();
+        self.preview_key = None;
+        if stale > 0 {
+            self.error = format!("{}处在预览后被修改过, 已跳过", stale);
+        }
+        return ret;
+    }
+
+    fn draw_filter(&mut self, ui: &mut egui::Ui, data_table: &HashMap<String, DataTable>) {
+        ui.horizontal(|ui| {
+            ui.label("字段类型:");
+            for (idx, (_, name)) in FIELD_TYPES.iter().enumerate() {
+                let mut checked = self.field_types.contains(&idx);
+                if ui.checkbox(&mut checked, *name).changed() {
+                    if checked {
+                        self.field_types.insert(idx);
+                    } else {
+                        self.field_types.remove(&idx);
+                    }
+                }
+            }
+        });
+        let title = if self.tables.is_empty() {
+            "表格: 全部".to_string()
+        } else {
+            format!("表格: 已选{}个", self.tables.len())
+        };
+        egui::CollapsingHeader::new(title)
+            .id_source("search_window_tables")
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.table_search);
+                    if ui.button("全部").clicked() {
+                        self.tables.clear();
+                    }
+                });
+                egui::ScrollArea::vertical()
+                    .id_source("search_window_table_list")
+                    .max_height(160.0)
+                    .show(ui, |ui| {
+                        for (name, table) in data_table.iter().sorted_by_key(|a| a.0) {
+                            let show = format!("{}({})", table.show_name, name);
+                            check_if!(
+                                !self.table_search.is_empty()
+                                    && !utils::fuzzy_match(&show, &self.table_search),
+                                continue
+                            );
+                            let mut checked = self.tables.contains(name);
+                            if ui.checkbox(&mut checked, show).changed() {
+                                if checked {
+                                    self.tables.insert(name.clone());
+                                } else {
+                                    self.tables.remove(name);
+                                }
+                            }
+                        }
+                    });
+            });
+    }
+
+    fn draw_result(&mut self, ui: &mut egui::Ui) -> Option<usize> {
+        let mut click = None;
+        let row_h = ui.spacing().interact_size.y;
+        let show = self.result.len().min(MAX_RESULT_SHOW);
+        egui::ScrollArea::vertical()
+            .id_source("search_window_result")
+            .auto_shrink([false; 2])
+            .show_rows(ui, row_h, show, |ui, range| {
+                for idx in range {
+                    let one = &self.result[idx];
+                    ui.horizontal(|ui| {
+                        let txt =
+                            format!("{} [{}] {}({})", one.table, one.key, one.title, one.field);
+                        if ui.selectable_label(idx == self.cur, txt).clicked() {
+                            click = Some(idx);
+                        }
+                        let val = one.val.lines().next().unwrap_or("");
+                        match &one.new {
+                            Some(_) if one.is_key => {
+                                ui.label(val);
+                                ui.label(egui::RichText::new(&one.error).color(Color32::YELLOW));
+                            }
+                            Some(new) => {
+                                ui.label(egui::RichText::new(val).color(Color32::RED));
+                                ui.label("->");
+                                let new = new.lines().next().unwrap_or("");
+                                ui.label(egui::RichText::new(new).color(Color32::GREEN));
+                                if !one.error.is_empty() {
+                                    ui.label(egui::RichText::new("⚠").color(Color32::RED))
+                                        .on_hover_text(&one.error);
+                                }
+                            }
+                            None => {
+                                ui.label(val);
+                            }
+                        }
+                    })
+                    .response
+                    .on_hover_text(&one.val);
+                }
+            });
+        if self.result.len() > MAX_RESULT_SHOW {
+            ui.label(format!("只显示前{}条", MAX_RESULT_SHOW));
+        }
+        return click;
+    }
+
+    pub fn ui(
+        &mut self,
+        ctx: &egui::Context,
+        data_table: &HashMap<String, DataTable>,
+    ) -> ESearchOp {
+        let mut ret = ESearchOp::None;
+        let mut show = self.show;
+        egui::Window::new("🔍全局搜索")
+            .id(egui::Id::new("search_window"))
+            .open(&mut show)
+            .resizable(true)
+            .default_width(640.0)
+            .default_height(480.0)
+            .show(ctx, |ui| {
+                egui::Grid::new("search_window_input")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("查找");
+                        let resp = ui.text_edit_singleline(&mut self.search);
+                        if resp.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                            self.do_search(data_table);
+                        }
+                        ui.end_row();
+                        ui.label("替换为");
+                        ui.text_edit_singleline(&mut self.replace);
+                        ui.end_row();
+                    });
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.regex, "正则表达式")
+                        .on_hover_text("替换内容中可以用 $1 引用分组");
+                    ui.checkbox(&mut self.match_case, "区分大小写");
+                });
+                self.draw_filter(ui, data_table);
+                ui.horizontal(|ui| {
+                    if ui.button("搜索").clicked() {
+                        self.do_search(data_table);
+                    }
+                    let enable = !self.result.is_empty();
+                    if ui
+                        .add_enabled(enable, egui::Button::new("预览替换"))
+                        .clicked()
+                    {
+                        self.preview(data_table);
+                    }
+                    // 预览后修改了查找/替换内容需要重新预览
+                    let confirmed = self.preview_key == Some(self.replace_key());
+                    let errors = self.error_count();
+                    let hint = if !confirmed {
+                        "先预览替换结果".to_string()
+                    } else if errors > 0 {
+                        format!("{}处替换后有错误", errors)
+                    } else {
+                        "替换预览中的内容, 主键字段不替换".to_string()
+                    };
+                    if ui
+                        .add_enabled(
+                            enable && confirmed && errors == 0,
+                            egui::Button::new("全部替换"),
+                        )
+                        .on_hover_text(&hint)
+                        .on_disabled_hover_text(&hint)
+                        .clicked()
+                    {
+                        let desc = format!("全局替换[{}]为[{}]", self.search, self.replace);
+                        let list = self.replace_all(data_table);
+                        ret = ESearchOp::Replace(desc, list);
+                    }
+                    ui.separator();
+                    let len = self.result.len();
+                    let mut next = None;
+                    if ui.add_enabled(len > 0, egui::Button::new("⏶")).clicked() {
+                        next = Some((self.cur + len - 1) % len);
+                    }
+                    if ui.add_enabled(len > 0, egui::Button::new("⏷")).clicked() {
+                        next = Some((self.cur + 1) % len);
+                    }
+                    if let Some(idx) = next {
+                        self.cur = idx;
+                        let one = &self.result[idx];
+                        ret = ESearchOp::Goto(one.table.clone(), one.row);
+                    }
+                    if len > 0 {
+                        ui.label(format!("{}/{}", self.cur + 1, len));
+                    }
+                });
+                if !self.error.is_empty() {
+                    ui.label(egui::RichText::new(&self.error).color(Color32::RED));
+                }
+                let errors = self.error_count();
+                if errors > 0 {
+                    let msg = format!("{}处替换后检查不通过, 不能全部替换", errors);
+                    ui.label(egui::RichText::new(msg).color(Color32::RED));
+                }
+                ui.separator();
+                if let Some(idx) = self.draw_result(ui) {
+                    self.cur = idx;
+                    let one = &self.result[idx];
+                    ret = ESearchOp::Goto(one.table.clone(), one.row);
+                }
+            });
+        self.show = show;
+        return ret;
+    }
+}
@@ -1,8 +1,10 @@
 #![allow(dead_code)]
 use eframe::egui;
-use egui::text::LayoutJob;
+use egui::text::{CCursor, LayoutJob};
 use serde::{Deserialize, Serialize};
 
+use crate::{data::data_expr::ExprError, marco::check_if};
+
 pub fn code_view_ui(ui: &mut egui::Ui, code: &mut String, language: &str) -> bool {
     let theme = CodeTheme::from_memory(ui.ctx());
 
@@ -22,6 +24,141 @@ pub fn code_view_ui(ui: &mut egui::Ui, code: &mut String, language: &str) -> boo
     ui.add(txt).gained_focus()
 }
 
+// 代码编辑, 标出错误位置(悬停显示错误信息), 输入时按前缀提示补全, Tab 或点击选中
+pub fn code_editor_ui(
+    ui: &mut egui::Ui,
+    id: egui::Id,
+    code: &mut String,
+    language: &str,
+    errors: &[ExprError],
+    completion: &[(String, String)],
+) -> bool {
+    let theme = CodeTheme::from_memory(ui.ctx());
+
+    let mut layouter = |ui: &egui::Ui, string: &str, wrap_width: f32| {
+        let mut layout_job = highlight(ui.ctx(), &theme, string, language);
+        layout_job.wrap.max_width = wrap_width; // no wrapping
+        ui.fonts(|f| f.layout_job(layout_job))
+    };
+
+    // 上一帧的补全状态: 替换的字符范围和第一个候选项
+    let completion_id = id.with("completion");
+    let pending = ui.data_mut(|d| d.get_temp::<(usize, usize, String)>(completion_id));
+    let mut accept = None;
+    if pending.is_some() && ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Tab)) {
+        accept = pending.clone();
+    }
+    if let Some((start, end, word)) = accept {
+        replace_chars(ui.ctx(), id, code, start, end, &word);
+    }
+
+    let output = egui::TextEdit::multiline(code)
+        .id(id)
+        .font(egui::TextStyle::Monospace) // for cursor height
+        .code_editor()
+        .desired_width(f32::INFINITY)
+        .desired_rows(1)
+        .lock_focus(true)
+        .layouter(&mut layouter)
+        .show(ui);
+
+    let char_index = |line: usize, col: usize| -> usize {
+        let mut idx = 0;
+        for (i, one) in code.split('\n').enumerate() {
+            if i + 1 == line {
+                return idx + (col - 1).min(one.chars().count());
+            }
+            idx += one.chars().count() + 1;
+        }
+        return idx;
+    };
+    let galley = &output.galley;
+    let char_pos = |idx: usize| galley.pos_from_cursor(&galley.from_ccursor(CCursor::new(idx)));
+    let painter = ui.painter();
+    let offset = output.text_draw_pos.to_vec2();
+    for (i, err) in errors.iter().enumerate() {
+        let start = char_index(err.line, err.col);
+        let min = char_pos(start);
+        let max = char_pos(start + err.len);
+        let rect = egui::Rect::from_min_max(min.left_top(), max.right_bottom()).translate(offset);
+        let rect = rect.expand2(egui::vec2(2.0, 0.0));
+        let stroke = egui::Stroke::new(1.5, ui.visuals().error_fg_color);
+        painter.hline(rect.x_range(), rect.bottom(), stroke);
+        if ui.rect_contains_pointer(rect) {
+            egui::show_tooltip_text(ui.ctx(), id.with(i), err.to_string());
+        }
+    }
+
+    let mut next = None;
+    if output.response.has_focus() && output.cursor_range.is_some() {
+        let cursor = output.cursor_range.unwrap().primary.ccursor.index;
+        let chars: Vec<char> = code.chars().collect();
+        let mut start = cursor.min(chars.len());
+        while start > 0 {
+            let c = chars[start - 1];
+            check_if!(
+                !(c.is_alphanumeric() || c == '_' || c == '.' || c == ':'),
+                break
+            );
+            start -= 1;
+        }
+        let word: String = chars[start..cursor.min(chars.len())].iter().collect();
+        let list: Vec<&(String, String)> = completion
+            .iter()
+            .filter(|(a, _)| !word.is_empty() && a.starts_with(&word) && *a != word)
+            .take(8)
+            .collect();
+        if !list.is_empty() {
+            next = Some((start, cursor, list[0].0.clone()));
+            let pos = char_pos(cursor);
+            let mut click = None;
+            egui::Area::new(id.with("completion_popup"))
+                .order(egui::Order::Foreground)
+                .fixed_pos(pos.left_bottom() + offset)
+                .show(ui.ctx(), |ui| {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        for (i, (name, hint)) in list.iter().enumerate() {
+                            let resp = ui.selectable_label(i == 0, name).on_hover_text(hint);
+                            if resp.clicked() {
+                                click = Some(name.clone());
+                            }
+                        }
+                    });
+                });
+            if let Some(name) = click {
+                replace_chars(ui.ctx(), id, code, start, cursor, &name);
+                next = None;
+            }
+        }
+    }
+    ui.data_mut(|d| match next {
+        Some(s) => d.insert_temp(completion_id, s),
+        None => d.remove::<(usize, usize, String)>(completion_id),
+    });
+    output.response.gained_focus()
+}
+
+// 用补全内容替换 [start, end) 的字符, 并把光标移到替换内容之后
+fn replace_chars(
+    ctx: &egui::Context,
+    id: egui::Id,
+    code: &mut String,
+    start: usize,
+    end: usize,
+    word: &str,
+) {
+    let chars: Vec<char> = code.chars().collect();
+    check_if!(start > end || end > chars.len(), return);
+    let head: String = chars[..start].iter().collect();
+    let tail: String = chars[end..].iter().collect();
+    *code = format!("{}{}{}", head, word, tail);
+    let mut state = egui::text_edit::TextEditState::load(ctx, id).unwrap_or_default();
+    let cursor = CCursor::new(start + word.chars().count());
+    state.set_ccursor_range(Some(egui::text_edit::CCursorRange::one(cursor)));
+    state.store(ctx, id);
+    ctx.memory_mut(|m| m.request_focus(id));
+}
+
 pub fn show_code_view_ui(ui: &mut egui::Ui, mut code: &str, language: &str) {
     let theme = CodeTheme::from_memory(ui.ctx());
 
@@ -0,0 +1,713 @@
+use anyhow::{bail, Result};
+use eframe::{
+    egui::{self, RichText},
+    epaint::Color32,
+};
+use itertools::Itertools;
+use std::{collections::HashMap, path::PathBuf};
+use walkdir::WalkDir;
+
+use crate::{
+    app::{
+        syntax_highlight::{code_editor_ui, show_code_view_ui},
+        SkillEditorApp, TempleteInfo, TEMPLETE_MAP_EXPR,
+    },
+    data::{
+        data_field::{self, EEditorType, FieldInfo},
+        data_table::DataTable,
+        data_value::ELuaValue,
+    },
+    marco::{check_if, check_some},
+    utils,
+};
+
+const TEMPLETE_DIR: &str = "save_data/editor_templete";
+const TEMPLETE_SHEET: &str = "editor_templete";
+
+// 模板配置的列名
+const COL_GROUP: &str = "__Group__";
+const COL_SUB_GROUP: &str = "__SubGroup__";
+const COL_TABLE: &str = "table";
+const COL_TABLE_KEY: &str = "table_key";
+const COL_TITLE: &str = "title";
+const COL_CONTENT: &str = "content";
+const COL_EXPR: &str = "expr";
+const COL_TYPE: &str = "templete_type";
+
+// 引用列表最多显示的行数
+const MAX_USAGE_SHOW: usize = 500;
+
+// 模板配置的一行, 按列名保存原始文本, 保存时不会丢失未知的列
+#[derive(Debug, Clone, PartialEq)]
+struct TempleteRow {
+    path: PathBuf,
+    data: HashMap<String, String>,
+}
+
+impl TempleteRow {
+    fn get(&self, key: &str) -> String {
+        return utils::map_get_string(&self.data, key, "");
+    }
+
+    fn to_info(&self, field_group: &HashMap<String, Vec<FieldInfo>>) -> Option<TempleteInfo> {
+        let field = field_group.get(&self.get(COL_TABLE))?;
+        return Some(TempleteInfo {
+            title: self.get(COL_TITLE),
+            table: self.get(COL_TABLE),
+            content: HashMap::new(),
+            expr: self.get(COL_EXPR),
+            field: field.clone(),
+        });
+    }
+}
+
+struct TempleteUsage {
+    table: String,
+    row: usize,
+    name: String,
+    field: String,
+}
+
+// 按模板重新生成后有变化的单元格
+struct RegenItem {
+    table: String,
+    row: usize,
+    key: String,
+    name: String,
+    field: String,
+    old: String,
+    new: String,
+    selected: bool,
+}
+
+pub enum ETempleteOp {
+    None,
+    Saved,
+    Goto(String, usize),
+    // (表格, 行, 字段, 新值)
+    Regen(Vec<(String, usize, String, String)>),
+}
+
+#[derive(Default)]
+pub struct TempleteEditor {
+    show: bool,
+    list: Vec<TempleteRow>,
+    origin: Vec<TempleteRow>,
+    files: Vec<PathBuf>,
+    cur: usize,
+    search: String,
+
+    // 预览用的参数
+    preview: HashMap<String, String>,
+    preview_key: String,
+    preview_idx: i32,
+
+    usage: Vec<TempleteUsage>,
+    usage_key: String,
+
+    show_regen: bool,
+    regen: Vec<RegenItem>,
+    regen_error: Vec<String>,
+}
+
+impl TempleteEditor {
+    pub fn show(&mut self) {
+        self.show = true;
+        // 有未保存的修改时保留
+        check_if!(self.is_dirty(), return);
+        if let Err(e) = self.load() {
+            utils::msg(format!("读取模板配置失败:{:?}", e), "错误".to_string());
+        }
+    }
+
+    fn is_dirty(&self) -> bool {
+        return self.list != self.origin;
+    }
+
+    fn load(&mut self) -> Result<()> {
+        self.list.clear();
+        self.origin.clear();
+        self.files.clear();
+        self.cur = 0;
+        self.preview_key.clear();
+        self.usage_key.clear();
+
+        let mut path = std::env::current_exe()?;
+        path.pop();
+        path.push(TEMPLETE_DIR);
+        check_if!(!path.exists(), return Ok(()));
+
+        for entry in WalkDir::new(path) {
+            let entry = entry?;
+            let p = entry.path();
+
+            check_if!(p.is_dir(), continue);
+            let ext = check_some!(p.extension(), continue);
+            check_if!(ext != "xlsx", continue);
+            let name = check_some!(p.file_name(), continue);
+            let name = check_some!(name.to_str(), continue);
+            check_if!(name.starts_with("~$"), continue);
+
+            let p = p.to_path_buf();
+            for data in utils::load_excel2map(&p, TEMPLETE_SHEET)? {
+                check_if!(data.values().all(|a| a.is_empty()), continue);
+                self.list.push(TempleteRow {
+                    path: p.clone(),
+                    data,
+                });
+            }
+            self.files.push(p);
+        }
+        self.origin = self.list.clone();
+        return Ok(());
+    }
+
+    // 主键为空或重复时不能保存, 不同类型的模板分开保存, 可以使用同一个模板表格
+    fn check_key(&self) -> Result<()> {
+        let mut keys = HashMap::new();
+        for one in &self.list {
+            let key = one.get(COL_TABLE);
+            if key.is_empty() {
+                bail!(format!("模板[{}]的模板表格为空", one.get(COL_TITLE)));
+            }
+            let type_key = (one.get(COL_TYPE), key.clone());
+            if let Some(title) = keys.insert(type_key, one.get(COL_TITLE)) {
+                bail!(format!(
+                    "模板[{}]与[{}]的模板表格[{}]重复",
+                    one.get(COL_TITLE),
+                    title,
+                    key
+                ));
+            }
+        }
+        return Ok(());
+    }
+
+    fn get_error(&self, idx: usize, field_group: &HashMap<String, Vec<FieldInfo>>) -> String {
+        let one = check_some!(self.list.get(idx), return String::new());
+        let key = one.get(COL_TABLE);
+        if key.is_empty() {
+            return "模板表格不能为空".to_string();
+        }
+        let templete_type = one.get(COL_TYPE);
+        let dup =
+            self.list.iter().enumerate().any(|(i, a)| {
+                i != idx && a.get(COL_TABLE) == key && a.get(COL_TYPE) == templete_type
+            });
+        if dup {
+            return format!("模板表格[{}]重复", key);
+        }
+        if !field_group.contains_key(&key) {
+            return format!("模板[{}]的字段配置[{}]未找到", one.get(COL_TITLE), key);
+        }
+        let content = one.get(COL_CONTENT);
+        if !content.trim().is_empty() && ELuaValue::parse(&content).is_err() {
+            return "模板内容不是合法的 lua 表".to_string();
+        }
+        return String::new();
+    }
+
+    fn save(&mut self) -> Result<()> {
+        self.check_key()?;
+        for path in &self.files {
+            let list: Vec<_> = self
+                .list
+                .iter()
+                .filter(|a| a.path == *path)
+                .map(|a| a.data.clone())
+                .collect();
+            let old: Vec<_> = self
+                .origin
+                .iter()
+                .filter(|a| a.path == *path)
+                .map(|a| a.data.clone())
+                .collect();
+            check_if!(list == old, continue);
+            utils::save_map2excel(path, TEMPLETE_SHEET, &list)?;
+        }
+        self.origin = self.list.clone();
+        return Ok(());
+    }
+
+    fn create(&mut self, copy: bool) {
+        let path = match self.list.get(self.cur) {
+            Some(one) => one.path.clone(),
+            None => check_some!(self.files.first().cloned(), {
+                utils::msg("未找到模板配置文件".to_string(), "错误".to_string());
+                return;
+            }),
+        };
+        let mut data = HashMap::new();
+        if let Some(one) = self.list.get(self.cur) {
+            if copy {
+                data = one.data.clone();
+                data.insert(COL_TABLE.to_string(), String::new());
+            } else {
+                data.insert(COL_GROUP.to_string(), one.get(COL_GROUP));
+                data.insert(COL_SUB_GROUP.to_string(), one.get(COL_SUB_GROUP));
+                data.insert(COL_TABLE_KEY.to_string(), one.get(COL_TABLE_KEY));
+            }
+        }
+        if !copy || data.is_empty() {
+            data.insert(COL_TITLE.to_string(), "新模板".to_string());
+            data.insert(COL_TYPE.to_string(), "Expr".to_string());
+        }
+        self.list.push(TempleteRow { path, data });
+        self.cur = self.list.len() - 1;
+    }
+
+    // 表达式模板查找 TempleteExpr 字段中的模板数据, 子结构模板查找使用该模板的字段
+    fn find_usage(
+        one: &TempleteRow,
+        data_table: &HashMap<String, DataTable>,
+    ) -> Vec<TempleteUsage> {
+        let key = one.get(COL_TABLE);
+        let templete_type = one.get(COL_TYPE);
+        let mut ret = Vec::new();
+        for (name, table) in data_table.iter().sorted_by_key(|a| a.0) {
+            for field in &table.info {
+                let used = match field.editor_type {
+                    EEditorType::TempleteExpr => templete_type == "Expr",
+                    EEditorType::SubField => {
+                        templete_type == "SubField" && field.sub_field_key == key
+                    }
+                    _ => false,
+                };
+                check_if!(!used, continue);
+                for (i, row) in table.data.iter().enumerate() {
+                    let val = check_some!(row.get(&field.name), continue);
+                    let found = match field.editor_type {
+                        EEditorType::TempleteExpr => {
+                            FieldInfo::get_templete_ids(val).contains(&key)
+                        }
+                        _ => !val.is_empty(),
+                    };
+                    check_if!(!found, continue);
+                    ret.push(TempleteUsage {
+                        table: name.clone(),
+                        row: i,
+                        name: TempleteEditor::row_name(table, row),
+                        field: field.title.clone(),
+                    });
+                }
+            }
+        }
+        return ret;
+    }
+
+    fn row_name(table: &DataTable, row: &HashMap<String, String>) -> String {
+        return format!(
+            "{} {}",
+            table.get_row_key(row),
+            utils::map_get_string(row, &table.show_field, "")
+        );
+    }
+
+    // 使用已保存的模板定义重新生成所有 TempleteExpr 单元格, 只记录有变化的
+    fn find_regen(&mut self, data_table: &HashMap<String, DataTable>) {
+        let templete = TEMPLETE_MAP_EXPR.lock().unwrap().clone();
+        self.regen.clear();
+        self.regen_error.clear();
+        for (name, table) in data_table.iter().sorted_by_key(|a| a.0) {
+            for field in &table.info {
+                check_if!(field.editor_type != EEditorType::TempleteExpr, continue);
+                for (i, row) in table.data.iter().enumerate() {
+                    let val = check_some!(row.get(&field.name), continue);
+                    // 手写的代码不处理
+                    check_if!(FieldInfo::get_templete_ids(val).is_empty(), continue);
+                    let row_name = TempleteEditor::row_name(table, row);
+                    let new = match FieldInfo::regen_templete(val, &templete) {
+                        Ok(v) => v,
+                        Err(e) => {
+                            let msg = format!("[{}] {} - {}: {}", name, row_name, field.title, e);
+                            self.regen_error.push(msg);
+                            continue;
+                        }
+                    };
+                    // 只有换行符不同时不算变化
+                    check_if!(new.lines().eq(val.lines()), continue);
+                    self.regen.push(RegenItem {
+                        table: name.clone(),
+                        row: i,
+                        key: field.name.clone(),
+                        name: row_name,
+                        field: field.title.clone(),
+                        old: val.clone(),
+                        new,
+                        selected: true,
+                    });
+                }
+            }
+        }
+    }
+
+    fn draw_diff(ui: &mut egui::Ui, old: &str, new: &str) {
+        // 去掉第一行的模板数据, 只比较生成的代码
+        let old = old.splitn(2, '\n').nth(1).unwrap_or("");
+        let new = new.splitn(2, '\n').nth(1).unwrap_or("");
+        for (op, line) in utils::diff_lines(old, new) {
+            let text = egui::RichText::new(format!("{} {}", op, line)).monospace();
+            let text = match op {
+                '+' => text.color(Color32::GREEN),
+                '-' => text.color(Color32::RED),
+                _ => text,
+            };
+            ui.label(text);
+        }
+    }
+
+    fn draw_regen(&mut self, ctx: &egui::Context) -> ETempleteOp {
+        let mut ret = ETempleteOp::None;
+        let mut show = self.show_regen;
+        egui::Window::new("🔁按模板重新生成")
+            .open(&mut show)
+            .resizable(true)
+            .default_width(600.0)
+            .default_height(500.0)
+            .show(ctx, |ui| {
+                if self.is_dirty() {
+                    let msg = "有未保存的模板修改, 重新生成使用的是已保存的模板";
+                    ui.label(egui::RichText::new(msg).color(Color32::YELLOW));
+                }
+                let cnt = self.regen.iter().filter(|a| a.selected).count();
+                ui.horizontal(|ui| {
+                    ui.label(format!("共{}处变化", self.regen.len()));
+                    if ui.button("全选").clicked() {
+                        self.regen.iter_mut().for_each(|a| a.selected = true);
+                    }
+                    if ui.button("全不选").clicked() {
+                        self.regen.iter_mut().for_each(|a| a.selected = false);
+                    }
+                    let apply =
+                        ui.add_enabled(cnt > 0, egui::Button::new(format!("应用({})", cnt)));
+                    if apply.clicked() {
+                        let list = self
+                            .regen
+                            .iter()
+                            .filter(|a| a.selected)
+                            .map(|a| (a.table.clone(), a.row, a.key.clone(), a.new.clone()))
+                            .collect();
+                        ret = ETempleteOp::Regen(list);
+                    }
+                });
+                if !self.regen_error.is_empty() {
+                    let title = format!("{}处无法生成", self.regen_error.len());
+                    egui::CollapsingHeader::new(RichText::new(title).color(Color32::RED))
+                        .id_source("templete_regen_error")
+                        .show(ui, |ui| {
+                            for one in &self.regen_error {
+                                ui.label(one);
+                            }
+                        });
+                }
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .id_source("templete_regen_list")
+                    .show(ui, |ui| {
+                        for (i, one) in self.regen.iter_mut().enumerate() {
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut one.selected, "");
+                                let title = format!("[{}] {} - {}", one.table, one.name, one.field);
+                                egui::CollapsingHeader::new(title)
+                                    .id_source(format!("templete_regen_{}", i))
+                                    .show(ui, |ui| {
+                                        TempleteEditor::draw_diff(ui, &one.old, &one.new);
+                                    });
+                            });
+                        }
+                    });
+            });
+        self.show_regen = show;
+        if !matches!(ret, ETempleteOp::None) {
+            self.show_regen = false;
+            self.regen.clear();
+        }
+        return ret;
+    }
+
+    fn text_edit(ui: &mut egui::Ui, data: &mut HashMap<String, String>, key: &str) {
+        let v = data.entry(key.to_string()).or_default();
+        ui.add(egui::TextEdit::singleline(v).desired_width(f32::INFINITY));
+    }
+
+    fn draw_list(&mut self, ui: &mut egui::Ui, field_group: &HashMap<String, Vec<FieldInfo>>) {
+        ui.horizontal(|ui| {
+            ui.label("搜索:");
+            ui.text_edit_singleline(&mut self.search);
+        });
+        ui.separator();
+        let order: Vec<usize> = (0..self.list.len())
+            .sorted_by_key(|i| {
+                let one = &self.list[*i];
+                (one.get(COL_TYPE), one.get(COL_GROUP), one.get(COL_TITLE))
+            })
+            .collect();
+        egui::ScrollArea::vertical()
+            .id_source("templete_editor_list")
+            .show(ui, |ui| {
+                let mut last_type = None;
+                for idx in order {
+                    let one = &self.list[idx];
+                    let title = one.get(COL_TITLE);
+                    let key = one.get(COL_TABLE);
+                    if !self.search.is_empty()
+                        && !utils::fuzzy_match(&title, &self.search)
+                        && !utils::fuzzy_match(&key, &self.search)
+                    {
+                        continue;
+                    }
+                    let templete_type = one.get(COL_TYPE);
+                    if last_type.as_ref() != Some(&templete_type) {
+                        ui.label(egui::RichText::new(&templete_type).strong());
+                        last_type = Some(templete_type);
+                    }
+                    let mut text = egui::RichText::new(format!("{}({})", title, key));
+                    if !self.get_error(idx, field_group).is_empty() {
+                        text = text.color(Color32::RED);
+                    }
+                    if ui.selectable_label(idx == self.cur, text).clicked() {
+                        self.cur = idx;
+                    }
+                }
+            });
+    }
+
+    fn draw_detail(
+        &mut self,
+        ui: &mut egui::Ui,
+        field_group: &HashMap<String, Vec<FieldInfo>>,
+        data_table: &HashMap<String, DataTable>,
+    ) -> ETempleteOp {
+        let mut ret = ETempleteOp::None;
+        let err = self.get_error(self.cur, field_group);
+        let mut type_list = vec!["Expr".to_string(), "SubField".to_string()];
+        for one in &self.list {
+            let t = one.get(COL_TYPE);
+            check_if!(t.is_empty() || type_list.contains(&t), continue);
+            type_list.push(t);
+        }
+        let one = check_some!(self.list.get_mut(self.cur), {
+            ui.label("未选择模板");
+            return ret;
+        });
+
+        egui::ScrollArea::vertical()
+            .id_source("templete_editor_detail")
+            .show(ui, |ui| {
+                egui::Grid::new("templete_editor_grid")
+                    .num_columns(2)
+                    .spacing([20.0, 4.0])
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("模板类型");
+                        let v = one.data.entry(COL_TYPE.to_string()).or_default();
+                        egui::ComboBox::from_id_source("templete_editor_type")
+                            .selected_text(v.clone())
+                            .show_ui(ui, |ui| {
+                                for t in &type_list {
+                                    ui.selectable_value(v, t.clone(), t);
+                                }
+                            });
+                        ui.end_row();
+
+                        ui.label("模板表格")
+                            .on_hover_text("参数的字段配置, 同时是模板的主键");
+                        ui.horizontal(|ui| {
+                            let v = one.data.entry(COL_TABLE.to_string()).or_default();
+                            ui.text_edit_singleline(v);
+                            egui::ComboBox::from_id_source("templete_editor_table")
+                                .selected_text("选择字段配置")
+                                .show_ui(ui, |ui| {
+                                    for k in field_group.keys().sorted() {
+                                        ui.selectable_value(v, k.clone(), k);
+                                    }
+                                });
+                        });
+                        ui.end_row();
+
+                        ui.label("所属表格");
+                        ui.horizontal(|ui| {
+                            let v = one.data.entry(COL_TABLE_KEY.to_string()).or_default();
+                            ui.text_edit_singleline(v);
+                            egui::ComboBox::from_id_source("templete_editor_table_key")
+                                .selected_text("选择表格")
+                                .show_ui(ui, |ui| {
+                                    for k in data_table.keys().sorted() {
+                                        ui.selectable_value(v, k.clone(), k);
+                                    }
+                                });
+                        });
+                        ui.end_row();
+
+                        ui.label("显示名称");
+                        TempleteEditor::text_edit(ui, &mut one.data, COL_TITLE);
+                        ui.end_row();
+
+                        ui.label("分组");
+                        TempleteEditor::text_edit(ui, &mut one.data, COL_GROUP);
+                        ui.end_row();
+
+                        ui.label("子分组");
+                        TempleteEditor::text_edit(ui, &mut one.data, COL_SUB_GROUP);
+                        ui.end_row();
+
+                        ui.label("模板内容")
+                            .on_hover_text("用模板新建行时填充的字段, lua 表格式");
+                        let v = one.data.entry(COL_CONTENT.to_string()).or_default();
+                        ui.add(egui::TextEdit::multiline(v).desired_width(f32::INFINITY));
+                        ui.end_row();
+
+                        ui.label("所在文件");
+                        ui.label(one.path.display().to_string());
+                        ui.end_row();
+                    });
+
+                if !err.is_empty() {
+                    ui.label(egui::RichText::new(&err).color(Color32::RED));
+                }
+
+                ui.label("模板表达式");
+                let id = ui.id().with("templete_editor_expr");
+                let v = one.data.entry(COL_EXPR.to_string()).or_default();
+                code_editor_ui(ui, id, v, "lua", &[], &[]);
+
+                let info = one.to_info(field_group);
+                egui::CollapsingHeader::new("预览")
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        let info = check_some!(&info, {
+                            ui.label("字段配置未找到, 无法预览");
+                            return;
+                        });
+                        // 切换模板后参数恢复默认值
+                        if self.preview_key != info.table {
+                            self.preview_key = info.table.clone();
+                            self.preview.clear();
+                            for field in &info.field {
+                                self.preview
+                                    .insert(field.name.clone(), field.default_val.clone());
+                            }
+                        }
+                        let click = SkillEditorApp::_draw_data(
+                            ui,
+                            "TempleteEditorPreview".to_string(),
+                            &info.field,
+                            &mut self.preview,
+                            self.preview_idx,
+                            &String::new(),
+                            None,
+                        );
+                        if let Some(idx) = click {
+                            self.preview_idx = idx;
+                        }
+                        let (expr, err) = data_field::preview_templete(info, &self.preview);
+                        show_code_view_ui(ui, &expr, "lua");
+                        for one in err {
+                            ui.label(egui::RichText::new(one).color(Color32::RED));
+                        }
+                    });
+
+                let key = format!("{}#{}", one.get(COL_TYPE), one.get(COL_TABLE));
+                egui::CollapsingHeader::new("引用").show(ui, |ui| {
+                    if ui.button("🔃刷新").clicked() || self.usage_key != key {
+                        self.usage = TempleteEditor::find_usage(one, data_table);
+                        self.usage_key = key;
+                    }
+                    let templete_type = one.get(COL_TYPE);
+                    if templete_type != "Expr" && templete_type != "SubField" {
+                        ui.label("该类型的模板只用于新建行, 不记录引用");
+                        return;
+                    }
+                    ui.label(format!("共{}处引用", self.usage.len()));
+                    for usage in self.usage.iter().take(MAX_USAGE_SHOW) {
+                        let text = format!("[{}] {} - {}", usage.table, usage.name, usage.field);
+                        if ui.link(text).clicked() {
+                            ret = ETempleteOp::Goto(usage.table.clone(), usage.row);
+                        }
+                    }
+                    if self.usage.len() > MAX_USAGE_SHOW {
+                        ui.label(format!("只显示前{}处", MAX_USAGE_SHOW));
+                    }
+                });
+            });
+        return ret;
+    }
+
+    pub fn ui(
+        &mut self,
+        ctx: &egui::Context,
+        field_group: &HashMap<String, Vec<FieldInfo>>,
+        data_table: &HashMap<String, DataTable>,
+    ) -> ETempleteOp {
+        let mut ret = ETempleteOp::None;
+        let mut show = self.show;
+        let title = if self.is_dirty() {
+            "📝模板管理*"
+        } else {
+            "📝模板管理"
+        };
+        egui::Window::new(title)
+            .id(egui::Id::new("templete_editor"))
+            .open(&mut show)
+            .resizable(true)
+            .default_width(900.0)
+            .default_height(600.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("新建").clicked() {
+                        self.create(false);
+                    }
+                    if ui.button("复制").clicked() {
+                        self.create(true);
+                    }
+                    if ui.button("删除").clicked() && self.cur < self.list.len() {
+                        let one = &self.list[self.cur];
+                        let msg = format!(
+                            "确定删除模板[{}({})]? 保存后生效",
+                            one.get(COL_TITLE),
+                            one.get(COL_TABLE)
+                        );
+                        if utils::confirm(msg, "删除模板".to_string()) {
+                            self.list.remove(self.cur);
+                            self.cur = self.cur.min(self.list.len().saturating_sub(1));
+                        }
+                    }
+                    if ui.button("💾保存").clicked() {
+                        match self.save() {
+                            Ok(_) => ret = ETempleteOp::Saved,
+                            Err(e) => utils::msg(format!("保存模板失败:{}", e), "错误".to_string()),
+                        }
+                    }
+                    if ui.button("🔃放弃修改").clicked() {
+                        if let Err(e) = self.load() {
+                            utils::msg(format!("读取模板配置失败:{:?}", e), "错误".to_string());
+                        }
+                    }
+                    let hint = "模板修改后, 按当前模板重新生成已有单元格的代码";
+                    if ui.button("🔁按模板重新生成").on_hover_text(hint).clicked() {
+                        self.find_regen(data_table);
+                        self.show_regen = true;
+                    }
+                });
+                ui.separator();
+                egui::SidePanel::left("templete_editor_list_panel")
+                    .resizable(true)
+                    .default_width(240.0)
+                    .show_inside(ui, |ui| {
+                        self.draw_list(ui, field_group);
+                    });
+                egui::CentralPanel::default().show_inside(ui, |ui| {
+                    let op = self.draw_detail(ui, field_group, data_table);
+                    if !matches!(op, ETempleteOp::None) {
+                        ret = op;
+                    }
+                });
+            });
+        self.show = show;
+        let op = self.draw_regen(ctx);
+        if !matches!(op, ETempleteOp::None) {
+            ret = op;
+        }
+        return ret;
+    }
+}
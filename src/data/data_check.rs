@@ -0,0 +1,353 @@
+use anyhow::Result;
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::Rc,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::marco::check_if;
+
+lazy_static! {
+    // (字段, 值) -> 检查结果, 选项/配置变化后由 clear_cache 清空
+    static ref CHECK_CACHE: Mutex<HashMap<(String, String), (bool, String)>> =
+        Mutex::new(HashMap::new());
+    // 模拟游戏接口的 lua 代码, 创建 lua 环境时加载
+    static ref MOCK_API: Mutex<String> = Mutex::new(String::new());
+}
+
+thread_local! {
+    // 数据检查共用的 lua 环境, 只在界面线程使用
+    static SANDBOX: RefCell<Option<Sandbox>> = RefCell::new(None);
+}
+
+const CACHE_LIMIT: usize = 50000;
+// 每 1000 条指令检查一次, 单次运行最多 1000 万条指令
+const HOOK_STEP: u32 = 1000;
+const MAX_HOOK_COUNT: u32 = 10000;
+// 单次运行的时间上限, 界面线程中运行, 不能卡住界面
+const MAX_RUN_TIME: Duration = Duration::from_millis(200);
+const MEMORY_LIMIT: usize = 32 * 1024 * 1024;
+// string.rep 结果的长度上限, 以及模式匹配函数处理的字符串长度上限
+const STRING_LIMIT: usize = 1024 * 1024;
+const PATTERN_LIMIT: usize = 64 * 1024;
+// 模式的长度上限, 以及回溯匹配的估算代价上限: (字符串长度+1) ^ (量词数+1)
+const PATTERN_LEN_LIMIT: usize = 256;
+const PATTERN_COST_LIMIT: f64 = 1e7;
+
+// 创建环境时运行一次:
+// 1. 移除可以加载代码/控制回收的函数
+// 2. 限制字符串函数处理的长度和模式的复杂度, C 函数中运行时指令钩子无法中断
+// 3. pcall/xpcall 捕获到超限错误时重新抛出, 超限后不能继续运行
+// 4. 返回生成运行环境的函数, 每次运行使用新的全局表, 库表为只读代理
+const SANDBOX_INIT: &str = r#"
+local STRING_LIMIT, PATTERN_LIMIT, PATTERN_LEN_LIMIT, PATTERN_COST_LIMIT, limit_error = ...
+local G = _G
+local type, tostring, error, next, setmetatable = type, tostring, error, next, setmetatable
+local select, pcall, xpcall, sub = select, pcall, xpcall, string.sub
+
+local rep = string.rep
+string.rep = function(s, n, sep)
+    if type(n) == "number" and n > 0 then
+        local len = #tostring(s) * n
+        if sep ~= nil then
+            len = len + #tostring(sep) * (n - 1)
+        end
+        if len > STRING_LIMIT then
+            error("字符串过长", 2)
+        end
+    end
+    return rep(s, n, sep)
+end
+
+-- 模式中的量词个数, 跳过 % 转义和 [] 字符集
+local function quantifiers(p)
+    local n, i, len = 0, 1, #p
+    while i <= len do
+        local c = sub(p, i, i)
+        if c == "%" then
+            i = i + 1
+        elseif c == "[" then
+            i = i + 1
+            if sub(p, i, i) == "^" then
+                i = i + 1
+            end
+            -- 字符集的第一个字符是 ] 时不结束
+            repeat
+                if sub(p, i, i) == "%" then
+                    i = i + 1
+                end
+                i = i + 1
+            until i > len or sub(p, i, i) == "]"
+        elseif c == "*" or c == "+" or c == "-" or c == "?" then
+            n = n + 1
+        end
+        i = i + 1
+    end
+    return n
+end
+
+for _, name in ipairs({ "find", "match", "gmatch", "gsub" }) do
+    local f = string[name]
+    string[name] = function(s, p, ...)
+        if type(s) == "string" and #s > PATTERN_LIMIT then
+            error("字符串过长", 2)
+        end
+        -- find 的第 4 个参数为 true 时按普通字符串查找
+        local plain = name == "find" and select(2, ...)
+        if type(p) == "string" and not plain then
+            if #p > PATTERN_LEN_LIMIT then
+                error("模式过长", 2)
+            end
+            local cost = (#tostring(s) + 1) ^ (quantifiers(p) + 1)
+            if cost > PATTERN_COST_LIMIT then
+                error("模式过于复杂", 2)
+            end
+        end
+        return f(s, p, ...)
+    end
+end
+
+local function rethrow(...)
+    local err = limit_error()
+    if err then
+        error(err, 0)
+    end
+    return ...
+end
+G.pcall = function(...)
+    return rethrow(pcall(...))
+end
+G.xpcall = function(...)
+    return rethrow(xpcall(...))
+end
+
+getmetatable("").__metatable = false
+dofile, loadfile, load, require, collectgarbage = nil, nil, nil, nil, nil
+
+local function readonly(t, seen)
+    local proxy = seen[t]
+    if proxy then
+        return proxy
+    end
+    proxy = {}
+    seen[t] = proxy
+    setmetatable(proxy, {
+        __index = function(_, k)
+            local v = t[k]
+            if type(v) == "table" then
+                return readonly(v, seen)
+            end
+            return v
+        end,
+        __newindex = function()
+            error("不能修改只读表", 2)
+        end,
+        __pairs = function()
+            return function(_, k)
+                local nk, v = next(t, k)
+                if type(v) == "table" then
+                    v = readonly(v, seen)
+                end
+                return nk, v
+            end, proxy, nil
+        end,
+        __len = function()
+            return #t
+        end,
+        __metatable = false,
+    })
+    return proxy
+end
+
+return function()
+    local seen = {}
+    local env = {}
+    for k, v in next, G do
+        if type(v) == "table" and v ~= G then
+            v = readonly(v, seen)
+        end
+        env[k] = v
+    end
+    env._G = env
+    return env
+end
+"#;
+
+struct Sandbox {
+    lua: mlua::Lua,
+    count: Rc<Cell<u32>>,
+    start: Rc<Cell<Instant>>,
+    // SANDBOX_INIT 返回的生成环境函数
+    make_env: mlua::RegistryKey,
+}
+
+// 超出指令数或运行时间时的错误, 计数和开始时间在每次运行前重置
+fn limit_error(count: u32, start: Instant) -> Option<&'static str> {
+    if count > MAX_HOOK_COUNT {
+        return Some("运行超出指令上限");
+    }
+    if start.elapsed() > MAX_RUN_TIME {
+        return Some("运行超时");
+    }
+    return None;
+}
+
+// 只开放 math/string/table/utf8, 限制内存, 指令数和运行时间
+fn new_sandbox() -> mlua::Result<Sandbox> {
+    let libs = mlua::StdLib::MATH | mlua::StdLib::STRING | mlua::StdLib::TABLE | mlua::StdLib::UTF8;
+    let lua = mlua::Lua::new_with(libs, mlua::LuaOptions::default())?;
+    lua.set_memory_limit(MEMORY_LIMIT)?;
+    let count = Rc::new(Cell::new(0));
+    let start = Rc::new(Cell::new(Instant::now()));
+    let hook_count = count.clone();
+    let hook_start = start.clone();
+    let triggers = mlua::HookTriggers {
+        every_nth_instruction: Some(HOOK_STEP),
+        ..Default::default()
+    };
+    // 超限后每次回调都报错, 被 pcall 捕获时由 SANDBOX_INIT 中的包装重新抛出
+    lua.set_hook(triggers, move |_, _| {
+        hook_count.set(hook_count.get() + 1);
+        if let Some(e) = limit_error(hook_count.get(), hook_start.get()) {
+            return Err(mlua::Error::RuntimeError(e.to_string()));
+        }
+        Ok(())
+    })?;
+    let err_count = count.clone();
+    let err_start = start.clone();
+    let limit_fn =
+        lua.create_function(move |_, ()| Ok(limit_error(err_count.get(), err_start.get())))?;
+    let make_env: mlua::Function = lua.load(SANDBOX_INIT).set_name("sandbox_init")?.call((
+        STRING_LIMIT,
+        PATTERN_LIMIT,
+        PATTERN_LEN_LIMIT,
+        PATTERN_COST_LIMIT,
+        limit_fn,
+    ))?;
+    let make_env = lua.create_registry_value(make_env)?;
+    let mock = MOCK_API.lock().unwrap().clone();
+    if !mock.is_empty() {
+        count.set(0);
+        start.set(Instant::now());
+        // 模拟接口有错误时不影响其他检查
+        if let Err(e) = lua.load(&mock).set_name("mock_api")?.exec() {
+            println!("模拟接口加载失败: {}", e);
+        }
+    }
+    return Ok(Sandbox {
+        lua,
+        count,
+        start,
+        make_env,
+    });
+}
+
+fn run_in<R>(sandbox: &Sandbox, f: impl FnOnce(&mlua::Lua, mlua::Table) -> Result<R>) -> Result<R> {
+    sandbox.count.set(0);
+    sandbox.start.set(Instant::now());
+    // 每次运行使用独立的全局表, 避免单元格之间相互影响
+    let lua = &sandbox.lua;
+    let make_env: mlua::Function = lua.registry_value(&sandbox.make_env)?;
+    let env: mlua::Table = make_env.call(())?;
+    let ret = f(lua, env);
+    let _ = lua.gc_collect();
+    return ret;
+}
+
+// 在共用的 lua 环境中运行, 回收后内存仍然过多时重建环境
+pub fn with_sandbox<R>(f: impl FnOnce(&mlua::Lua, mlua::Table) -> Result<R>) -> Result<R> {
+    SANDBOX.with(|cell| {
+        let mut slot = match cell.try_borrow_mut() {
+            Ok(s) => s,
+            // 嵌套调用时使用临时环境
+            Err(_) => return run_in(&new_sandbox()?, f),
+        };
+        if slot.is_none() {
+            *slot = Some(new_sandbox()?);
+        }
+        let ret = run_in(slot.as_ref().unwrap(), f);
+        if slot.as_ref().unwrap().lua.used_memory() > MEMORY_LIMIT / 2 {
+            *slot = None;
+        }
+        return ret;
+    })
+}
+
+pub fn set_mock(script: String) {
+    *MOCK_API.lock().unwrap() = script;
+    SANDBOX.with(|cell| {
+        if let Ok(mut slot) = cell.try_borrow_mut() {
+            *slot = None;
+        }
+    });
+    clear_cache();
+}
+
+pub fn clear_cache() {
+    CHECK_CACHE.lock().unwrap().clear();
+}
+
+// 按 (字段, 值) 缓存检查结果, 计算时不持有锁, 允许递归检查
+pub fn check_cached(
+    key: String,
+    val: &String,
+    f: impl FnOnce() -> (bool, String),
+) -> (bool, String) {
+    let key = (key, val.clone());
+    if let Some(ret) = CHECK_CACHE.lock().unwrap().get(&key) {
+        return ret.clone();
+    }
+    let ret = f();
+    let mut cache = CHECK_CACHE.lock().unwrap();
+    check_if!(cache.len() >= CACHE_LIMIT, cache.clear());
+    cache.insert(key, ret.clone());
+    return ret;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(code: &str) -> Result<()> {
+        return with_sandbox(|lua, env| {
+            lua.load(code).set_environment(env)?.exec()?;
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn pcall_cannot_catch_limit() {
+        let start = Instant::now();
+        let ret = run("while true do pcall(function() while true do end end) end");
+        assert!(ret.is_err());
+        assert!(start.elapsed() < MAX_RUN_TIME * 5);
+
+        let ret = run("while true do xpcall(function() while true do end end, function() end) end");
+        assert!(ret.is_err());
+        assert!(start.elapsed() < MAX_RUN_TIME * 10);
+
+        // 普通错误仍然可以捕获
+        assert!(run("assert(not pcall(error, 'x'))").is_ok());
+    }
+
+    #[test]
+    fn pattern_limit() {
+        let start = Instant::now();
+        let ret = run(r#"local _ = ("a"):rep(30):find(("a*"):rep(30).."b")"#);
+        assert!(ret.unwrap_err().to_string().contains("模式过于复杂"));
+        assert!(start.elapsed() < MAX_RUN_TIME);
+
+        let ret = run(r#"local _ = ("a"):find(("a"):rep(1000))"#);
+        assert!(ret.unwrap_err().to_string().contains("模式过长"));
+
+        // 普通的模式和按普通字符串查找不受影响
+        let code = r#"
+            assert(("12-34"):match("^(%d+)-(%d+)$") == "12")
+            assert(("[a-z]*"):find("[a-z]*", 1, true) == 1)
+            assert(("a,b,c"):gsub("[^,]+", "x") == "x,x,x")
+        "#;
+        assert!(run(code).is_ok());
+    }
+}
@@ -0,0 +1,745 @@
+use anyhow::{bail, Result};
+use eframe::{egui, epaint::Color32};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+use walkdir::WalkDir;
+
+use crate::marco::{check_if, check_some};
+
+use super::{data_check, data_field::FieldInfo, data_option, data_value::ELuaValue};
+
+lazy_static! {
+    // editor_api 中配置的游戏接口, 用于检查未定义的全局变量和自动补全
+    pub static ref API_LIST: Mutex<Vec<ApiInfo>> = Mutex::new(Vec::new());
+    // 表达式字段的测试用例
+    static ref EXPR_TEST: Mutex<Vec<ExprTestCase>> = Mutex::new(Vec::new());
+}
+
+const EXPR_TEST_FILE: &str = "save_data/editor_expr_test.json";
+
+// 接口配置变化后递增, 使表达式检查的缓存失效
+static API_VERSION: AtomicUsize = AtomicUsize::new(0);
+
+pub const API_TABLE: &str = "editor_api";
+
+// lua 自带的全局变量, 和 data_check 沙盒中加载的库一致
+const LUA_GLOBALS: [&str; 24] = [
+    "assert",
+    "error",
+    "getmetatable",
+    "ipairs",
+    "next",
+    "pairs",
+    "pcall",
+    "print",
+    "rawequal",
+    "rawget",
+    "rawlen",
+    "rawset",
+    "select",
+    "setmetatable",
+    "tonumber",
+    "tostring",
+    "type",
+    "xpcall",
+    "_G",
+    "_ENV",
+    "math",
+    "string",
+    "table",
+    "utf8",
+];
+
+const LUA_KEYWORDS: [&str; 22] = [
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if", "in",
+    "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+// 接口名可以带模块, 如 "Skill.AddBuff", 检查时只看第一段
+#[derive(Debug, Clone)]
+pub struct ApiInfo {
+    pub name: String,
+    pub params: String,
+    pub desc: String,
+}
+
+// 表达式错误, 行列从 1 开始, 相对于单元格中的文本
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExprError {
+    pub line: usize,
+    pub col: usize,
+    pub len: usize,
+    pub msg: String,
+}
+
+impl std::fmt::Display for ExprError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "第{}行第{}列: {}", self.line, self.col, self.msg)
+    }
+}
+
+pub fn load_api(list: Vec<ApiInfo>) {
+    let mut api = API_LIST.lock().unwrap();
+    *api = list.into_iter().filter(|a| !a.name.is_empty()).collect();
+    API_VERSION.fetch_add(1, Ordering::Relaxed);
+    data_check::clear_cache();
+}
+
+// 自动补全的候选项: 参数 + 接口名 + 共享枚举名, 附带提示文本
+pub fn get_completion(params: &Vec<String>) -> Vec<(String, String)> {
+    let mut ret: Vec<(String, String)> = params
+        .iter()
+        .map(|a| (a.clone(), "参数".to_string()))
+        .collect();
+    for one in API_LIST.lock().unwrap().iter() {
+        let hint = format!("{}({}) {}", one.name, one.params, one.desc);
+        ret.push((one.name.clone(), hint));
+    }
+    for name in data_option::ENUM_DEF_MAP.lock().unwrap().keys() {
+        ret.push((name.clone(), "共享枚举".to_string()));
+    }
+    ret.sort();
+    ret.dedup_by(|a, b| a.0 == b.0);
+    return ret;
+}
+
+// 解析字段类型后缀中的签名, 如 "number(caster, target)" -> ("number", [caster, target])
+pub fn parse_signature(suffix: &str) -> (String, Vec<String>) {
+    let suffix = suffix.trim();
+    let (ret_type, params) = match suffix.find("(") {
+        Some(pos) => (&suffix[..pos], &suffix[pos + 1..]),
+        None => return (suffix.to_string(), Vec::new()),
+    };
+    let params = params.trim_end().trim_end_matches(")");
+    let mut list = Vec::new();
+    for one in params.split(",") {
+        // 参数可以写成 "名称:类型"
+        let name = one.split(":").next().unwrap_or("").trim();
+        check_if!(name.is_empty(), continue);
+        list.push(name.to_string());
+    }
+    return (ret_type.trim().to_string(), list);
+}
+
+#[derive(Debug, PartialEq)]
+enum ETokenKind {
+    Ident,
+    Symbol,
+    Literal,
+}
+
+#[derive(Debug)]
+struct Token {
+    kind: ETokenKind,
+    text: String,
+    line: usize,
+    col: usize,
+}
+
+fn long_bracket_level(chars: &Vec<char>, pos: usize) -> Option<usize> {
+    check_if!(chars.get(pos) != Some(&'['), return None);
+    let mut level = 0;
+    while chars.get(pos + 1 + level) == Some(&'=') {
+        level += 1;
+    }
+    check_if!(chars.get(pos + 1 + level) != Some(&'['), return None);
+    return Some(level);
+}
+
+// 简单的 lua 词法分析, 跳过注释和字符串, 只用于查找标识符
+fn tokenize(code: &str) -> Vec<Token> {
+    let chars: Vec<char> = code.chars().collect();
+    let mut ret = Vec::new();
+    let mut pos = 0;
+    let mut line = 1;
+    let mut line_start = 0;
+    while pos < chars.len() {
+        let c = chars[pos];
+        let col = pos - line_start + 1;
+        let start = pos;
+        if c == '\n' {
+            pos += 1;
+            line += 1;
+            line_start = pos;
+            continue;
+        }
+        if c.is_whitespace() {
+            pos += 1;
+            continue;
+        }
+        let is_comment = c == '-' && chars.get(pos + 1) == Some(&'-');
+        let long_start = if is_comment { pos + 2 } else { pos };
+        if let Some(level) = long_bracket_level(&chars, long_start) {
+            let close: Vec<char> = format!("]{}]", "=".repeat(level)).chars().collect();
+            pos = long_start + level + 2;
+            while pos < chars.len() && !chars[pos..].starts_with(&close) {
+                if chars[pos] == '\n' {
+                    line += 1;
+                    line_start = pos + 1;
+                }
+                pos += 1;
+            }
+            pos = (pos + close.len()).min(chars.len());
+            if !is_comment {
+                ret.push(Token {
+                    kind: ETokenKind::Literal,
+                    text: String::new(),
+                    line,
+                    col,
+                });
+            }
+            continue;
+        }
+        if is_comment {
+            while pos < chars.len() && chars[pos] != '\n' {
+                pos += 1;
+            }
+            continue;
+        }
+        if c == '"' || c == '\'' {
+            pos += 1;
+            while pos < chars.len() && chars[pos] != c && chars[pos] != '\n' {
+                if chars[pos] == '\\' {
+                    pos += 1;
+                }
+                pos += 1;
+            }
+            pos += 1;
+            ret.push(Token {
+                kind: ETokenKind::Literal,
+                text: String::new(),
+                line,
+                col,
+            });
+            continue;
+        }
+        if c.is_ascii_digit()
+            || (c == '.' && chars.get(pos + 1).map_or(false, |a| a.is_ascii_digit()))
+        {
+            while pos < chars.len() && (chars[pos].is_ascii_alphanumeric() || chars[pos] == '.') {
+                pos += 1;
+            }
+            ret.push(Token {
+                kind: ETokenKind::Literal,
+                text: String::new(),
+                line,
+                col,
+            });
+            continue;
+        }
+        if c.is_alphabetic() || c == '_' {
+            while pos < chars.len() && (chars[pos].is_alphanumeric() || chars[pos] == '_') {
+                pos += 1;
+            }
+            ret.push(Token {
+                kind: ETokenKind::Ident,
+                text: chars[start..pos].iter().collect(),
+                line,
+                col,
+            });
+            continue;
+        }
+        let two: String = chars[pos..(pos + 2).min(chars.len())].iter().collect();
+        let text = match two.as_str() {
+            "==" | "~=" | "<=" | ">=" | "::" | "//" | "<<" | ">>" => two,
+            ".." => {
+                if chars.get(pos + 2) == Some(&'.') {
+                    "...".to_string()
+                } else {
+                    two
+                }
+            }
+            _ => c.to_string(),
+        };
+        pos += text.chars().count();
+        ret.push(Token {
+            kind: ETokenKind::Symbol,
+            text,
+            line,
+            col,
+        });
+    }
+    return ret;
+}
+
+fn is_symbol(tokens: &Vec<Token>, idx: usize, text: &str) -> bool {
+    match tokens.get(idx) {
+        Some(t) => t.kind == ETokenKind::Symbol && t.text == text,
+        None => false,
+    }
+}
+
+fn is_ident(tokens: &Vec<Token>, idx: usize) -> bool {
+    match tokens.get(idx) {
+        Some(t) => t.kind == ETokenKind::Ident && !LUA_KEYWORDS.contains(&t.text.as_str()),
+        None => false,
+    }
+}
+
+// 表达式中声明的局部变量/函数参数, 不区分作用域
+fn collect_local(tokens: &Vec<Token>, known: &mut HashSet<String>) {
+    for (i, t) in tokens.iter().enumerate() {
+        check_if!(t.kind != ETokenKind::Ident, continue);
+        let mut j = i + 1;
+        match t.text.as_str() {
+            "local" | "for" => {
+                if tokens.get(j).map_or(false, |a| a.text == "function") {
+                    j += 1;
+                }
+                while is_ident(tokens, j) {
+                    known.insert(tokens[j].text.clone());
+                    j += 1;
+                    // local a <const> 的属性
+                    if is_symbol(tokens, j, "<") {
+                        j += 3;
+                    }
+                    check_if!(!is_symbol(tokens, j, ","), break);
+                    j += 1;
+                }
+            }
+            "function" => {
+                // function foo.bar() 和 function foo:bar() 不定义 foo
+                let is_field = is_symbol(tokens, j + 1, ".") || is_symbol(tokens, j + 1, ":");
+                if is_ident(tokens, j) && !is_field {
+                    known.insert(tokens[j].text.clone());
+                }
+                while j < tokens.len() && !is_symbol(tokens, j, "(") {
+                    j += 1;
+                }
+                j += 1;
+                while is_ident(tokens, j) {
+                    known.insert(tokens[j].text.clone());
+                    j += 1;
+                    check_if!(!is_symbol(tokens, j, ","), break);
+                    j += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn check_global(code: &str, params: &Vec<String>) -> Vec<ExprError> {
+    let api = API_LIST.lock().unwrap();
+    // 未配置接口时不检查全局变量
+    check_if!(api.is_empty(), return Vec::new());
+    let mut known: HashSet<String> = params.iter().cloned().collect();
+    for one in LUA_GLOBALS {
+        known.insert(one.to_string());
+    }
+    for one in api.iter() {
+        let name = one
+            .name
+            .split(|c| c == '.' || c == ':')
+            .next()
+            .unwrap_or("");
+        known.insert(name.to_string());
+    }
+    drop(api);
+    for name in data_option::ENUM_DEF_MAP.lock().unwrap().keys() {
+        known.insert(name.clone());
+    }
+
+    let tokens = tokenize(code);
+    collect_local(&tokens, &mut known);
+
+    let mut ret = Vec::new();
+    let mut brace = 0;
+    for (i, t) in tokens.iter().enumerate() {
+        match t.text.as_str() {
+            "{" if t.kind == ETokenKind::Symbol => brace += 1,
+            "}" if t.kind == ETokenKind::Symbol => brace -= 1,
+            _ => {}
+        }
+        check_if!(!is_ident(&tokens, i), continue);
+        check_if!(known.contains(&t.text), continue);
+        if i > 0 {
+            let prev = &tokens[i - 1];
+            // 字段访问/方法调用/标签
+            check_if!(
+                [".", ":", "::"].contains(&prev.text.as_str()) && prev.kind == ETokenKind::Symbol,
+                continue
+            );
+            check_if!(prev.text == "goto", continue);
+            // 表格构造中的键 { key = 1 }
+            let is_key = brace > 0
+                && (is_symbol(&tokens, i - 1, "{")
+                    || is_symbol(&tokens, i - 1, ",")
+                    || is_symbol(&tokens, i - 1, ";"))
+                && is_symbol(&tokens, i + 1, "=");
+            check_if!(is_key, continue);
+        }
+        ret.push(ExprError {
+            line: t.line,
+            col: t.col,
+            len: t.text.chars().count(),
+            msg: format!("未定义的全局变量[{}]", t.text),
+        });
+    }
+    return ret;
+}
+
+// 把 lua 的编译错误转为行列, 如 [string "..."]:3: unexpected symbol near 'x'
+fn parse_lua_error(err: &str, code: &str, line_offset: usize) -> ExprError {
+    let mut line = 1;
+    let mut msg = err.to_string();
+    if let Some(pos) = err.find("]:") {
+        let rest = &err[pos + 2..];
+        let num: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+        if let Ok(n) = num.parse::<usize>() {
+            line = n.saturating_sub(line_offset).max(1);
+            msg = rest[num.len()..].trim_start_matches(":").trim().to_string();
+        }
+    }
+    let msg = msg.lines().next().unwrap_or("").to_string();
+    let lines: Vec<&str> = code.lines().collect();
+    line = line.min(lines.len().max(1));
+    let text = lines.get(line - 1).cloned().unwrap_or("");
+    let mut col = 1;
+    let mut len = text.chars().count().max(1);
+    if let Some(pos) = msg.find("near '") {
+        let near = msg[pos + 6..].trim_end_matches("'");
+        if let Some(p) = text.find(near) {
+            col = text[..p].chars().count() + 1;
+            len = near.chars().count().max(1);
+        }
+    }
+    return ExprError {
+        line,
+        col,
+        len,
+        msg,
+    };
+}
+
+// 没有 return 语句时视为表达式, 在前面加上 return
+// 按词法判断, 注释/字符串/标识符中的 return 不算
+fn with_return(code: &str) -> String {
+    let has_return = tokenize(code)
+        .iter()
+        .any(|t| t.kind == ETokenKind::Ident && t.text == "return");
+    check_if!(has_return, return code.to_string());
+    return format!("return {}", code);
+}
+
+// 编译检查 + 未定义全局变量检查, 参数来自字段类型后缀中的签名
+pub fn check_expr(code: &str, suffix: &str) -> Vec<ExprError> {
+    let (ret_type, params) = parse_signature(suffix);
+    let mut body = code.to_string();
+    if ret_type != "void" {
+        body = with_return(&body);
+    }
+    let s = format!("return function({})\n{}\nend", params.join(", "), body);
+    let ret = data_check::with_sandbox(|lua, _| {
+        lua.load(s.as_str()).into_function()?;
+        Ok(())
+    });
+    if let Err(e) = ret {
+        return vec![parse_lua_error(&e.to_string(), code, 1)];
+    }
+    return check_global(code, &params);
+}
+
+struct ExprChecker {}
+
+impl Default for ExprChecker {
+    fn default() -> Self {
+        Self {}
+    }
+}
+
+impl egui::util::cache::ComputerMut<(&str, &str, usize), Vec<ExprError>> for ExprChecker {
+    fn compute(&mut self, (code, suffix, _): (&str, &str, usize)) -> Vec<ExprError> {
+        check_expr(code, suffix)
+    }
+}
+
+// 界面中每帧都会检查, 按内容缓存结果
+pub fn check_expr_cached(ctx: &egui::Context, code: &str, suffix: &str) -> Vec<ExprError> {
+    type CheckCache = egui::util::cache::FrameCache<Vec<ExprError>, ExprChecker>;
+    let version = API_VERSION.load(Ordering::Relaxed);
+    ctx.memory_mut(|mem| {
+        mem.caches
+            .cache::<CheckCache>()
+            .get((code, suffix, version))
+    })
+}
+
+// 表达式测试用例, field 为 "表格.字段", params 为每个参数的 lua 表达式
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExprTestCase {
+    pub field: String,
+    pub name: String,
+    pub params: Vec<String>,
+    // 期望结果, 为空时只检查能否正常运行
+    #[serde(default)]
+    pub expect: String,
+}
+
+fn get_path(p: &str) -> Result<PathBuf> {
+    let mut path = std::env::current_exe()?;
+    path.pop();
+    path.push(p);
+    return Ok(path);
+}
+
+pub fn load_mock_api() -> Result<()> {
+    let path = get_path("save_data/editor_api")?;
+    let mut list = Vec::new();
+    if path.exists() {
+        for entry in WalkDir::new(path).sort_by_file_name() {
+            let entry = entry?;
+            let p = entry.path();
+            let ext = check_some!(p.extension(), continue);
+            check_if!(ext != "lua", continue);
+            list.push(std::fs::read_to_string(p)?);
+        }
+    }
+    // editor_api 目录下的 lua 文件用于模拟游戏接口
+    data_check::set_mock(list.join("\n"));
+    return Ok(());
+}
+
+pub fn load_test() -> Result<()> {
+    let path = get_path(EXPR_TEST_FILE)?;
+    let mut list = Vec::new();
+    if path.exists() {
+        let s = std::fs::read_to_string(path)?;
+        list = serde_json::from_str(&s)?;
+    }
+    *EXPR_TEST.lock().unwrap() = list;
+    data_check::clear_cache();
+    return Ok(());
+}
+
+fn save_test(list: &Vec<ExprTestCase>) -> Result<()> {
+    data_check::clear_cache();
+    let mut list = list.clone();
+    list.sort_by(|a, b| (&a.field, &a.name).cmp(&(&b.field, &b.name)));
+    let s = serde_json::to_string_pretty(&list)?;
+    std::fs::write(get_path(EXPR_TEST_FILE)?, s)?;
+    return Ok(());
+}
+
+pub fn get_test(field: &str) -> Vec<ExprTestCase> {
+    let list = EXPR_TEST.lock().unwrap();
+    return list.iter().filter(|a| a.field == field).cloned().collect();
+}
+
+// 同名用例会被覆盖
+fn add_test(case: ExprTestCase) -> Result<()> {
+    let mut list = EXPR_TEST.lock().unwrap();
+    list.retain(|a| !(a.field == case.field && a.name == case.name));
+    list.push(case);
+    return save_test(&list);
+}
+
+fn remove_test(field: &str, name: &str) -> Result<()> {
+    let mut list = EXPR_TEST.lock().unwrap();
+    list.retain(|a| !(a.field == field && a.name == name));
+    return save_test(&list);
+}
+
+fn value_str(value: mlua::Value) -> String {
+    match value {
+        mlua::Value::Nil => "nil".to_string(),
+        mlua::Value::Table(_) | mlua::Value::String(_) => match ELuaValue::from_lua(value) {
+            Ok(v) => v.to_lua(),
+            Err(e) => e.to_string(),
+        },
+        mlua::Value::Boolean(b) => b.to_string(),
+        mlua::Value::Integer(i) => i.to_string(),
+        mlua::Value::Number(n) => ELuaValue::Number(n).to_lua(),
+        _ => value.type_name().to_string(),
+    }
+}
+
+// 用给定的参数运行表达式, 返回值转为 lua 文本, 多个返回值用 ", " 分隔
+pub fn run_expr(code: &str, suffix: &str, args: &Vec<String>) -> Result<String> {
+    let (ret_type, params) = parse_signature(suffix);
+    let mut body = code.to_string();
+    if ret_type != "void" {
+        body = with_return(&body);
+    }
+    let s = format!("return function({})\n{}\nend", params.join(", "), body);
+    data_check::with_sandbox(|lua, env| {
+        let mut values = Vec::new();
+        for (i, name) in params.iter().enumerate() {
+            let arg = args.get(i).map(|a| a.trim()).unwrap_or("");
+            if arg.is_empty() {
+                values.push(mlua::Value::Nil);
+                continue;
+            }
+            let chunk = format!("return {}", arg);
+            let v: mlua::Value = match lua.load(&chunk).set_environment(env.clone())?.eval() {
+                Ok(v) => v,
+                Err(e) => bail!("参数[{}]错误: {}", name, e),
+            };
+            values.push(v);
+        }
+        let chunk = lua.load(&s).set_name("expr")?.set_environment(env)?;
+        let func: mlua::Function = chunk.eval()?;
+        let ret: mlua::MultiValue = func.call(mlua::MultiValue::from_vec(values))?;
+        let ret: Vec<String> = ret.into_iter().map(value_str).collect();
+        Ok(ret.join(", "))
+    })
+}
+
+// 批量编辑的 lua 变换: v 为字段当前的值, row 为整行的单元格文本, 返回值作为新值
+pub fn run_transform(
+    code: &str,
+    field: &FieldInfo,
+    row: &HashMap<String, String>,
+) -> Result<String> {
+    let body = with_return(code);
+    let val = row.get(&field.name).cloned().unwrap_or_default();
+    let val = field.cell_to_lua(&val)?.to_lua();
+    let ret = data_check::with_sandbox(|lua, env| {
+        let v: mlua::Value = lua.load(&format!("return {}", val)).eval()?;
+        env.set("v", v)?;
+        let t = lua.create_table()?;
+        for (k, s) in row {
+            t.set(k.as_str(), s.as_str())?;
+        }
+        env.set("row", t)?;
+        let chunk = lua
+            .load(&body)
+            .set_name("transform")?
+            .set_environment(env)?;
+        let ret: mlua::Value = chunk.eval()?;
+        check_if!(matches!(ret, mlua::Value::Nil), bail!("没有返回值"));
+        ELuaValue::from_lua(ret)
+    })?;
+    return Ok(field.lua_to_cell(&ret));
+}
+
+// 运行字段的全部测试用例, 返回第一个失败的用例
+pub fn run_test(field: &str, code: &str, suffix: &str) -> Option<String> {
+    for case in get_test(field) {
+        match run_expr(code, suffix, &case.params) {
+            Ok(ret) => {
+                if !case.expect.is_empty() && ret != case.expect.trim() {
+                    return Some(format!(
+                        "用例[{}]结果[{}]与期望[{}]不一致",
+                        case.name, ret, case.expect
+                    ));
+                }
+            }
+            Err(e) => return Some(format!("用例[{}]运行错误: {}", case.name, e)),
+        }
+    }
+    return None;
+}
+
+#[derive(Clone, Default)]
+struct TestPanel {
+    params: Vec<String>,
+    name: String,
+    expect: String,
+    result: Option<(bool, String)>,
+}
+
+// 表达式测试面板: 填写参数运行, 保存/载入/删除用例
+pub fn draw_test_panel(ui: &mut egui::Ui, id: egui::Id, field: &str, code: &str, suffix: &str) {
+    let (_, params) = parse_signature(suffix);
+    let mut panel = ui.data_mut(|d| d.get_temp::<TestPanel>(id).unwrap_or_default());
+    panel.params.resize(params.len(), String::new());
+    ui.group(|ui| {
+        egui::Grid::new(id.with("params"))
+            .num_columns(2)
+            .spacing([4.0, 4.0])
+            .show(ui, |ui| {
+                for (i, name) in params.iter().enumerate() {
+                    ui.label(name);
+                    let txt = egui::TextEdit::singleline(&mut panel.params[i])
+                        .hint_text("lua 表达式, 如 100 或 {hp = 10}")
+                        .desired_width(f32::INFINITY);
+                    ui.add(txt);
+                    ui.end_row();
+                }
+            });
+        ui.horizontal(|ui| {
+            if ui.button("▶运行").clicked() {
+                panel.result = Some(match run_expr(code, suffix, &panel.params) {
+                    Ok(s) => (true, s),
+                    Err(e) => (false, e.to_string()),
+                });
+            }
+            if let Some((ok, s)) = &panel.result {
+                let color = if *ok { Color32::GREEN } else { Color32::RED };
+                ui.label(egui::RichText::new(s).color(color));
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut panel.name)
+                    .hint_text("用例名")
+                    .desired_width(80.0),
+            );
+            ui.add(
+                egui::TextEdit::singleline(&mut panel.expect)
+                    .hint_text("期望结果(可选)")
+                    .desired_width(120.0),
+            );
+            if ui.button("💾保存用例").clicked() && !panel.name.is_empty() {
+                let case = ExprTestCase {
+                    field: field.to_string(),
+                    name: panel.name.clone(),
+                    params: panel.params.clone(),
+                    expect: panel.expect.clone(),
+                };
+                if let Err(e) = add_test(case) {
+                    panel.result = Some((false, e.to_string()));
+                }
+            }
+        });
+        for case in get_test(field) {
+            ui.horizontal(|ui| {
+                // 每帧都会显示, 运行结果按 (用例, 表达式) 缓存
+                let key = format!("{}#{}#{}", field, case.name, case.params.join("#"));
+                let ret = data_check::check_cached(key, &code.to_string(), || {
+                    match run_expr(code, suffix, &case.params) {
+                        Ok(s) => (false, s),
+                        Err(e) => (true, e.to_string()),
+                    }
+                });
+                let ret = if ret.0 { Err(ret.1) } else { Ok(ret.1) };
+                let pass = match &ret {
+                    Ok(s) => case.expect.is_empty() || *s == case.expect.trim(),
+                    Err(_) => false,
+                };
+                let (icon, color) = if pass {
+                    ("✔", Color32::GREEN)
+                } else {
+                    ("✖", Color32::RED)
+                };
+                let hint = match &ret {
+                    Ok(s) => format!("结果: {}\n期望: {}", s, case.expect),
+                    Err(e) => e.to_string(),
+                };
+                ui.label(egui::RichText::new(icon).color(color))
+                    .on_hover_text(hint);
+                ui.label(&case.name);
+                ui.label(egui::RichText::new(case.params.join(", ")).weak());
+                if ui.small_button("载入").clicked() {
+                    panel.params = case.params.clone();
+                    panel.name = case.name.clone();
+                    panel.expect = case.expect.clone();
+                }
+                if ui.small_button("🗑").on_hover_text("删除用例").clicked() {
+                    if let Err(e) = remove_test(field, &case.name) {
+                        panel.result = Some((false, e.to_string()));
+                    }
+                }
+            });
+        }
+    });
+    ui.data_mut(|d| d.insert_temp(id, panel));
+}
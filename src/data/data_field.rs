@@ -1,13 +1,27 @@
 use anyhow::{bail, Result};
 use eframe::{egui, epaint::Color32};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicI32, Ordering},
+        Arc,
+    },
+};
 
 use crate::{
-    app::syntax_highlight::{code_view_ui, show_code_view_ui},
-    app::{TempleteInfo, TEMPLETE_MAP_EXPR, TEMPLETE_MAP_SUB_FIELD},
+    app::syntax_highlight::{code_editor_ui, show_code_view_ui},
+    app::{TempleteInfo, TEMPLETE_MAP_EXPR, TEMPLETE_MAP_SUB_FIELD, TEMPLETE_VERSION},
     error,
-    utils::{self, map2tablestr},
+    marco::{check_if, check_some},
+    utils,
+};
+
+use super::{
+    data_check, data_expr,
+    data_option::{self, EOptionSource},
+    data_value::{self, ELuaValue, ETableKey},
 };
 
 #[derive(Debug, PartialEq, Clone)]
@@ -30,12 +44,28 @@ pub enum EEditorType {
     BitFlag,
     TempleteExpr,
     SubField,
+    Reference,
 }
 
-#[derive(Debug, Clone)]
+// 子结构的绘制层数, 嵌套的子结构折叠显示
+static SUB_FIELD_DEPTH: AtomicI32 = AtomicI32::new(0);
+
+// 数组复制的内容, 存在 egui 的 memory 中, 同时写入系统剪贴板
+const ARRAY_CLIPBOARD: &str = "array_clipboard";
+
+enum EArrayOp {
+    Insert(usize),
+    Remove(usize),
+    Move(usize, usize),
+    Duplicate(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct EnumOption {
     pub show: String,
     pub val: String,
+    // 导出名, 共享枚举配置了按名称导出时使用
+    pub code: String,
 }
 
 #[derive(Debug, Clone)]
@@ -47,7 +77,7 @@ pub struct FieldInfo {
     pub val_type: EFieldType,
     pub editor_type: EEditorType,
     pub opt: Vec<EnumOption>,
-    pub bit_name: Vec<String>,
+    pub opt_source: EOptionSource,
     pub sub_field_key: String,
     pub default_val: String,
     pub link_table: String,
@@ -56,8 +86,12 @@ pub struct FieldInfo {
 
     pub is_key: bool,
     pub is_array: bool,
+    // 数组嵌套层数, A<N> 为 1, A<A<N>> 为 2
+    pub array_depth: i32,
     pub suffix: String,
     pub origin: String,
+    // 所属的字段配置(table_key), 加载字段配置后设置
+    pub table_key: String,
 }
 
 impl FieldInfo {
@@ -74,7 +108,7 @@ impl FieldInfo {
         export: bool,
         header: Vec<String>,
     ) -> Result<FieldInfo> {
-        let (is_key, is_array, data_type, suffix) = utils::parse_data_type(&field_type)?;
+        let (is_key, array_depth, data_type, suffix) = utils::parse_data_type(&field_type)?;
 
         let editor_type = match editor_type.as_str() {
             "Const" => EEditorType::Const,
@@ -86,25 +120,23 @@ impl FieldInfo {
             "BitFlag" => EEditorType::BitFlag,
             "TempleteExpr" => EEditorType::TempleteExpr,
             "SubField" => EEditorType::SubField,
+            "Reference" => EEditorType::Reference,
             _ => {
                 bail!(error::AppError::EditorTypeNotSupport(editor_type))
             }
         };
         let mut opt: Vec<EnumOption> = Vec::new();
+        let mut opt_source = EOptionSource::Static;
         if editor_type == EEditorType::Enum {
-            for one in &opt_str {
-                let one: Vec<&str> = one.split(":").collect();
-                if one.len() >= 2 {
-                    let val = one.get(0).unwrap().clone().trim().to_string();
-                    let show = one.get(1).unwrap().clone().trim().to_string();
-                    opt.push(EnumOption { show, val })
-                }
+            opt_source = EOptionSource::parse(&opt_str);
+            if opt_source == EOptionSource::Static {
+                opt = data_option::parse_static_option(&opt_str);
             }
         }
-        let mut bit_name = Vec::new();
         if editor_type == EEditorType::BitFlag {
-            for one in &opt_str {
-                bit_name.push(one.clone());
+            opt_source = EOptionSource::parse(&opt_str);
+            if opt_source == EOptionSource::Static {
+                opt = data_option::parse_bit_option(&opt_str);
             }
         }
         let mut sub_field_key = String::new();
@@ -121,11 +153,13 @@ impl FieldInfo {
             val_type: data_type,
             editor_type,
             opt,
-            bit_name,
+            opt_source,
             is_key,
-            is_array,
+            is_array: array_depth > 0,
+            array_depth,
             suffix,
             origin: field_type.clone(),
+            table_key: String::new(),
             default_val: default,
             link_table,
             export,
@@ -170,7 +204,28 @@ fn uasset2str(path: PathBuf, is_bp: bool) -> Result<String> {
     return Ok(path_str);
 }
 
-#[derive(Serialize, Deserialize)]
+// 按参数生成模板表达式并检查, 用于模板管理窗口的预览
+pub fn preview_templete(
+    info: &TempleteInfo,
+    data: &HashMap<String, String>,
+) -> (String, Vec<String>) {
+    let one = TempleteData {
+        id: info.table.clone(),
+        data: data.clone(),
+    };
+    return (one.get_expr(info), one.check(info, ""));
+}
+
+// 无法识别为模板的手写代码, 作为特殊的模板实例保存
+const RAW_TEMPLETE: &str = "__raw__";
+const RAW_CODE: &str = "code";
+
+enum ETempletePart {
+    Lit(String),
+    Param(FieldInfo),
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 struct TempleteData {
     id: String,
     #[serde(serialize_with = "utils::ordered_map")]
@@ -178,17 +233,572 @@ struct TempleteData {
 }
 
 impl TempleteData {
+    // 参数按字段类型转为 lua 代码: 字符串转义为字面量, 表达式原样插入
+    fn get_param(field: &FieldInfo, val: &String) -> String {
+        if field.val_type == EFieldType::Expr && !field.is_array {
+            return val.clone();
+        }
+        match field.cell_to_lua(val) {
+            Ok(v) => v.to_lua(),
+            Err(_) => data_value::lua_str_literal(val),
+        }
+    }
+
     fn get_expr(&self, info: &TempleteInfo) -> String {
         let mut expr = info.expr.clone();
+        for field in &info.field {
+            let val = check_some!(self.data.get(&field.name), continue);
+            let param = TempleteData::get_param(field, val);
+            let templete_key = format!("%{}%", field.name);
+            // 兼容模板中已经写了引号的 "%name%"
+            if param.starts_with("\"") {
+                for quote in ["\"", "'"] {
+                    let quoted = format!("{}{}{}", quote, templete_key, quote);
+                    expr = expr.replace(quoted.as_str(), param.as_str());
+                }
+            }
+            expr = expr.replace(templete_key.as_str(), param.as_str());
+        }
+        // 模板字段中已经没有的参数按原文本替换
         for (kk, vv) in &self.data {
             let templete_key = format!("%{}%", kk);
             expr = expr.replace(templete_key.as_str(), vv.as_str());
         }
         return expr;
     }
+
+    fn new_raw(code: String) -> TempleteData {
+        let mut data = HashMap::new();
+        data.insert(RAW_CODE.to_string(), code);
+        TempleteData {
+            id: RAW_TEMPLETE.to_string(),
+            data,
+        }
+    }
+
+    fn is_raw(&self) -> bool {
+        self.id == RAW_TEMPLETE
+    }
+
+    fn get_raw_code(&self) -> String {
+        match self.data.get(RAW_CODE) {
+            Some(s) => s.clone(),
+            None => String::new(),
+        }
+    }
+
+    // 模板表达式拆成字面量和参数, 带引号的 "%name%" 连同引号作为参数
+    fn split_pattern(info: &TempleteInfo) -> Vec<ETempletePart> {
+        let mut ret = Vec::new();
+        let mut rest = info.expr.as_str();
+        loop {
+            let mut found: Option<(usize, &FieldInfo)> = None;
+            for field in &info.field {
+                let pos = check_some!(rest.find(&format!("%{}%", field.name)), continue);
+                if found.map_or(true, |(p, _)| pos < p) {
+                    found = Some((pos, field));
+                }
+            }
+            let (pos, field) = match found {
+                Some(f) => f,
+                None => {
+                    ret.push(ETempletePart::Lit(rest.to_string()));
+                    break;
+                }
+            };
+            let mut lit = &rest[..pos];
+            let mut end = pos + field.name.len() + 2;
+            if field.val_type != EFieldType::Expr {
+                for quote in ["\"", "'"] {
+                    if lit.ends_with(quote) && rest[end..].starts_with(quote) {
+                        lit = &lit[..lit.len() - 1];
+                        end = end + 1;
+                        break;
+                    }
+                }
+            }
+            ret.push(ETempletePart::Lit(lit.to_string()));
+            ret.push(ETempletePart::Param(field.clone()));
+            rest = &rest[end..];
+        }
+        return ret;
+    }
+
+    // 与 get_param 相反, 从 lua 代码还原参数的单元格文本
+    fn param_to_cell(field: &FieldInfo, code: &str) -> Option<String> {
+        let code = code.trim();
+        if field.val_type == EFieldType::Expr && !field.is_array {
+            return Some(code.to_string());
+        }
+        let v = match ELuaValue::parse(&format!("{{{}}}", code)) {
+            Ok(ELuaValue::Table(mut list)) if list.len() == 1 => list.remove(0).1,
+            // 旧数据的字符串参数没有引号
+            _ if field.val_type == EFieldType::Str && !field.is_array => {
+                return Some(code.to_string())
+            }
+            _ => return None,
+        };
+        let ok = match (&v, &field.val_type) {
+            _ if field.is_array => v.is_table(),
+            (ELuaValue::Bool(_), EFieldType::Bool) => true,
+            (ELuaValue::Int(_), EFieldType::Number) => true,
+            (ELuaValue::Number(_), EFieldType::Number) => true,
+            (ELuaValue::Str(_), EFieldType::Str) => true,
+            (ELuaValue::Table(_), EFieldType::Table) => true,
+            _ => false,
+        };
+        check_if!(!ok, return None);
+        return Some(field.lua_to_cell(&v));
+    }
+
+    // 按模板匹配一段代码, 参数类型不符时视为不匹配
+    fn match_expr(key: &String, info: &TempleteInfo, code: &str) -> Option<TempleteData> {
+        let parts = TempleteData::split_pattern(info);
+        let mut matcher = PartMatcher::new(&parts, code);
+        let mut caps = Vec::new();
+        check_if!(!matcher.run(0, 0, &mut caps), return None);
+        let mut data = HashMap::new();
+        for field in &info.field {
+            data.insert(field.name.clone(), field.default_val.clone());
+        }
+        let params = parts.iter().filter_map(|a| match a {
+            ETempletePart::Param(f) => Some(f),
+            _ => None,
+        });
+        for (field, cap) in params.zip(caps.iter()) {
+            let cell = TempleteData::param_to_cell(field, &matcher.get_cap(*cap))?;
+            data.insert(field.name.clone(), cell);
+        }
+        return Some(TempleteData {
+            id: key.clone(),
+            data,
+        });
+    }
+
+    // 把手写的 lua 按行匹配模板, 无法识别的连续行作为原始代码保留
+    fn parse_expr(code: &String, templete: &HashMap<String, TempleteInfo>) -> Vec<TempleteData> {
+        // 固定内容多的模板优先, 避免只有参数的模板匹配所有代码
+        let mut list: Vec<(&String, &TempleteInfo, usize)> = templete
+            .iter()
+            .map(|(k, v)| (k, v, v.expr.lines().count().max(1)))
+            .collect();
+        list.sort_by_key(|(k, v, _)| {
+            let lit: usize = TempleteData::split_pattern(v)
+                .iter()
+                .map(|a| match a {
+                    ETempletePart::Lit(s) => s.len(),
+                    _ => 0,
+                })
+                .sum();
+            (usize::MAX - lit, (*k).clone())
+        });
+
+        let lines: Vec<&str> = code.lines().collect();
+        let mut ret = Vec::new();
+        let mut raw: Vec<&str> = Vec::new();
+        let mut i = 0;
+        while i < lines.len() {
+            let mut matched = None;
+            for (k, info, cnt) in &list {
+                check_if!(i + cnt > lines.len(), continue);
+                let text = lines[i..i + cnt].join("\n");
+                matched = TempleteData::match_expr(k, info, &text).map(|a| (a, *cnt));
+                check_if!(matched.is_some(), break);
+            }
+            match matched {
+                Some((one, cnt)) => {
+                    if !raw.is_empty() {
+                        ret.push(TempleteData::new_raw(raw.join("\n")));
+                        raw.clear();
+                    }
+                    ret.push(one);
+                    i += cnt;
+                }
+                None => {
+                    raw.push(lines[i]);
+                    i += 1;
+                }
+            }
+        }
+        if !raw.is_empty() {
+            ret.push(TempleteData::new_raw(raw.join("\n")));
+        }
+        return ret;
+    }
+
+    // 模板数据转为单元格内容: 第一行是模板数据, 后面是生成的代码
+    fn gen_cell(data: &Vec<TempleteData>, templete: &HashMap<String, TempleteInfo>) -> String {
+        let mut expr = Vec::new();
+        for one in data {
+            match templete.get(&one.id) {
+                Some(info) => expr.push(one.get_expr(info)),
+                None => expr.push(one.get_raw_code()),
+            }
+        }
+        let json = serde_json::to_string(data).unwrap();
+        return format!("--{}\r\n{}", json, expr.join("\r\n"));
+    }
+
+    // 参数按字段类型检查, 生成的表达式按所在字段的签名编译
+    fn check(&self, info: &TempleteInfo, suffix: &str) -> Vec<String> {
+        let mut ret = Vec::new();
+        for field in &info.field {
+            let val = check_some!(self.data.get(&field.name), continue);
+            let (err, msg) = field.check_data(val);
+            check_if!(err, ret.push(format!("参数[{}]: {}", field.title, msg)));
+        }
+        for err in data_expr::check_expr(&self.get_expr(info), suffix) {
+            ret.push(err.to_string());
+        }
+        return ret;
+    }
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// 字面量中连续的空白匹配任意空白, 但前后都是单词字符时至少要有一个空白
+// 字面量中相邻的字符之间可以有空白, 但不能把一个单词拆开, 如 "return x" 不匹配 "returnx"
+fn match_lit(text: &Vec<char>, pos: usize, lit: &str) -> Option<usize> {
+    let lit: Vec<char> = lit.chars().collect();
+    let mut pos = pos;
+    let mut i = 0;
+    while i < lit.len() {
+        if lit[i].is_whitespace() {
+            while i < lit.len() && lit[i].is_whitespace() {
+                i += 1;
+            }
+            let start = pos;
+            while pos < text.len() && text[pos].is_whitespace() {
+                pos += 1;
+            }
+            let prev = match start {
+                0 => None,
+                _ => Some(text[start - 1]),
+            };
+            let next = lit.get(i).or(text.get(pos)).copied();
+            check_if!(
+                pos == start && prev.map_or(false, is_word) && next.map_or(false, is_word),
+                return None
+            );
+            continue;
+        }
+        if i == 0 || !(is_word(lit[i - 1]) && is_word(lit[i])) {
+            while pos < text.len() && text[pos].is_whitespace() {
+                pos += 1;
+            }
+        }
+        check_if!(pos >= text.len() || text[pos] != lit[i], return None);
+        pos += 1;
+        i += 1;
+    }
+    return Some(pos);
+}
+
+// 按模板拆分的字面量和参数匹配代码, 记录失败的状态避免重复回溯
+struct PartMatcher<'a> {
+    parts: &'a Vec<ETempletePart>,
+    text: Vec<char>,
+    // 同名参数再次出现时, 对应第一次出现的捕获序号
+    same: Vec<Option<usize>>,
+    // 后面还会再次出现的参数的捕获序号
+    bound: Vec<usize>,
+    failed: HashSet<(usize, usize, Vec<(usize, usize)>)>,
+}
+
+impl<'a> PartMatcher<'a> {
+    fn new(parts: &'a Vec<ETempletePart>, code: &str) -> PartMatcher<'a> {
+        let mut same = Vec::new();
+        let mut bound = Vec::new();
+        let mut names: Vec<&String> = Vec::new();
+        for one in parts {
+            let field = match one {
+                ETempletePart::Param(f) => f,
+                ETempletePart::Lit(_) => {
+                    same.push(None);
+                    continue;
+                }
+            };
+            match names.iter().position(|a| **a == field.name) {
+                Some(first) => {
+                    same.push(Some(first));
+                    check_if!(!bound.contains(&first), bound.push(first));
+                }
+                None => same.push(None),
+            }
+            names.push(&field.name);
+        }
+        PartMatcher {
+            parts,
+            text: code.chars().collect(),
+            same,
+            bound,
+            failed: HashSet::new(),
+        }
+    }
+
+    fn get_cap(&self, (start, end): (usize, usize)) -> String {
+        return self.text[start..end].iter().collect();
+    }
+
+    // out 中记录参数捕获的 [开始, 结束) 位置
+    fn run(&mut self, idx: usize, pos: usize, out: &mut Vec<(usize, usize)>) -> bool {
+        // 匹配结果只和位置以及已捕获的同名参数有关
+        let bound: Vec<(usize, usize)> = self
+            .bound
+            .iter()
+            .filter_map(|i| out.get(*i).cloned())
+            .collect();
+        let key = (idx, pos, bound);
+        check_if!(self.failed.contains(&key), return false);
+        let ok = match &self.parts.get(idx) {
+            None => self.text[pos..].iter().all(|c| c.is_whitespace()),
+            Some(ETempletePart::Lit(lit)) => match match_lit(&self.text, pos, lit) {
+                Some(end) => self.run(idx + 1, end, out),
+                None => false,
+            },
+            Some(ETempletePart::Param(_)) => self.run_param(idx, pos, out),
+        };
+        check_if!(!ok, self.failed.insert(key));
+        return ok;
+    }
+
+    fn run_param(&mut self, idx: usize, pos: usize, out: &mut Vec<(usize, usize)>) -> bool {
+        // 同名参数必须和第一次出现的内容一致
+        if let Some(first) = self.same[idx] {
+            let prev = self.get_cap(out[first]);
+            let end = check_some!(match_lit(&self.text, pos, prev.trim()), return false);
+            out.push((pos, end));
+            check_if!(self.run(idx + 1, end, out), return true);
+            out.pop();
+            return false;
+        }
+        // 逐个字符扩展参数, 括号配对且字符串闭合时才能作为完整的参数
+        let mut stack = Vec::new();
+        let mut quote: Option<char> = None;
+        let mut escape = false;
+        let mut blank = true;
+        for end in pos + 1..=self.text.len() {
+            let c = self.text[end - 1];
+            blank = blank && c.is_whitespace();
+            if let Some(q) = quote {
+                if escape {
+                    escape = false;
+                } else if c == '\\' {
+                    escape = true;
+                } else if c == q {
+                    quote = None;
+                }
+            } else {
+                match c {
+                    '"' | '\'' => quote = Some(c),
+                    '(' | '[' | '{' => stack.push(c),
+                    ')' => check_if!(stack.pop() != Some('('), return false),
+                    ']' => check_if!(stack.pop() != Some('['), return false),
+                    '}' => check_if!(stack.pop() != Some('{'), return false),
+                    _ => {}
+                }
+            }
+            check_if!(blank || quote.is_some() || !stack.is_empty(), continue);
+            out.push((pos, end));
+            check_if!(self.run(idx + 1, end, out), return true);
+            out.pop();
+        }
+        return false;
+    }
+}
+
+struct TempleteParser {}
+
+impl Default for TempleteParser {
+    fn default() -> Self {
+        Self {}
+    }
+}
+
+impl egui::util::cache::ComputerMut<(&str, usize), Arc<Vec<TempleteData>>> for TempleteParser {
+    fn compute(&mut self, (code, _): (&str, usize)) -> Arc<Vec<TempleteData>> {
+        let templete = TEMPLETE_MAP_EXPR.lock().unwrap().clone();
+        Arc::new(TempleteData::parse_expr(&code.to_string(), &templete))
+    }
+}
+
+// 非模板格式的代码每帧都会显示识别结果, 按 (代码, 模板版本) 缓存
+fn parse_expr_cached(ctx: &egui::Context, code: &str) -> Arc<Vec<TempleteData>> {
+    type ParseCache = egui::util::cache::FrameCache<Arc<Vec<TempleteData>>, TempleteParser>;
+    let version = TEMPLETE_VERSION.load(Ordering::Relaxed);
+    ctx.memory_mut(|mem| mem.caches.cache::<ParseCache>().get((code, version)))
 }
 
 impl FieldInfo {
+    // 单元格文本转为带类型的 lua 值, 数组转为 lua 数组
+    pub fn cell_to_lua(&self, cell: &str) -> Result<ELuaValue> {
+        if self.is_array {
+            let element = self.get_element_field();
+            let mut list = Vec::new();
+            for one in utils::split_array(cell) {
+                let v = if element.is_array {
+                    element.cell_to_lua(&utils::unwrap_nested_array(&one))?
+                } else {
+                    element.cell_to_lua(&one)?
+                };
+                list.push((ETableKey::Index, v));
+            }
+            return Ok(ELuaValue::Table(list));
+        }
+        let ret = match self.val_type {
+            EFieldType::Bool => ELuaValue::Bool(cell.to_lowercase() == "true"),
+            EFieldType::Number if cell.trim().parse::<i64>().is_ok() => {
+                ELuaValue::Int(cell.trim().parse::<i64>().unwrap())
+            }
+            EFieldType::Number => match cell.trim().parse::<f64>() {
+                Ok(n) => ELuaValue::Number(n),
+                Err(_) if cell.trim().is_empty() => ELuaValue::Int(0),
+                // 保留原文本, 由数据检查提示错误
+                Err(_) => ELuaValue::Str(cell.to_string()),
+            },
+            EFieldType::Str | EFieldType::Expr => ELuaValue::Str(cell.to_string()),
+            EFieldType::Table => ELuaValue::parse(cell)?,
+        };
+        return Ok(ret);
+    }
+
+    // lua 值转回编辑用的单元格文本
+    pub fn lua_to_cell(&self, v: &ELuaValue) -> String {
+        if self.is_array {
+            let list = match v {
+                ELuaValue::Table(list) => list,
+                _ => return v.to_cell(),
+            };
+            let element = self.get_element_field();
+            let mut arr = Vec::new();
+            for (_, one) in list {
+                if element.is_array {
+                    arr.push(utils::wrap_nested_array(&element.lua_to_cell(one)));
+                } else {
+                    arr.push(element.lua_to_cell(one));
+                }
+            }
+            return utils::join_array(&arr);
+        }
+        match v {
+            ELuaValue::Bool(true) => "True".to_string(),
+            ELuaValue::Bool(false) => "false".to_string(),
+            _ => v.to_cell(),
+        }
+    }
+
+    fn get_sub_field(&self) -> Option<Vec<FieldInfo>> {
+        let templete = TEMPLETE_MAP_SUB_FIELD.lock().unwrap();
+        return templete.get(&self.sub_field_key).map(|a| a.field.clone());
+    }
+
+    // 子结构的值拆成各子字段的单元格文本, 未填写的使用默认值
+    fn sub_field_to_map(field: &Vec<FieldInfo>, table: &ELuaValue) -> HashMap<String, String> {
+        let mut map = HashMap::new();
+        for one in field {
+            let key = ETableKey::Name(one.name.clone());
+            let v = match table {
+                ELuaValue::Table(list) => list.iter().find(|(k, _)| *k == key).map(|(_, v)| v),
+                _ => None,
+            };
+            let cell = match v {
+                Some(v) => one.lua_to_cell(v),
+                None => one.default_val.clone(),
+            };
+            map.insert(one.name.clone(), cell);
+        }
+        return map;
+    }
+
+    // 按子字段顺序和类型组装子结构, 模板中没有的键原样保留
+    fn map_to_sub_field(
+        field: &Vec<FieldInfo>,
+        map: &HashMap<String, String>,
+        old: &ELuaValue,
+    ) -> ELuaValue {
+        let mut list = Vec::new();
+        for one in field {
+            let cell = check_some!(map.get(&one.name), continue);
+            let v = match one.cell_to_lua(cell) {
+                Ok(v) => v,
+                Err(_) => ELuaValue::Str(cell.clone()),
+            };
+            list.push((ETableKey::Name(one.name.clone()), v));
+        }
+        if let ELuaValue::Table(old) = old {
+            for (k, v) in old {
+                let known = match k {
+                    ETableKey::Name(n) => field.iter().any(|a| a.name == *n),
+                    _ => false,
+                };
+                check_if!(!known, list.push((k.clone(), v.clone())));
+            }
+        }
+        return ELuaValue::Table(list);
+    }
+
+    // 子结构编辑, 嵌套的子结构默认折叠, 避免模板自引用时无限展开
+    fn create_sub_field_ui(&self, val: &mut String, ui: &mut egui::Ui, idx: i32) -> bool {
+        let field = self.get_sub_field();
+        let table = ELuaValue::parse(val);
+        let msg = match (&field, &table) {
+            (None, _) => format!("未找到模板[{}]", self.sub_field_key),
+            (_, Err(e)) => format!("格式错误[{}]", e),
+            _ => String::new(),
+        };
+        if !msg.is_empty() {
+            let txt = egui::TextEdit::multiline(val).desired_width(f32::INFINITY);
+            let flag = ui.add(txt).gained_focus();
+            ui.label(egui::RichText::new(msg).color(Color32::RED));
+            return flag;
+        }
+        let field = field.unwrap();
+        let table = table.unwrap();
+        let mut map = FieldInfo::sub_field_to_map(&field, &table);
+        let old = map.clone();
+
+        let mut flag = false;
+        let depth = SUB_FIELD_DEPTH.fetch_add(1, Ordering::Relaxed);
+        if depth == 0 {
+            flag = self.draw_one_templete(&field, &mut map, ui, idx);
+        } else {
+            let id = ui.make_persistent_id(format!("{}_{}_sub_field", self.name, idx));
+            egui::CollapsingHeader::new(self.sub_field_key.clone())
+                .id_source(id)
+                .default_open(false)
+                .show(ui, |ui| {
+                    flag = self.draw_one_templete(&field, &mut map, ui, idx);
+                });
+        }
+        SUB_FIELD_DEPTH.fetch_sub(1, Ordering::Relaxed);
+
+        if old != map {
+            *val = FieldInfo::map_to_sub_field(&field, &map, &table).to_lua();
+        }
+        return flag;
+    }
+
+    // 递归检查子结构中每个子字段的值
+    fn check_sub_field(&self, val: &String) -> (bool, String) {
+        let field = match self.get_sub_field() {
+            Some(f) => f,
+            None => return (true, format!("未找到模板[{}]", self.sub_field_key)),
+        };
+        let table = match ELuaValue::parse(val) {
+            Ok(t) => t,
+            Err(e) => return (true, format!("格式错误[{}]", e)),
+        };
+        let map = FieldInfo::sub_field_to_map(&field, &table);
+        for one in &field {
+            let cell = check_some!(map.get(&one.name), continue);
+            let (ret, msg) = one.check_data(cell);
+            check_if!(ret, return (true, format!("{}: {}", one.name, msg)));
+        }
+        return (false, String::new());
+    }
+
     fn draw_one_templete(
         &self,
         field: &Vec<FieldInfo>,
@@ -245,18 +855,75 @@ impl FieldInfo {
         return click_flag;
     }
 
+    // 逐个模板实例检查, 错误信息带上实例序号
+    fn check_templete(&self, val: &String) -> (bool, String) {
+        let json = val.lines().next().unwrap_or("").trim_start_matches("-");
+        let data: Vec<TempleteData> = match serde_json::from_str(json) {
+            Ok(d) => d,
+            Err(_) => return (false, String::new()),
+        };
+        // 检查参数时可能再次读取模板, 不能持有锁
+        let templete = TEMPLETE_MAP_EXPR.lock().unwrap().clone();
+        for (i, one) in data.iter().enumerate() {
+            let err = match templete.get(&one.id) {
+                Some(info) => one.check(info, &self.suffix),
+                None => data_expr::check_expr(&one.get_raw_code(), &self.suffix)
+                    .iter()
+                    .map(|a| a.to_string())
+                    .collect(),
+            };
+            check_if!(
+                !err.is_empty(),
+                return (true, format!("模板{}: {}", i + 1, err[0]))
+            );
+        }
+        return (false, String::new());
+    }
+
+    // 单元格中使用的模板, 不是模板格式时返回空
+    pub fn get_templete_ids(val: &String) -> Vec<String> {
+        let json = val.lines().next().unwrap_or("").trim_start_matches("-");
+        let data: Vec<TempleteData> = match serde_json::from_str(json) {
+            Ok(d) => d,
+            Err(_) => return Vec::new(),
+        };
+        return data.into_iter().map(|a| a.id).collect();
+    }
+
+    // 按当前的模板定义重新生成代码, 第一行的模板数据保持不变
+    pub fn regen_templete(
+        val: &String,
+        templete: &HashMap<String, TempleteInfo>,
+    ) -> Result<String> {
+        let first = val.lines().next().unwrap_or("");
+        let data: Vec<TempleteData> = serde_json::from_str(first.trim_start_matches("-"))?;
+        let mut expr = Vec::new();
+        for one in &data {
+            if one.is_raw() {
+                expr.push(one.get_raw_code());
+                continue;
+            }
+            match templete.get(&one.id) {
+                Some(info) => expr.push(one.get_expr(info)),
+                None => bail!(format!("模板[{}]未找到", one.id)),
+            }
+        }
+        return Ok(format!("{}\r\n{}", first, expr.join("\r\n")));
+    }
+
     fn draw_templete(
         &self,
         data: &mut Vec<TempleteData>,
         ui: &mut egui::Ui,
         idx: i32,
     ) -> (bool, String) {
-        let templete = TEMPLETE_MAP_EXPR.lock().unwrap();
+        // 参数编辑器中可能再次读取模板, 不能持有锁
+        let templete = TEMPLETE_MAP_EXPR.lock().unwrap().clone();
         let mut click = false;
         let mut ret = Vec::new();
         let mut list = Vec::new();
         let mut first = String::new();
-        for (k, v) in &*templete {
+        for (k, v) in &templete {
             if first.is_empty() {
                 first = k.clone();
             }
@@ -269,21 +936,25 @@ impl FieldInfo {
             ui.label(err_info);
             return (false, String::new());
         }
+        list.push(("原始代码".to_string(), RAW_TEMPLETE.to_string()));
 
         let mut child_idx = 0;
         for one in data {
             child_idx = child_idx + 1;
-            let mut key = &one.id;
+            let mut key = one.id.clone();
             let mut reset = false;
-            if !templete.contains_key(key) {
-                key = &first;
+            if !one.is_raw() && !templete.contains_key(&key) {
+                key = first.clone();
                 reset = true;
             }
-            let info = templete.get(key).unwrap();
+            let title = match templete.get(&key) {
+                Some(info) => info.title.clone(),
+                None => "原始代码".to_string(),
+            };
             let id = format!("{}_{}_{}_combobox", self.name, idx, child_idx);
             let mut new_id = key.clone();
             egui::ComboBox::from_id_source(id)
-                .selected_text(info.title.clone())
+                .selected_text(title)
                 .show_ui(ui, |ui| {
                     for (show, key) in &list {
                         let resp = ui.selectable_value(&mut new_id, key.clone(), show);
@@ -296,18 +967,47 @@ impl FieldInfo {
                         }
                     }
                 });
-            one.id = new_id;
             if reset {
-                let info = templete.get(&one.id).unwrap();
-                for field in &info.field {
-                    one.data
-                        .insert(field.name.clone(), field.default_val.clone());
+                // 从模板切换为原始代码时保留生成的代码
+                let code = match templete.get(&one.id) {
+                    Some(info) => one.get_expr(info),
+                    None => one.get_raw_code(),
+                };
+                one.id = new_id;
+                one.data.clear();
+                match templete.get(&one.id) {
+                    Some(info) => {
+                        for field in &info.field {
+                            one.data
+                                .insert(field.name.clone(), field.default_val.clone());
+                        }
+                    }
+                    None => {
+                        one.data.insert(RAW_CODE.to_string(), code);
+                    }
                 }
             }
+
+            if one.is_raw() {
+                let mut code = one.get_raw_code();
+                let errors = data_expr::check_expr_cached(ui.ctx(), &code, &self.suffix);
+                let id = egui::Id::new(format!("{}_{}_{}_raw", self.name, idx, child_idx));
+                if code_editor_ui(ui, id, &mut code, "lua", &errors, &[]) {
+                    click = true;
+                }
+                one.data.insert(RAW_CODE.to_string(), code.clone());
+                ret.push(code);
+                continue;
+            }
+
+            let info = templete.get(&one.id).unwrap();
             let id = format!("{}_{}_{}_CollapsingHeader", self.name, idx, child_idx);
             let expr = one.get_expr(info);
 
             show_code_view_ui(ui, expr.as_str(), "lua");
+            for err in one.check(info, &self.suffix) {
+                ui.label(egui::RichText::new(err).color(Color32::RED));
+            }
             // ui.label(expr);
             egui::CollapsingHeader::new(info.title.clone())
                 .id_source(id)
@@ -321,6 +1021,240 @@ impl FieldInfo {
         return (false, ret.join("\r\n"));
     }
 
+    // 枚举选项, 来源于其他表格或共享枚举时返回 None 表示来源未找到
+    pub fn get_opt(&self) -> Option<Arc<Vec<EnumOption>>> {
+        if self.opt_source == EOptionSource::Static {
+            return Some(Arc::new(self.opt.clone()));
+        }
+        return data_option::get_source_option(&self.opt_source);
+    }
+
+    fn get_bit_mask(opt: &Vec<EnumOption>) -> u32 {
+        let mut mask = 0;
+        for one in opt {
+            mask = mask | one.val.parse::<u32>().unwrap_or(0);
+        }
+        return mask;
+    }
+
+    // 共享枚举配置了按名称导出时返回其选项
+    fn get_export_name_opt(&self) -> Option<Arc<Vec<EnumOption>>> {
+        let name = match &self.opt_source {
+            EOptionSource::Enum(name) => name,
+            _ => return None,
+        };
+        let def = data_option::get_enum_def(name)?;
+        if !def.export_name {
+            return None;
+        }
+        return Some(def.opt.clone());
+    }
+
+    pub fn is_export_name(&self) -> bool {
+        self.get_export_name_opt().is_some()
+    }
+
+    fn get_one_export_val(&self, val: &str, opt: &Vec<EnumOption>) -> String {
+        let code = |one: &EnumOption| {
+            if one.code.is_empty() {
+                one.show.clone()
+            } else {
+                one.code.clone()
+            }
+        };
+        if self.editor_type == EEditorType::BitFlag {
+            let num = val.parse::<u32>().unwrap_or(0);
+            let mut ret = Vec::new();
+            for one in opt {
+                let bit = check_some!(one.val.parse::<u32>().ok(), continue);
+                check_if!(num & bit == 0, continue);
+                ret.push(code(one));
+            }
+            return ret.join("|");
+        }
+        for one in opt {
+            if one.val == val {
+                return code(one);
+            }
+        }
+        return val.to_string();
+    }
+
+    // 测试用例等按 "表格.字段" 区分
+    pub fn get_field_key(&self) -> String {
+        format!("{}.{}", self.table_key, self.name)
+    }
+
+    // 数组元素对应的字段, 嵌套数组的元素仍是数组
+    pub fn get_element_field(&self) -> FieldInfo {
+        let mut ret = self.clone();
+        ret.array_depth = self.array_depth - 1;
+        ret.is_array = ret.array_depth > 0;
+        return ret;
+    }
+
+    // 导出时的值, 按名称导出的共享枚举会把数值转换为名称
+    pub fn get_export_val(&self, val: &String) -> String {
+        let opt = check_some!(self.get_export_name_opt(), return val.clone());
+        if self.is_array {
+            let element = self.get_element_field();
+            let mut arr = Vec::new();
+            for one in utils::split_array(val) {
+                if element.is_array {
+                    let v = element.get_export_val(&utils::unwrap_nested_array(&one));
+                    arr.push(utils::wrap_nested_array(&v));
+                } else {
+                    arr.push(self.get_one_export_val(&one, &opt));
+                }
+            }
+            return utils::join_array(&arr);
+        }
+        return self.get_one_export_val(val, &opt);
+    }
+
+    fn get_ref_label(&self, val: &String, opts: &Option<Arc<Vec<EnumOption>>>) -> egui::RichText {
+        let opts = match opts {
+            Some(s) => s,
+            None => {
+                let msg = format!("关联表[{}]未找到", self.link_table);
+                return egui::RichText::new(msg).color(Color32::RED);
+            }
+        };
+        if val.is_empty() {
+            return egui::RichText::new("");
+        }
+        for one in opts.iter() {
+            if one.val == *val {
+                return egui::RichText::new(one.show.clone());
+            }
+        }
+        return egui::RichText::new("无效引用").color(Color32::RED);
+    }
+
+    // 带搜索的选项列表, 返回点击的选项, 多选时显示为勾选列表
+    fn draw_option_search(
+        &self,
+        ui: &mut egui::Ui,
+        id: egui::Id,
+        opts: &Vec<EnumOption>,
+        selected: &Vec<String>,
+        multi: bool,
+    ) -> Option<String> {
+        let mut ret = None;
+        let mut search = ui.data_mut(|d| d.get_temp_mut_or_default::<String>(id).clone());
+        let txt = egui::TextEdit::singleline(&mut search)
+            .hint_text("搜索")
+            .desired_width(f32::INFINITY);
+        ui.add(txt);
+        egui::ScrollArea::vertical()
+            .id_source(id)
+            .max_height(200.0)
+            .show(ui, |ui| {
+                for one in opts {
+                    let show = format!("[{}]{}", one.val, one.show);
+                    check_if!(!utils::fuzzy_match(&show, &search), continue);
+                    let mut select = selected.contains(&one.val);
+                    let click = if multi {
+                        ui.checkbox(&mut select, show).clicked()
+                    } else {
+                        ui.selectable_label(select, show).clicked()
+                    };
+                    if click {
+                        ret = Some(one.val.clone());
+                    }
+                }
+            });
+        ui.data_mut(|d| d.insert_temp(id, search));
+        return ret;
+    }
+
+    // 枚举/引用数组的多选编辑: 已选条目显示为可排序的标签, 下方为带搜索的勾选列表
+    // 只能调整顺序, 不提供插入/复制, 避免产生重复项
+    fn create_multi_select_ui(
+        &self,
+        val: &mut String,
+        opts: Option<Arc<Vec<EnumOption>>>,
+        ui: &mut egui::Ui,
+        idx: i32,
+    ) -> bool {
+        let mut flag = false;
+        let mut arr = utils::split_array(val);
+        ui.vertical(|ui| {
+            let mut remove = None;
+            let mut swap = None;
+            ui.horizontal_wrapped(|ui| {
+                let len = arr.len();
+                for i in 0..len {
+                    let one = &arr[i];
+                    let mut txt =
+                        egui::RichText::new(format!("[{}]未定义", one)).color(Color32::RED);
+                    let found = match &opts {
+                        Some(opts) => opts.iter().find(|a| a.val == *one),
+                        None => None,
+                    };
+                    if found.is_some() {
+                        txt = egui::RichText::new(format!("[{}]{}", one, found.unwrap().show));
+                    }
+                    if arr[..i].contains(one) {
+                        txt = txt.color(Color32::YELLOW);
+                    }
+                    ui.group(|ui| {
+                        if i > 0 && ui.small_button("◀").clicked() {
+                            swap = Some((i - 1, i));
+                        }
+                        ui.label(txt);
+                        if i + 1 < len && ui.small_button("▶").clicked() {
+                            swap = Some((i, i + 1));
+                        }
+                        if ui.small_button("✖").on_hover_text("移除").clicked() {
+                            remove = Some(i);
+                        }
+                    });
+                }
+            });
+            if swap.is_some() {
+                let (a, b) = swap.unwrap();
+                arr.swap(a, b);
+                flag = true;
+            }
+            if remove.is_some() {
+                arr.remove(remove.unwrap());
+                flag = true;
+            }
+            if opts.is_none() {
+                let msg = match self.editor_type {
+                    EEditorType::Reference => format!("关联表[{}]未找到", self.link_table),
+                    _ => format!("选项来源[{:?}]未找到", self.opt_source),
+                };
+                ui.label(egui::RichText::new(msg).color(Color32::RED));
+                return;
+            }
+            let opts = opts.unwrap();
+            let id = ui.make_persistent_id(format!("{}_{}_multi_select", self.name, idx));
+            egui::CollapsingHeader::new("选择")
+                .id_source(id)
+                .show(ui, |ui| {
+                    let click = self.draw_option_search(ui, id, &opts, &arr, true);
+                    if click.is_none() {
+                        return;
+                    }
+                    // 已选中的再次点击为取消, 不会产生重复项
+                    let click = click.unwrap();
+                    flag = true;
+                    match arr.iter().position(|a| *a == click) {
+                        Some(pos) => {
+                            arr.remove(pos);
+                        }
+                        None => arr.push(click),
+                    }
+                });
+        });
+        if flag {
+            *val = utils::join_array(&arr);
+        }
+        return flag;
+    }
+
     fn create_one_ui(&self, val: &String, ui: &mut egui::Ui, idx: i32) -> (bool, String) {
         let mut flag = false;
         let mut ret = String::new();
@@ -367,7 +1301,29 @@ impl FieldInfo {
                     };
 
                     if self.val_type == EFieldType::Expr {
-                        if code_view_ui(ui, &mut v, "lua") {
+                        let id = ui.make_persistent_id(format!("{}_{}_expr", self.name, idx));
+                        let errors = data_expr::check_expr_cached(ui.ctx(), &v, &self.suffix);
+                        let (_, params) = data_expr::parse_signature(&self.suffix);
+                        let completion = data_expr::get_completion(&params);
+                        if code_editor_ui(ui, id, &mut v, "lua", &errors, &completion) {
+                            flag = true;
+                        }
+                        let test_id = id.with("test");
+                        let mut open = ui.data_mut(|d| *d.get_temp_mut_or_default::<bool>(test_id));
+                        ui.toggle_value(&mut open, "🧪测试");
+                        if open {
+                            let key = self.get_field_key();
+                            data_expr::draw_test_panel(
+                                ui,
+                                id.with("test_panel"),
+                                &key,
+                                &v,
+                                &self.suffix,
+                            );
+                        }
+                        ui.data_mut(|d| d.insert_temp(test_id, open));
+                    } else if self.val_type == EFieldType::Table {
+                        if self.create_table_ui(&mut v, ui, idx) {
                             flag = true;
                         }
                     } else {
@@ -417,10 +1373,24 @@ impl FieldInfo {
                             let data = serde_json::to_string(&data).unwrap();
                             ret = format!("--{}\r\n{}", data, expr);
                         } else {
-                            ui.label("格式错误, 请删除表达式后再使用模板功能");
+                            // 手写的代码尝试按模板识别, 识别不了的部分保留为原始代码
+                            let data = parse_expr_cached(ui.ctx(), &v);
+                            let cnt = data.iter().filter(|a| !a.is_raw()).count();
+                            ui.label("非模板格式, 可以转换为模板后编辑");
+                            let txt = format!(
+                                "转换为模板(识别{}个, 原始代码{}段)",
+                                cnt,
+                                data.len() - cnt
+                            );
+                            let convert = ui.button(txt).clicked();
                             let txt =
                                 egui::TextEdit::multiline(&mut v).desired_width(f32::INFINITY);
                             ui.add(txt);
+                            if convert {
+                                let templete = TEMPLETE_MAP_EXPR.lock().unwrap().clone();
+                                v = TempleteData::gen_cell(&data, &templete);
+                                flag = true;
+                            }
                             ret = v;
                         }
                     });
@@ -432,19 +1402,27 @@ impl FieldInfo {
                         Ok(n) => n,
                         Err(_) => 0,
                     };
+                    let opt = match self.get_opt() {
+                        Some(s) => s,
+                        None => {
+                            let msg = format!("选项来源[{:?}]未找到", self.opt_source);
+                            ui.label(egui::RichText::new(msg).color(Color32::RED));
+                            Arc::new(Vec::new())
+                        }
+                    };
 
                     ui.collapsing(self.title.clone(), |ui| {
-                        let mut bit = 1;
-                        let mut result = 0;
-                        for name in &self.bit_name {
+                        // 未定义的位保持原样, 由数据检查提示
+                        let mut result = num & !FieldInfo::get_bit_mask(&opt);
+                        for one in opt.iter() {
+                            let bit = check_some!(one.val.parse::<u32>().ok(), continue);
                             let mut select = num & bit != 0;
-                            if ui.checkbox(&mut select, name).gained_focus() {
+                            if ui.checkbox(&mut select, &one.show).gained_focus() {
                                 flag = true;
                             }
                             if select {
-                                result = result + bit;
+                                result = result | bit;
                             }
-                            bit = bit << 1;
                         }
                         v = result.to_string();
                     });
@@ -497,41 +1475,52 @@ impl FieldInfo {
                     });
                 }
                 EEditorType::SubField => {
-                    let templete = TEMPLETE_MAP_SUB_FIELD.lock().unwrap();
-                    let info = templete.get(&self.sub_field_key);
                     let mut v = val.clone();
-                    let data = utils::tablestr2map(&v);
-
-                    let mut msg = String::new();
-                    if data.is_err() {
-                        msg = format!("格式错误[{:?}]", data);
-                    }
-                    if info.is_none() {
-                        msg = format!("未找到模板[{}]", self.sub_field_key);
+                    if self.create_sub_field_ui(&mut v, ui, idx) {
+                        flag = true;
                     }
-
-                    if !msg.is_empty() {
-                        let txt = egui::TextEdit::multiline(&mut v).desired_width(f32::INFINITY);
-                        ui.add(txt);
-                        let err_info = egui::RichText::new(msg).color(Color32::RED);
-                        ui.label(err_info);
-                    } else {
-                        let mut data = data.unwrap();
-                        let info = info.unwrap();
-                        self.draw_one_templete(&info.field, &mut data, ui, idx);
-                        let ret = map2tablestr(&data);
-                        if ret.is_ok() {
-                            v = ret.unwrap();
+                    ret = v;
+                }
+                EEditorType::Reference => {
+                    let mut v = val.clone();
+                    let opts = data_option::get_table_option(&self.link_table);
+                    ui.horizontal(|ui| {
+                        let txt = egui::TextEdit::singleline(&mut v).desired_width(80.0);
+                        if ui.add(txt).gained_focus() {
+                            flag = true;
                         }
+                        ui.label(self.get_ref_label(&v, &opts));
+                    });
+                    if opts.is_some() {
+                        let opts = opts.unwrap();
+                        let id = ui.make_persistent_id(format!("{}_{}_reference", self.name, idx));
+                        egui::CollapsingHeader::new("选择")
+                            .id_source(id)
+                            .show(ui, |ui| {
+                                let selected = vec![v.clone()];
+                                let click =
+                                    self.draw_option_search(ui, id, &opts, &selected, false);
+                                if click.is_some() {
+                                    v = click.unwrap();
+                                    flag = true;
+                                }
+                            });
                     }
-
                     ret = v;
                 }
                 EEditorType::Enum => {
                     let mut v = val.clone();
+                    let opt = match self.get_opt() {
+                        Some(s) => s,
+                        None => {
+                            let msg = format!("选项来源[{:?}]未找到", self.opt_source);
+                            ui.label(egui::RichText::new(msg).color(Color32::RED));
+                            Arc::new(Vec::new())
+                        }
+                    };
                     let mut txt = String::new();
                     let mut found = false;
-                    for one in &self.opt {
+                    for one in opt.iter() {
                         if one.val != v {
                             continue;
                         }
@@ -552,7 +1541,7 @@ impl FieldInfo {
                         .width(size.x * 0.95)
                         .selected_text(label)
                         .show_ui(ui, |ui| {
-                            for one in &self.opt {
+                            for one in opt.iter() {
                                 let show = format!("[{}]{}", one.val, one.show);
                                 ui.selectable_value(&mut v, one.val.clone(), show);
                             }
@@ -570,7 +1559,13 @@ impl FieldInfo {
         return (flag, ret);
     }
 
+    // 检查结果按 (字段, 值) 缓存, 界面每帧调用也不会重复编译表达式
     fn check_one_data(&self, val: &String) -> (bool, String) {
+        let key = format!("{}#one", self.get_field_key());
+        data_check::check_cached(key, val, || self.check_one_data_impl(val))
+    }
+
+    fn check_one_data_impl(&self, val: &String) -> (bool, String) {
         let mut ret = false;
         let mut msg = String::new();
         // 类型检查
@@ -585,45 +1580,96 @@ impl FieldInfo {
                 }
             }
             EFieldType::Expr => {
-                let v = val.clone();
-                let lua = mlua::Lua::new();
-                let mut body = v;
-                if !self.suffix.starts_with("void") && !body.contains("return") {
-                    body = format!("return {}", body);
-                }
-                let s = format!(
-                    r#"return function()
-                {}
-                end"#,
-                    body
-                );
-                let result = lua.load(s.as_str()).exec();
-                match result {
-                    Ok(_) => {}
-                    Err(e) => {
+                let errors = data_expr::check_expr(val, &self.suffix);
+                if !errors.is_empty() {
+                    ret = true;
+                    msg = errors
+                        .iter()
+                        .map(|a| a.to_string())
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                } else if let Some(err) =
+                    data_expr::run_test(&self.get_field_key(), val, &self.suffix)
+                {
+                    ret = true;
+                    msg = err;
+                }
+            }
+            EFieldType::Table => {
+                if let Err(e) = ELuaValue::parse(val).and_then(|a| a.check_value()) {
+                    ret = true;
+                    msg = format!("表格格式错误[{}]", e);
+                }
+            }
+            _ => {} // 其他不检查
+        }
+
+        check_if!(ret || val.is_empty(), return (ret, msg));
+        match self.editor_type {
+            EEditorType::Reference => {
+                let opts = data_option::get_table_option(&self.link_table);
+                match opts {
+                    Some(opts) => {
+                        if !opts.iter().any(|a| a.val == *val) {
+                            ret = true;
+                            msg = format!("关联表[{}]中不存在[{}]", self.link_table, val);
+                        }
+                    }
+                    None => {
                         ret = true;
-                        msg = e.to_string();
+                        msg = format!("关联表[{}]未找到", self.link_table);
                     }
                 }
             }
-            _ => {} // 其他不检查
+            EEditorType::SubField => (ret, msg) = self.check_sub_field(val),
+            EEditorType::TempleteExpr => (ret, msg) = self.check_templete(val),
+            EEditorType::Enum => match self.get_opt() {
+                Some(opt) => {
+                    if !opt.iter().any(|a| a.val == *val) {
+                        ret = true;
+                        msg = format!("枚举值[{}]未定义", val);
+                    }
+                }
+                None => {
+                    ret = true;
+                    msg = format!("选项来源[{:?}]未找到", self.opt_source);
+                }
+            },
+            EEditorType::BitFlag => match self.get_opt() {
+                Some(opt) => {
+                    let num = val.parse::<u32>().unwrap_or(0);
+                    if num & !FieldInfo::get_bit_mask(&opt) != 0 {
+                        ret = true;
+                        msg = format!("位标记[{}]包含未定义的位", val);
+                    }
+                }
+                None => {
+                    ret = true;
+                    msg = format!("选项来源[{:?}]未找到", self.opt_source);
+                }
+            },
+            _ => {}
         }
         return (ret, msg);
     }
 
-    #[allow(dead_code)]
     pub fn check_data(&self, val: &String) -> (bool, String) {
+        let key = format!("{}#{}", self.get_field_key(), self.array_depth);
+        data_check::check_cached(key, val, || self.check_data_impl(val))
+    }
+
+    fn check_data_impl(&self, val: &String) -> (bool, String) {
         let mut ret = false;
         let mut msg = String::new();
 
         if self.is_array {
-            let mut arr: Vec<&str> = Vec::new();
-            if !val.is_empty() {
-                arr = val.split(";").collect();
-            }
-            for one in arr {
-                let s = one.to_string();
-                (ret, msg) = self.check_one_data(&s);
+            let element = self.get_element_field();
+            for one in utils::split_array(val) {
+                (ret, msg) = if element.is_array {
+                    element.check_data(&utils::unwrap_nested_array(&one))
+                } else {
+                    self.check_one_data(&one)
+                };
                 if ret {
                     break;
                 }
@@ -634,6 +1680,250 @@ impl FieldInfo {
         return (ret, msg);
     }
 
+    // 表格(M)编辑: 键值树, 格式错误或选择文本编辑时直接编辑 lua 文本
+    fn create_table_ui(&self, val: &mut String, ui: &mut egui::Ui, idx: i32) -> bool {
+        let mut flag = false;
+        let id = ui.make_persistent_id(format!("{}_{}_table", self.name, idx));
+        let text_id = id.with("text_mode");
+        let mut text_mode = ui.data_mut(|d| *d.get_temp_mut_or_default::<bool>(text_id));
+        // 有重复键等错误时改动暂存在界面数据中, 不写回单元格
+        let pending_id = id.with("pending");
+        let pending = ui.data_mut(|d| d.get_temp::<(String, ELuaValue)>(pending_id));
+        let table = ELuaValue::parse(val);
+        ui.checkbox(&mut text_mode, "文本编辑");
+        match table {
+            Ok(old) if !text_mode => {
+                let mut table = match pending {
+                    Some((base, pending)) if base == *val => pending,
+                    _ => old.clone(),
+                };
+                if data_value::draw_table_tree(ui, &mut table, id) {
+                    flag = true;
+                }
+                match table.check_value() {
+                    Ok(_) => {
+                        ui.data_mut(|d| d.remove::<(String, ELuaValue)>(pending_id));
+                        // 未改动时保留原文本, 避免仅格式不同也产生修改
+                        if old != table {
+                            *val = table.to_lua();
+                        }
+                    }
+                    Err(e) => {
+                        let msg = format!("未保存: {}", e);
+                        ui.label(egui::RichText::new(msg).color(Color32::RED));
+                        ui.data_mut(|d| d.insert_temp(pending_id, (val.clone(), table)));
+                    }
+                }
+            }
+            table => {
+                let txt = egui::TextEdit::multiline(val).desired_width(f32::INFINITY);
+                if ui.add(txt).gained_focus() {
+                    flag = true;
+                }
+                if let Err(e) = table {
+                    let msg = format!("表格格式错误[{}]", e);
+                    ui.label(egui::RichText::new(msg).color(Color32::RED));
+                }
+            }
+        }
+        ui.data_mut(|d| d.insert_temp(text_id, text_mode));
+        return flag;
+    }
+
+    // 数组编辑: 复制/粘贴/文本编辑 + 按元素类型编辑
+    fn create_array_ui(&self, val: &mut String, ui: &mut egui::Ui, start: i32) -> bool {
+        let mut flag = false;
+        let id = ui.make_persistent_id(format!("{}_{}_array", self.name, start));
+        let text_id = id.with("text_mode");
+        let mut text_mode = ui.data_mut(|d| *d.get_temp_mut_or_default::<bool>(text_id));
+        ui.vertical(|ui| {
+            ui.horizontal(|ui| {
+                let clipboard = egui::Id::new(ARRAY_CLIPBOARD);
+                if ui.small_button("📋").on_hover_text("复制数组").clicked() {
+                    ui.output_mut(|o| o.copied_text = val.clone());
+                    ui.data_mut(|d| d.insert_temp(clipboard, val.clone()));
+                }
+                if ui.small_button("📥").on_hover_text("粘贴数组").clicked() {
+                    let copied = ui.data_mut(|d| d.get_temp::<String>(clipboard));
+                    if copied.is_some() {
+                        *val = copied.unwrap();
+                        flag = true;
+                    }
+                }
+                ui.checkbox(&mut text_mode, "文本编辑");
+            });
+            if text_mode {
+                let txt = egui::TextEdit::multiline(val).desired_width(f32::INFINITY);
+                if ui.add(txt).gained_focus() {
+                    flag = true;
+                }
+                let (has_err, msg) = self.check_data(val);
+                if has_err {
+                    ui.label(egui::RichText::new(msg).color(Color32::RED));
+                }
+                return;
+            }
+            let f = match self.editor_type {
+                _ if self.array_depth > 1 => self.create_element_list_ui(val, ui, id, start),
+                EEditorType::Reference => {
+                    let opts = data_option::get_table_option(&self.link_table);
+                    self.create_multi_select_ui(val, opts, ui, start)
+                }
+                EEditorType::Enum => self.create_multi_select_ui(val, self.get_opt(), ui, start),
+                _ => self.create_element_list_ui(val, ui, id, start),
+            };
+            if f {
+                flag = true;
+            }
+        });
+        ui.data_mut(|d| d.insert_temp(text_id, text_mode));
+        return flag;
+    }
+
+    // 逐个元素编辑, 每个元素可以插入/删除/移动/复制, 拖动 ☰ 调整顺序
+    // 枚举/引用数组使用 create_multi_select_ui, 不会走到这里
+    fn create_element_list_ui(
+        &self,
+        val: &mut String,
+        ui: &mut egui::Ui,
+        id: egui::Id,
+        start: i32,
+    ) -> bool {
+        let mut flag = false;
+        let arr = utils::split_array(val);
+        let drag_id = id.with("drag");
+        let drag_src = ui.data_mut(|d| d.get_temp::<usize>(drag_id));
+        let pointer = ui.input(|i| i.pointer.interact_pos());
+        let released = ui.input(|i| i.pointer.any_released());
+        let mut op = None;
+        let mut new = Vec::new();
+        ui.vertical_centered(|ui| {
+            ui.horizontal(|ui| {
+                if ui.button("+").clicked() {
+                    op = Some(EArrayOp::Insert(arr.len()));
+                }
+                if ui.button("-").clicked() && arr.len() > 0 {
+                    op = Some(EArrayOp::Remove(arr.len() - 1));
+                }
+            });
+            let mut idx = start;
+            let len = arr.len();
+            for i in 0..len {
+                idx = idx + 1;
+                let row = ui.horizontal(|ui| {
+                    let handle = ui
+                        .add(egui::Label::new("☰").sense(egui::Sense::drag()))
+                        .on_hover_text("拖动调整顺序");
+                    if handle.drag_started() {
+                        ui.data_mut(|d| d.insert_temp(drag_id, i));
+                    }
+                    ui.menu_button("⋯", |ui| {
+                        if ui.button("上方插入").clicked() {
+                            op = Some(EArrayOp::Insert(i));
+                            ui.close_menu();
+                        }
+                        if ui.button("下方插入").clicked() {
+                            op = Some(EArrayOp::Insert(i + 1));
+                            ui.close_menu();
+                        }
+                        if i > 0 && ui.button("上移").clicked() {
+                            op = Some(EArrayOp::Move(i, i - 1));
+                            ui.close_menu();
+                        }
+                        if i + 1 < len && ui.button("下移").clicked() {
+                            op = Some(EArrayOp::Move(i, i + 1));
+                            ui.close_menu();
+                        }
+                        if ui.button("复制此项").clicked() {
+                            op = Some(EArrayOp::Duplicate(i));
+                            ui.close_menu();
+                        }
+                        if ui.button("删除").clicked() {
+                            op = Some(EArrayOp::Remove(i));
+                            ui.close_menu();
+                        }
+                    });
+                    if self.array_depth > 1 {
+                        let element = self.get_element_field();
+                        let mut v = utils::unwrap_nested_array(&arr[i]);
+                        if element.create_array_ui(&mut v, ui, idx * 100) {
+                            flag = true;
+                        }
+                        new.push(utils::wrap_nested_array(&v));
+                    } else {
+                        let (f, ret) = self.create_one_ui(&arr[i], ui, idx);
+                        if f {
+                            flag = true
+                        };
+                        new.push(ret);
+                    }
+                });
+
+                // 拖动中, 在目标位置画出提示线, 松开时移动
+                let rect = row.response.rect;
+                check_if!(drag_src.is_none() || pointer.is_none(), continue);
+                check_if!(!rect.contains(pointer.unwrap()), continue);
+                let stroke = egui::Stroke::new(2.0, Color32::LIGHT_BLUE);
+                ui.painter().hline(rect.x_range(), rect.top(), stroke);
+                if released {
+                    op = Some(EArrayOp::Move(drag_src.unwrap(), i));
+                }
+            }
+        });
+        if released {
+            ui.data_mut(|d| d.remove::<usize>(drag_id));
+        }
+
+        let mut default_val = self.default_val.clone();
+        if self.array_depth > 1 {
+            default_val = utils::wrap_nested_array("");
+        }
+        match op {
+            Some(EArrayOp::Insert(i)) => new.insert(i, default_val),
+            Some(EArrayOp::Remove(i)) => {
+                new.remove(i);
+            }
+            Some(EArrayOp::Move(from, to)) => {
+                let one = new.remove(from);
+                new.insert(to, one);
+            }
+            Some(EArrayOp::Duplicate(i)) => {
+                let one = new[i].clone();
+                new.insert(i + 1, one);
+            }
+            None => {}
+        }
+        if op.is_some() {
+            flag = true;
+        }
+        *val = utils::join_array(&new);
+        return flag;
+    }
+
+    // 表格视图中可以直接编辑的字段, 其他字段在弹出窗口中编辑
+    pub fn is_inline_editor(&self) -> bool {
+        check_if!(self.is_array, return false);
+        return match self.editor_type {
+            EEditorType::Const
+            | EEditorType::Check
+            | EEditorType::Enum
+            | EEditorType::UEFile
+            | EEditorType::Blueprint
+            | EEditorType::Reference => true,
+            EEditorType::Text => {
+                self.val_type != EFieldType::Expr && self.val_type != EFieldType::Table
+            }
+            _ => false,
+        };
+    }
+
+    // 表格视图的单元格, 只绘制编辑控件, 不显示字段名
+    pub fn create_cell_ui(&self, val: &mut String, ui: &mut egui::Ui, idx: i32) -> bool {
+        let (flag, ret) = self.create_one_ui(val, ui, idx);
+        *val = ret;
+        return flag;
+    }
+
     pub fn create_ui(
         &self,
         val: &mut String,
@@ -664,32 +1954,9 @@ impl FieldInfo {
         }
 
         if self.is_array {
-            let mut arr: Vec<&str> = Vec::new();
-            if !val.is_empty() {
-                arr = val.split(";").collect();
-            }
-            let mut new = Vec::new();
-            ui.vertical_centered(|ui| {
-                ui.horizontal(|ui| {
-                    if ui.button("+").clicked() {
-                        arr.push("0");
-                    }
-                    if ui.button("-").clicked() {
-                        arr.pop();
-                    }
-                });
-                let mut idx = start;
-                for one in arr {
-                    idx = idx + 1;
-                    let s = one.to_string();
-                    let (f, ret) = self.create_one_ui(&s, ui, idx);
-                    if f {
-                        flag = true
-                    };
-                    new.push(ret);
-                }
-            });
-            *val = new.join(";");
+            if self.create_array_ui(val, ui, start) {
+                flag = true;
+            }
         } else {
             let (f, ret) = self.create_one_ui(&val, ui, 1);
             if f {
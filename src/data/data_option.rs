@@ -0,0 +1,312 @@
+use itertools::Itertools;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    app::{TEMPLETE_MAP_EXPR, TEMPLETE_MAP_SUB_FIELD},
+    marco::{check_if, check_some},
+    utils,
+};
+
+use super::{
+    data_check,
+    data_field::{EnumOption, FieldInfo},
+    data_table::DataTable,
+};
+
+lazy_static! {
+    // 选项来源 -> 选项列表, 数据变化后由 refresh_table_option 刷新
+    pub static ref TABLE_OPTION_MAP: Mutex<HashMap<String, Arc<Vec<EnumOption>>>> =
+        Mutex::new(HashMap::new());
+    // editor_enum 中定义的共享枚举/位标记
+    pub static ref ENUM_DEF_MAP: Mutex<HashMap<String, Arc<EnumDef>>> =
+        Mutex::new(HashMap::new());
+}
+
+// 共享枚举定义, 位标记的选项值为对应的掩码, 如 "1:燃烧", "2:冰冻", "4:眩晕"
+#[derive(Debug, PartialEq)]
+pub struct EnumDef {
+    pub name: String,
+    pub opt: Arc<Vec<EnumOption>>,
+    // 导出时输出选项名而不是数值
+    pub export_name: bool,
+}
+
+// 共享枚举配置所在的表格, 在编辑器内修改后实时生效
+pub const ENUM_TABLE: &str = "editor_enum";
+
+// 枚举选项的来源
+// 静态选项:     "1:火", "2:冰"
+// 其他表格:     "@table:表格[:显示字段]", 后续的 "字段=值" 作为筛选条件
+// 共享枚举:     "@enum:枚举名"
+#[derive(Debug, PartialEq, Clone)]
+pub enum EOptionSource {
+    Static,
+    Table {
+        table: String,
+        show_field: String,
+        filter: Vec<(String, String)>,
+    },
+    Enum(String),
+}
+
+impl EOptionSource {
+    pub fn parse(opt_str: &Vec<String>) -> EOptionSource {
+        let first = match opt_str.get(0) {
+            Some(s) => s.trim(),
+            None => return EOptionSource::Static,
+        };
+        if let Some(name) = first.strip_prefix("@enum:") {
+            return EOptionSource::Enum(name.trim().to_string());
+        }
+        if let Some(spec) = first.strip_prefix("@table:") {
+            let arr: Vec<&str> = spec.split(":").collect();
+            let table = arr[0].trim().to_string();
+            let show_field = match arr.get(1) {
+                Some(s) => s.trim().to_string(),
+                None => String::new(),
+            };
+            let mut filter = Vec::new();
+            for one in &opt_str[1..] {
+                let kv: Vec<&str> = one.splitn(2, "=").collect();
+                if kv.len() == 2 {
+                    filter.push((kv[0].trim().to_string(), kv[1].trim().to_string()));
+                }
+            }
+            return EOptionSource::Table {
+                table,
+                show_field,
+                filter,
+            };
+        }
+        return EOptionSource::Static;
+    }
+
+    pub fn table(table: &String) -> EOptionSource {
+        EOptionSource::Table {
+            table: table.clone(),
+            show_field: String::new(),
+            filter: Vec::new(),
+        }
+    }
+
+    fn key(&self) -> String {
+        match self {
+            EOptionSource::Static => String::new(),
+            EOptionSource::Table {
+                table,
+                show_field,
+                filter,
+            } => {
+                let filter: Vec<String> =
+                    filter.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+                format!("{}:{}:{}", table, show_field, filter.join("&"))
+            }
+            EOptionSource::Enum(name) => name.clone(),
+        }
+    }
+}
+
+// 解析 "值:显示名[:导出名]" 格式的选项
+pub fn parse_static_option(opt_str: &Vec<String>) -> Vec<EnumOption> {
+    let mut opt = Vec::new();
+    for one in opt_str {
+        let one: Vec<&str> = one.split(":").collect();
+        if one.len() >= 2 {
+            let val = one[0].trim().to_string();
+            let show = one[1].trim().to_string();
+            let code = match one.get(2) {
+                Some(s) => s.trim().to_string(),
+                None => String::new(),
+            };
+            opt.push(EnumOption { show, val, code })
+        }
+    }
+    return opt;
+}
+
+// 位标记的选项: 第 i 个名称对应掩码 1 << i
+pub fn parse_bit_option(bit_name: &Vec<String>) -> Vec<EnumOption> {
+    let mut opt = Vec::new();
+    let mut bit: u32 = 1;
+    for name in bit_name {
+        opt.push(EnumOption {
+            show: name.clone(),
+            val: bit.to_string(),
+            code: String::new(),
+        });
+        bit = bit << 1;
+    }
+    return opt;
+}
+
+// 定义有变化时返回 true
+pub fn load_enum_def(list: Vec<(String, Vec<String>, bool)>) -> bool {
+    let mut new_map = HashMap::new();
+    for (name, opt, export_name) in list {
+        check_if!(name.is_empty(), continue);
+        let def = EnumDef {
+            name: name.clone(),
+            opt: Arc::new(parse_static_option(&opt)),
+            export_name,
+        };
+        new_map.insert(name, Arc::new(def));
+    }
+    let mut map = ENUM_DEF_MAP.lock().unwrap();
+    check_if!(*map == new_map, return false);
+    *map = new_map;
+    return true;
+}
+
+pub fn get_enum_def(name: &String) -> Option<Arc<EnumDef>> {
+    let map = ENUM_DEF_MAP.lock().unwrap();
+    return map.get(name).cloned();
+}
+
+// 生成共享枚举的 lua 定义, 选项名优先使用导出名
+pub fn gen_enum_lua() -> String {
+    let map = ENUM_DEF_MAP.lock().unwrap();
+    let mut ret = Vec::new();
+    for (_, def) in map.iter().sorted_by_key(|a| a.0) {
+        let mut lines = Vec::new();
+        for one in def.opt.iter() {
+            let name = if one.code.is_empty() {
+                &one.show
+            } else {
+                &one.code
+            };
+            let val = match one.val.parse::<f64>() {
+                Ok(_) => one.val.clone(),
+                Err(_) => format!("{:?}", one.val),
+            };
+            lines.push(format!("    [{:?}] = {},", name, val));
+        }
+        ret.push(format!("{} = {{\n{}\n}}", def.name, lines.join("\n")));
+    }
+    return ret.join("\n\n");
+}
+
+fn collect_source(field: &Vec<FieldInfo>, list: &mut Vec<EOptionSource>) {
+    for one in field {
+        if let EOptionSource::Table { .. } = one.opt_source {
+            list.push(one.opt_source.clone());
+        }
+    }
+}
+
+fn calc_source(
+    data_table: &HashMap<String, DataTable>,
+    source: &EOptionSource,
+) -> Option<Vec<EnumOption>> {
+    match source {
+        EOptionSource::Table {
+            table,
+            show_field,
+            filter,
+        } => {
+            let table = data_table.get(table)?;
+            Some(table.get_option_list(show_field, filter))
+        }
+        _ => None,
+    }
+}
+
+// 枚举/关联检查依赖选项, 只在选项变化后让检查结果失效
+// 修改数据时每次都会调用, 选项不变时保留缓存
+pub fn refresh_table_option(data_table: &HashMap<String, DataTable>) {
+    let mut sources = Vec::new();
+    for (name, table) in data_table {
+        sources.push(EOptionSource::table(name));
+        collect_source(&table.info, &mut sources);
+    }
+    for (_, info) in TEMPLETE_MAP_EXPR.lock().unwrap().iter() {
+        collect_source(&info.field, &mut sources);
+    }
+    for (_, info) in TEMPLETE_MAP_SUB_FIELD.lock().unwrap().iter() {
+        collect_source(&info.field, &mut sources);
+    }
+
+    let mut new_map = HashMap::new();
+    for one in sources {
+        let key = one.key();
+        check_if!(new_map.contains_key(&key), continue);
+        let list = check_some!(calc_source(data_table, &one), continue);
+        new_map.insert(key, Arc::new(list));
+    }
+    let mut map = TABLE_OPTION_MAP.lock().unwrap();
+    if *map != new_map {
+        *map = new_map;
+        data_check::clear_cache();
+    }
+    drop(map);
+
+    // 共享枚举表在编辑器内被修改时同步刷新
+    let enum_table = check_some!(data_table.get(ENUM_TABLE), return);
+    let mut list = Vec::new();
+    for row in &enum_table.data {
+        let name = utils::map_get_string(row, "name", "");
+        let opt = utils::map_get_string(row, "opt", "");
+        let opt = utils::split_array(&opt);
+        let export_name = utils::map_get_string(row, "export_name", "").to_lowercase() == "true";
+        list.push((name, opt, export_name));
+    }
+    if load_enum_def(list) {
+        data_check::clear_cache();
+    }
+}
+
+// 只刷新来源于指定表格的选项
+// 选项有变化时返回 true
+fn update_option(
+    map: &mut HashMap<String, Arc<Vec<EnumOption>>>,
+    key: String,
+    list: Vec<EnumOption>,
+) -> bool {
+    check_if!(map.get(&key).map_or(false, |a| **a == list), return false);
+    map.insert(key, Arc::new(list));
+    return true;
+}
+
+pub fn refresh_one_table_option(data_table: &HashMap<String, DataTable>, table: &String) {
+    let mut map = TABLE_OPTION_MAP.lock().unwrap();
+    let source = EOptionSource::table(table);
+    let list = check_some!(calc_source(data_table, &source), return);
+    let mut changed = update_option(&mut map, source.key(), list);
+    for (_, t) in data_table {
+        for one in &t.info {
+            match &one.opt_source {
+                EOptionSource::Table { table: t, .. } if t == table => {
+                    let list = check_some!(calc_source(data_table, &one.opt_source), continue);
+                    if update_option(&mut map, one.opt_source.key(), list) {
+                        changed = true;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    // 每帧都会调用, 只在选项变化时让检查结果失效
+    if changed {
+        data_check::clear_cache();
+    }
+}
+
+pub fn get_source_option(source: &EOptionSource) -> Option<Arc<Vec<EnumOption>>> {
+    match source {
+        EOptionSource::Static => None,
+        EOptionSource::Table { .. } => {
+            let map = TABLE_OPTION_MAP.lock().unwrap();
+            map.get(&source.key()).cloned()
+        }
+        EOptionSource::Enum(name) => {
+            let def = get_enum_def(name)?;
+            Some(def.opt.clone())
+        }
+    }
+}
+
+pub fn get_table_option(table: &String) -> Option<Arc<Vec<EnumOption>>> {
+    return get_source_option(&EOptionSource::table(table));
+}
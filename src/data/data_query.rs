@@ -0,0 +1,517 @@
+use anyhow::{bail, Result};
+use regex::{Regex, RegexBuilder};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    data::{data_field::FieldInfo, row_meta},
+    marco::{check_if, check_some},
+};
+
+// 列表/表格视图的筛选语法:
+//   火球            任意字段包含(忽略大小写)
+//   name:火球       字段包含, 字段可以写字段名或标题
+//   cooldown>5      数字比较, 支持 = != > >= < <=
+//   type=Enum("Fire") 按枚举的显示名/导出名匹配
+//   name~^fire_\d+  正则, 也可以写 name:/^fire/ 或 /^fire/
+//   has:name        字段不为空, has:error 有检查错误, has:broken 关联表缺失, has:skill-broken 指定关联表
+//   has:fav/tag/color  已收藏/有标签/有颜色标记, tag:WIP 有指定标签, color:red 或 color:红 颜色标记
+//   a AND b, a OR b, NOT a, -a, (a OR b) c     空格分隔默认为 AND
+lazy_static! {
+    static ref QUERY_CACHE: Mutex<HashMap<String, Arc<Query>>> = Mutex::new(HashMap::new());
+}
+
+const CACHE_LIMIT: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ECmpOp {
+    Contains,
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+#[derive(Debug)]
+enum ECond {
+    Text(String),
+    Regex(Option<String>, Regex),
+    Has(String),
+    Tag(String),
+    Color(String),
+    Cmp(String, ECmpOp, String),
+    Not(Box<ECond>),
+    And(Vec<ECond>),
+    Or(Vec<ECond>),
+}
+
+#[derive(Debug, PartialEq)]
+enum EToken {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    Term(String),
+}
+
+#[derive(Debug)]
+pub struct Query {
+    cond: Option<ECond>,
+}
+
+// 按空白和括号切分, 引号和 Enum("..") 里的内容不切分
+fn tokenize(text: &str) -> Vec<EToken> {
+    let mut ret = Vec::new();
+    let chars: Vec<char> = text.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c == '(' || c == ')' {
+            ret.push(if c == '(' {
+                EToken::LParen
+            } else {
+                EToken::RParen
+            });
+            i += 1;
+            continue;
+        }
+        let mut term = String::new();
+        let mut quote = false;
+        let mut depth = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            if quote {
+                term.push(c);
+                quote = c != '"';
+                i += 1;
+                continue;
+            }
+            check_if!(c.is_whitespace() || (c == ')' && depth == 0), break);
+            match c {
+                '"' => quote = true,
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            term.push(c);
+            i += 1;
+        }
+        let token = match term.as_str() {
+            "AND" | "and" | "&&" => EToken::And,
+            "OR" | "or" | "||" | "|" => EToken::Or,
+            "NOT" | "not" | "!" => EToken::Not,
+            _ => EToken::Term(term),
+        };
+        ret.push(token);
+    }
+    return ret;
+}
+
+fn unquote(val: &str) -> String {
+    let val = val.trim();
+    if val.len() >= 2 && val.starts_with('"') && val.ends_with('"') {
+        return val[1..val.len() - 1].to_string();
+    }
+    return val.to_string();
+}
+
+fn build_regex(pattern: &str) -> Result<Regex> {
+    match RegexBuilder::new(pattern).case_insensitive(true).build() {
+        Ok(re) => Ok(re),
+        Err(e) => bail!("正则表达式[{}]错误: {}", pattern, e),
+    }
+}
+
+// /xxx/ 形式的正则
+fn slash_regex(val: &str) -> Option<&str> {
+    check_if!(
+        val.len() < 2 || !val.starts_with('/') || !val.ends_with('/'),
+        return None
+    );
+    return Some(&val[1..val.len() - 1]);
+}
+
+fn parse_term(term: &str) -> Result<ECond> {
+    if let Some(pattern) = slash_regex(term) {
+        return Ok(ECond::Regex(None, build_regex(pattern)?));
+    }
+    if let Some(rest) = term.strip_prefix("has:") {
+        return Ok(ECond::Has(unquote(rest)));
+    }
+    if let Some(rest) = term.strip_prefix("tag:") {
+        return Ok(ECond::Tag(unquote(rest).to_lowercase()));
+    }
+    if let Some(rest) = term.strip_prefix("color:") {
+        let name = unquote(rest);
+        let color = check_some!(row_meta::find_color(&name), bail!("颜色[{}]不存在", name));
+        return Ok(ECond::Color(color.0.to_string()));
+    }
+    // 字段名部分不含引号, 找第一个运算符
+    let ops = [
+        ("!=", ECmpOp::Ne),
+        (">=", ECmpOp::Ge),
+        ("<=", ECmpOp::Le),
+        (":", ECmpOp::Contains),
+        ("=", ECmpOp::Eq),
+        (">", ECmpOp::Gt),
+        ("<", ECmpOp::Lt),
+    ];
+    let end = term.find('"').unwrap_or(term.len());
+    let mut found: Option<(usize, &str, ECmpOp)> = None;
+    for (s, op) in ops {
+        let pos = check_some!(term[..end].find(s), continue);
+        if found.map_or(true, |(p, _, _)| pos < p) {
+            found = Some((pos, s, op));
+        }
+    }
+    let regex_pos = term[..end].find('~');
+    if let Some(pos) = regex_pos {
+        if pos > 0 && found.map_or(true, |(p, _, _)| pos < p) {
+            let field = term[..pos].to_string();
+            return Ok(ECond::Regex(
+                Some(field),
+                build_regex(&unquote(&term[pos + 1..]))?,
+            ));
+        }
+    }
+    let (pos, s, op) = check_some!(found, return Ok(ECond::Text(unquote(term).to_lowercase())));
+    check_if!(
+        pos == 0,
+        return Ok(ECond::Text(unquote(term).to_lowercase()))
+    );
+    let field = term[..pos].to_string();
+    let val = &term[pos + s.len()..];
+    if op == ECmpOp::Contains {
+        if let Some(pattern) = slash_regex(val) {
+            return Ok(ECond::Regex(Some(field), build_regex(pattern)?));
+        }
+    }
+    return Ok(ECond::Cmp(field, op, unquote(val)));
+}
+
+struct Parser {
+    tokens: Vec<EToken>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&EToken> {
+        return self.tokens.get(self.pos);
+    }
+
+    fn parse_or(&mut self) -> Result<ECond> {
+        let mut list = vec![self.parse_and()?];
+        while self.peek() == Some(&EToken::Or) {
+            self.pos += 1;
+            list.push(self.parse_and()?);
+        }
+        check_if!(list.len() == 1, return Ok(list.pop().unwrap()));
+        return Ok(ECond::Or(list));
+    }
+
+    fn parse_and(&mut self) -> Result<ECond> {
+        let mut list = vec![self.parse_not()?];
+        loop {
+            match self.peek() {
+                Some(EToken::And) => self.pos += 1,
+                Some(EToken::Or) | Some(EToken::RParen) | None => break,
+                _ => {}
+            }
+            list.push(self.parse_not()?);
+        }
+        check_if!(list.len() == 1, return Ok(list.pop().unwrap()));
+        return Ok(ECond::And(list));
+    }
+
+    fn parse_not(&mut self) -> Result<ECond> {
+        let token = check_some!(self.tokens.get(self.pos), bail!("筛选条件不完整"));
+        self.pos += 1;
+        match token {
+            EToken::Not => Ok(ECond::Not(Box::new(self.parse_not()?))),
+            EToken::LParen => {
+                let ret = self.parse_or()?;
+                check_if!(self.peek() != Some(&EToken::RParen), bail!("缺少右括号"));
+                self.pos += 1;
+                Ok(ret)
+            }
+            EToken::Term(term) => {
+                if term.len() > 1 && term.starts_with('-') {
+                    return Ok(ECond::Not(Box::new(parse_term(&term[1..])?)));
+                }
+                parse_term(term)
+            }
+            EToken::RParen => bail!("多余的右括号"),
+            EToken::And | EToken::Or => bail!("AND/OR 前面缺少条件"),
+        }
+    }
+}
+
+fn find_field<'a>(info: &'a Vec<FieldInfo>, name: &str) -> Option<&'a FieldInfo> {
+    let lower = name.to_lowercase();
+    return info
+        .iter()
+        .find(|a| a.name.to_lowercase() == lower)
+        .or_else(|| info.iter().find(|a| a.title == name));
+}
+
+// Enum("Fire") 转换为枚举的值, 可以写显示名或导出名
+fn resolve_val(field: Option<&FieldInfo>, val: &str) -> String {
+    let inner = val
+        .strip_prefix("Enum(")
+        .and_then(|a| a.strip_suffix(')'))
+        .map(unquote);
+    let inner = check_some!(inner, return val.to_string());
+    let field = check_some!(field, return inner);
+    let opts = check_some!(field.get_opt(), return inner);
+    let found = opts
+        .iter()
+        .find(|a| a.show == inner || a.code == inner || a.val == inner);
+    return found.map_or(inner, |a| a.val.clone());
+}
+
+fn compare(op: ECmpOp, val: &str, target: &str) -> bool {
+    if let (Ok(a), Ok(b)) = (val.trim().parse::<f64>(), target.trim().parse::<f64>()) {
+        return match op {
+            ECmpOp::Contains => val.contains(target),
+            ECmpOp::Eq => a == b,
+            ECmpOp::Ne => a != b,
+            ECmpOp::Gt => a > b,
+            ECmpOp::Ge => a >= b,
+            ECmpOp::Lt => a < b,
+            ECmpOp::Le => a <= b,
+        };
+    }
+    let val = val.to_lowercase();
+    let target = target.to_lowercase();
+    return match op {
+        ECmpOp::Contains => val.contains(&target),
+        ECmpOp::Eq => val == target,
+        ECmpOp::Ne => val != target,
+        ECmpOp::Gt => val > target,
+        ECmpOp::Ge => val >= target,
+        ECmpOp::Lt => val < target,
+        ECmpOp::Le => val <= target,
+    };
+}
+
+fn has_error(field: &FieldInfo, row: &HashMap<String, String>) -> bool {
+    let val = row.get(&field.name).cloned().unwrap_or_default();
+    return field.check_data(&val).0;
+}
+
+impl ECond {
+    fn matches(&self, info: &Vec<FieldInfo>, row: &HashMap<String, String>) -> bool {
+        let get = |name: &str| -> (Option<&FieldInfo>, String) {
+            let field = find_field(info, name);
+            let key = field.map_or(name, |a| a.name.as_str());
+            (field, row.get(key).cloned().unwrap_or_default())
+        };
+        match self {
+            ECond::Text(text) => row.values().any(|v| v.to_lowercase().contains(text)),
+            ECond::Regex(None, re) => row.values().any(|v| re.is_match(v)),
+            ECond::Regex(Some(name), re) => re.is_match(&get(name).1),
+            ECond::Cmp(name, op, val) => {
+                let (field, v) = get(name);
+                compare(*op, &v, &resolve_val(field, val))
+            }
+            ECond::Tag(tag) => row_meta::get_tags(row)
+                .iter()
+                .any(|a| a.to_lowercase() == *tag),
+            ECond::Color(color) => row.get(row_meta::FIELD_COLOR) == Some(color),
+            ECond::Has(what) => match what.as_str() {
+                "error" => info.iter().any(|f| has_error(f, row)),
+                "fav" => row_meta::is_favourite(row),
+                "tag" => !row_meta::get_tags(row).is_empty(),
+                "color" => row_meta::get_color(row).is_some(),
+                "broken" => info
+                    .iter()
+                    .any(|f| !f.link_table.is_empty() && has_error(f, row)),
+                _ => match what.strip_suffix("-broken") {
+                    Some(table) => info
+                        .iter()
+                        .any(|f| f.link_table == table && has_error(f, row)),
+                    None => !get(what).1.trim().is_empty(),
+                },
+            },
+            ECond::Not(one) => !one.matches(info, row),
+            ECond::And(list) => list.iter().all(|a| a.matches(info, row)),
+            ECond::Or(list) => list.iter().any(|a| a.matches(info, row)),
+        }
+    }
+}
+
+impl Query {
+    pub fn parse(text: &str) -> Result<Query> {
+        let tokens = tokenize(text);
+        check_if!(tokens.is_empty(), return Ok(Query { cond: None }));
+        let mut parser = Parser { tokens, pos: 0 };
+        let cond = parser.parse_or()?;
+        check_if!(parser.pos < parser.tokens.len(), bail!("多余的右括号"));
+        return Ok(Query { cond: Some(cond) });
+    }
+
+    pub fn matches(&self, info: &Vec<FieldInfo>, row: &HashMap<String, String>) -> bool {
+        let cond = check_some!(&self.cond, return true);
+        return cond.matches(info, row);
+    }
+}
+
+// 每帧都会用到, 按文本缓存解析结果
+// 解析失败时不筛选, 错误由输入框通过 check_query 显示
+pub fn get_query(text: &str) -> Arc<Query> {
+    let mut cache = QUERY_CACHE.lock().unwrap();
+    if let Some(one) = cache.get(text) {
+        return one.clone();
+    }
+    if cache.len() > CACHE_LIMIT {
+        cache.clear();
+    }
+    let query = Query::parse(text).unwrap_or(Query { cond: None });
+    let query = Arc::new(query);
+    cache.insert(text.to_string(), query.clone());
+    return query;
+}
+
+// 表格视图的列筛选, 以运算符开头时按比较, 否则按包含
+// 如 >5, =Enum("Fire"), ~^fire, 火球; 格式错误时不筛选
+pub fn field_query(field: &str, filter: &str) -> Query {
+    let filter = filter.trim();
+    let is_op = ["!=", ">", "<", "=", ":", "~"]
+        .iter()
+        .any(|a| filter.starts_with(a));
+    let term = if is_op {
+        format!("{}{}", field, filter)
+    } else {
+        format!("{}:{}", field, filter)
+    };
+    return Query {
+        cond: parse_term(&term).ok(),
+    };
+}
+
+// 返回筛选语法的错误信息, 用于输入框提示
+pub fn check_query(text: &str) -> Option<String> {
+    return Query::parse(text).err().map(|e| e.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info() -> Vec<FieldInfo> {
+        let field = |name: &str, title: &str, t: &str, editor: &str, opt: &[&str]| {
+            FieldInfo::parse(
+                name.to_string(),
+                title.to_string(),
+                String::new(),
+                String::new(),
+                t.to_string(),
+                editor.to_string(),
+                opt.iter().map(|a| a.to_string()).collect(),
+                String::new(),
+                String::new(),
+                false,
+                Vec::new(),
+            )
+            .unwrap()
+        };
+        return vec![
+            field("id", "编号", "K<N>", "Text", &[]),
+            field("name", "名称", "S", "Text", &[]),
+            field("cd", "冷却", "N", "Text", &[]),
+            field("kind", "类型", "N", "Enum", &["1:火:Fire", "2:冰:Ice"]),
+        ];
+    }
+
+    fn row(id: &str, name: &str, cd: &str, kind: &str) -> HashMap<String, String> {
+        let mut ret = HashMap::new();
+        ret.insert("id".to_string(), id.to_string());
+        ret.insert("name".to_string(), name.to_string());
+        ret.insert("cd".to_string(), cd.to_string());
+        ret.insert("kind".to_string(), kind.to_string());
+        return ret;
+    }
+
+    // 返回匹配的行的 id
+    fn filter(text: &str) -> Vec<String> {
+        return filter_by(&Query::parse(text).unwrap());
+    }
+
+    fn filter_by(query: &Query) -> Vec<String> {
+        let info = info();
+        let rows = vec![
+            row("1", "fire_ball", "5", "1"),
+            row("2", "ice_ball", "10", "2"),
+            row("3", "fire_wall", "20", "1"),
+            row("4", "heal", "0", "2"),
+        ];
+        return rows
+            .iter()
+            .filter(|a| query.matches(&info, a))
+            .map(|a| a["id"].clone())
+            .collect();
+    }
+
+    #[test]
+    fn precedence() {
+        // AND 优先于 OR
+        assert_eq!(filter("heal OR fire cd>10"), vec!["3", "4"]);
+        assert_eq!(filter("heal OR fire AND cd>10"), vec!["3", "4"]);
+        assert_eq!(filter("(heal OR fire) cd<10"), vec!["1", "4"]);
+        assert_eq!(filter("NOT fire OR cd=5"), vec!["1", "2", "4"]);
+    }
+
+    #[test]
+    fn negate() {
+        assert_eq!(filter("-fire"), vec!["2", "4"]);
+        assert_eq!(filter("ball -name:ice"), vec!["1"]);
+        assert_eq!(filter("-cd>5"), vec!["1", "4"]);
+    }
+
+    #[test]
+    fn enum_value() {
+        assert_eq!(filter("kind=Enum(\"火\")"), vec!["1", "3"]);
+        assert_eq!(filter("类型=Enum(\"Ice\")"), vec!["2", "4"]);
+        assert_eq!(filter("kind!=Enum(\"Fire\") ball"), vec!["2"]);
+    }
+
+    #[test]
+    fn regex() {
+        assert_eq!(filter("name~^fire_"), vec!["1", "3"]);
+        assert_eq!(filter("name:/_ball$/"), vec!["1", "2"]);
+        assert_eq!(filter("/^HEAL$/"), vec!["4"]);
+    }
+
+    #[test]
+    fn field_filter() {
+        assert_eq!(filter_by(&field_query("cd", ">5")), vec!["2", "3"]);
+        assert_eq!(filter_by(&field_query("cd", "<= 5")), vec!["1", "4"]);
+        assert_eq!(filter_by(&field_query("name", "FIRE")), vec!["1", "3"]);
+        assert_eq!(filter_by(&field_query("name", "~_ball$")), vec!["1", "2"]);
+        assert_eq!(
+            filter_by(&field_query("kind", "=Enum(\"冰\")")),
+            vec!["2", "4"]
+        );
+        // 格式错误时不筛选
+        assert_eq!(filter_by(&field_query("name", "~(")).len(), 4);
+    }
+
+    #[test]
+    fn parse_error() {
+        assert!(Query::parse("(fire").is_err());
+        assert!(Query::parse("fire)").is_err());
+        assert!(Query::parse("name~(").is_err());
+        assert!(Query::parse("AND fire").is_err());
+        // 解析失败时不筛选
+        assert!(get_query("(fire").cond.is_none());
+    }
+}
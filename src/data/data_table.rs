@@ -1,6 +1,13 @@
 use anyhow::{bail, Result};
 use itertools::Itertools;
-use std::{collections::HashMap, fs, path::PathBuf};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    fs,
+    path::PathBuf,
+    sync::Mutex,
+};
 use walkdir::WalkDir;
 use xlsxwriter::{FormatAlignment, FormatBorder, FormatColor};
 
@@ -12,7 +19,241 @@ use crate::{
     utils,
 };
 
-use super::data_field::FieldInfo;
+use super::{
+    data_field::{EFieldType, EnumOption, FieldInfo},
+    data_query,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum ESortType {
+    // 数字字段按数值, 其他按自然排序
+    #[default]
+    Auto,
+    Number,
+    Str,
+    Natural,
+}
+
+// 列表的分组和排序方式, 按表格保存在 AppCfg 中
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ListViewCfg {
+    // 分组字段, 为空时按 __Group__/__SubGroup__ 分组
+    pub group: String,
+    // 不分组, 所有行平铺显示
+    pub flatten: bool,
+    // 排序字段, 为空时按主键
+    pub sort: String,
+    pub sort_type: ESortType,
+    pub desc: bool,
+}
+
+// 组合主键的各字段值用 | 连接, 作为行的唯一标识和关联表的值
+pub const KEY_SEP: &str = "|";
+
+lazy_static! {
+    // 本地用户配置中为当前用户保留的主键段 [start, end], 由 AppCfg 设置
+    pub static ref USER_KEY_RANGE: Mutex<Option<(i32, i32)>> = Mutex::new(None);
+}
+
+pub fn set_user_key_range(range: Option<(i32, i32)>) {
+    *USER_KEY_RANGE.lock().unwrap() = range;
+}
+
+// 新增行时主键的分配方式, 在 editor_table 的 key_alloc 列配置, 只对主键中最后一个字段生效
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum EKeyAlloc {
+    // 已有的最大值 + 1
+    #[default]
+    Max,
+    // user: 使用本地用户配置的主键段
+    User,
+    // group:火系=1000-1999;冰系=2000-2999  按 __Group__ 分配主键段
+    Group(Vec<(String, i32, i32)>),
+    // group*1000+n  字段值乘以间隔作为起始值, group/master 指分组字段/主表字段
+    Pattern(String, i32),
+}
+
+impl EKeyAlloc {
+    pub fn parse(s: &str) -> Result<EKeyAlloc> {
+        let s = s.trim();
+        check_if!(s.is_empty(), return Ok(EKeyAlloc::Max));
+        check_if!(s == "user", return Ok(EKeyAlloc::User));
+        if let Some(list) = s.strip_prefix("group:") {
+            let mut ret = Vec::new();
+            for one in list.split(';') {
+                let one = one.trim();
+                check_if!(one.is_empty(), continue);
+                let (name, range) = check_some!(
+                    one.rsplit_once('='),
+                    bail!("主键分配[{}]缺少主键段, 如 火系=1000-1999", one)
+                );
+                let (start, end) = parse_key_range(range)?;
+                ret.push((name.trim().to_string(), start, end));
+            }
+            return Ok(EKeyAlloc::Group(ret));
+        }
+        if let Some(expr) = s.strip_suffix("+n") {
+            let (field, step) = check_some!(
+                expr.split_once('*'),
+                bail!("主键分配[{}]格式错误, 如 group*1000+n", s)
+            );
+            let step = match step.trim().parse::<i32>() {
+                Ok(step) if step > 1 => step,
+                _ => bail!("主键分配[{}]的间隔必须是大于1的整数", s),
+            };
+            return Ok(EKeyAlloc::Pattern(field.trim().to_string(), step));
+        }
+        bail!(
+            "主键分配方式[{}]不支持, 可以填 user, group:分组=起始-结束, 字段*间隔+n",
+            s
+        );
+    }
+}
+
+// 解析 "1000-1999" 形式的主键段
+pub fn parse_key_range(s: &str) -> Result<(i32, i32)> {
+    let (start, end) = check_some!(s.trim().split_once('-'), bail!("主键段[{}]格式错误", s));
+    let start = start.trim().parse::<i32>();
+    let end = end.trim().parse::<i32>();
+    match (start, end) {
+        (Ok(start), Ok(end)) if start <= end => return Ok((start, end)),
+        _ => bail!("主键段[{}]格式错误", s),
+    }
+}
+
+// 按主键比较, 组合主键按字段顺序依次比较
+pub fn compare_key(
+    info: &Vec<FieldInfo>,
+    a: &HashMap<String, String>,
+    b: &HashMap<String, String>,
+) -> Ordering {
+    let empty = String::new();
+    for field in info.iter().filter(|f| f.is_key) {
+        let va = a.get(&field.name).unwrap_or(&empty);
+        let vb = b.get(&field.name).unwrap_or(&empty);
+        let ord = compare_by_type(Some(field), ESortType::Auto, va, vb);
+        check_if!(ord != Ordering::Equal, return ord);
+    }
+    return Ordering::Equal;
+}
+
+// 按字段类型比较, 不是数字的排在数字后面
+pub fn compare_by_type(
+    field: Option<&FieldInfo>,
+    sort_type: ESortType,
+    a: &str,
+    b: &str,
+) -> Ordering {
+    let is_number = field.map_or(false, |f| f.val_type == EFieldType::Number && !f.is_array);
+    match sort_type {
+        ESortType::Number => {}
+        ESortType::Auto if is_number => {}
+        ESortType::Str => return a.cmp(b),
+        _ => return utils::natural_cmp(a, b),
+    }
+    match (a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        (Ok(_), Err(_)) => Ordering::Less,
+        (Err(_), Ok(_)) => Ordering::Greater,
+        _ => utils::natural_cmp(a, b),
+    }
+}
+
+// 表格视图的状态, order 是筛选排序后的行号, order_key 变化时重新计算
+#[derive(Debug, Default)]
+pub struct GridState {
+    pub show: bool,
+    pub sort: String,
+    pub sort_desc: bool,
+    pub filter: HashMap<String, String>,
+    // 筛选语法, 见 data_query
+    pub query: String,
+    pub edit: Option<(usize, String)>,
+    pub order: Vec<usize>,
+    pub order_key: String,
+    // 复制时只复制这些列, 为空时复制所有字段
+    pub copy_cols: Vec<String>,
+}
+
+// 多选的行, 没有多选时为空, 视为只选中 cur_row; 增删行后清空
+#[derive(Debug, Default)]
+pub struct RowSelect {
+    pub rows: HashSet<usize>,
+    pub anchor: usize,
+    pub len: usize,
+}
+
+impl RowSelect {
+    // ctrl 切换选中, shift 按显示顺序选中 anchor 到 row 之间的行
+    pub fn click(
+        &mut self,
+        row: usize,
+        cur_row: usize,
+        order: &Vec<usize>,
+        ctrl: bool,
+        shift: bool,
+    ) {
+        if !ctrl && !shift {
+            // 普通点击只选中当前行
+            self.rows.clear();
+            self.anchor = row;
+            return;
+        }
+        if self.rows.is_empty() {
+            self.rows.insert(cur_row);
+            self.anchor = cur_row;
+        }
+        if shift {
+            let a = order.iter().position(|a| *a == self.anchor);
+            let b = order.iter().position(|a| *a == row);
+            if let (Some(a), Some(b)) = (a, b) {
+                if !ctrl {
+                    self.rows.clear();
+                }
+                self.rows.extend(order[a.min(b)..=a.max(b)].iter());
+                return;
+            }
+        }
+        if !self.rows.remove(&row) {
+            self.rows.insert(row);
+        }
+        self.anchor = row;
+    }
+
+    pub fn select_all(&mut self, rows: &Vec<usize>) {
+        self.rows = rows.iter().cloned().collect();
+    }
+
+    pub fn clear(&mut self) {
+        self.rows.clear();
+    }
+
+    pub fn is_selected(&self, row: usize, cur_row: i32) -> bool {
+        if self.rows.is_empty() {
+            return row as i32 == cur_row;
+        }
+        return self.rows.contains(&row);
+    }
+
+    pub fn get_rows(&self, cur_row: i32) -> Vec<usize> {
+        if self.rows.is_empty() {
+            check_if!(
+                cur_row < 0 || cur_row as usize >= self.len,
+                return Vec::new()
+            );
+            return vec![cur_row as usize];
+        }
+        return self.rows.iter().cloned().sorted().collect();
+    }
+
+    pub fn validate(&mut self, len: usize) {
+        if self.len != len {
+            self.rows.clear();
+            self.len = len;
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct DataTable {
@@ -22,17 +263,24 @@ pub struct DataTable {
     pub master_field: String,
     pub group_key: String,
     pub export_sort: String,
+    // 手动调整列表顺序时使用的数字字段, 为空时没有手动顺序
+    pub order_field: String,
     pub output_type: Vec<String>,
     pub output_path: Vec<String>,
 
     pub info: Vec<FieldInfo>,
     pub data: Vec<HashMap<String, String>>,
     pub key_name: String,
+    // 所有主键字段(K<>), 按字段顺序, 多于一个时为组合主键; key_name 为第一个
+    pub key_fields: Vec<String>,
+    pub key_alloc: EKeyAlloc,
     pub templete: Vec<TempleteInfo>,
     pub post_save_exec: String,
     pub reload_editor: bool,
     pub data_hash: String,
     pub data_str: String,
+    // 数据的修改次数, 执行/撤销/重做操作时增加, 表格视图据此重新筛选排序
+    pub revision: usize,
 
     // UI 相关
     pub cur: i32,
@@ -41,6 +289,8 @@ pub struct DataTable {
     pub detail_search: String,
     pub show_all: bool,
     pub templete_idx: i32,
+    pub grid: GridState,
+    pub select: RowSelect,
 
     pub error: String,
 }
@@ -68,6 +318,7 @@ impl DataTable {
             master_field,
             group_key: group_field,
             export_sort,
+            order_field: String::new(),
             output_type,
             output_path,
             post_save_exec,
@@ -78,7 +329,10 @@ impl DataTable {
             info,
             data: Vec::new(),
             key_name,
+            key_fields: Vec::new(),
+            key_alloc: EKeyAlloc::Max,
             templete,
+            revision: 0,
 
             cur: 0,
             cur_row: 0,
@@ -86,6 +340,8 @@ impl DataTable {
             search: String::new(),
             detail_search: String::new(),
             show_all: false,
+            grid: GridState::default(),
+            select: RowSelect::default(),
             error: String::new(),
         };
 
@@ -109,14 +365,20 @@ impl DataTable {
     }
 
     fn _load_data(&mut self) -> Result<()> {
-        for one in &self.info {
-            if !one.is_key {
-                continue;
-            }
-            self.key_name = one.name.clone();
-            if self.export_sort.is_empty() {
-                self.export_sort = self.key_name.clone();
-            }
+        self.key_fields = self
+            .info
+            .iter()
+            .filter(|a| a.is_key)
+            .map(|a| a.name.clone())
+            .collect();
+        self.key_name = self.key_fields.first().cloned().unwrap_or_default();
+        // 没有配置导出排序时, 有顺序字段的按顺序导出, 否则按主键
+        if self.export_sort.is_empty() {
+            self.export_sort = if self.order_field.is_empty() {
+                self.key_name.clone()
+            } else {
+                self.order_field.clone()
+            };
         }
         if self.key_name.is_empty() {
             bail!(error::AppError::TableKeyNotFound(self.table_name.clone()));
@@ -161,7 +423,7 @@ impl DataTable {
             "csv" => saver::csv::CsvSaver::output(
                 &self.info,
                 &self.data,
-                &self.key_name,
+                &self.export_sort,
                 &self.table_name,
                 full_path,
                 false,
@@ -169,7 +431,7 @@ impl DataTable {
             "scsv" => saver::scsv::ScsvSaver::output(
                 &self.info,
                 &self.data,
-                &self.key_name,
+                &self.export_sort,
                 &self.table_name,
                 full_path,
                 false,
@@ -177,7 +439,7 @@ impl DataTable {
             "json" => saver::json::JsonSaver::output(
                 &self.info,
                 &self.data,
-                &self.key_name,
+                &self.export_sort,
                 &self.table_name,
                 full_path,
                 false,
@@ -185,7 +447,7 @@ impl DataTable {
             "excel" => saver::excel::ExcelSaver::output(
                 &self.info,
                 &self.data,
-                &self.key_name,
+                &self.export_sort,
                 &self.table_name,
                 full_path,
                 false,
@@ -263,7 +525,13 @@ impl DataTable {
             fs::remove_file(p)?;
         }
 
-        let list = self.get_show_name_list(&String::new(), &String::new(), true, &String::new());
+        let list = self.get_show_name_list(
+            &String::new(),
+            &String::new(),
+            true,
+            &String::new(),
+            &ListViewCfg::default(),
+        );
         for (group, one) in list.iter().sorted_by_key(|a| a.0) {
             for (sub_group, two) in one.iter().sorted_by_key(|a| a.0) {
                 let mut arr = Vec::new();
@@ -286,7 +554,7 @@ impl DataTable {
                 saver::excel::ExcelSaver::output(
                     &self.info,
                     &arr,
-                    &self.key_name,
+                    &self.export_sort,
                     &self.table_name,
                     p,
                     true,
@@ -323,10 +591,11 @@ impl DataTable {
                 println!("load excel sheet: {:?}", data);
             }
             for mut one in data {
-                let key_val = utils::map_get_string(&one, &self.key_name, "");
-                if key_val.is_empty() {
-                    continue;
-                }
+                let empty = self
+                    .key_fields
+                    .iter()
+                    .all(|k| utils::map_get_string(&one, k, "").is_empty());
+                check_if!(empty, continue);
 
                 for field in &self.info {
                     check_if!(one.contains_key(&field.name), continue);
@@ -348,15 +617,85 @@ impl DataTable {
             return ret;
         }
         let row = row.unwrap();
-        let v = row.get(&self.key_name);
-        if v.is_none() {
-            return ret;
-        }
-        let v = v.unwrap();
-        ret = v.clone();
+        check_if!(!row.contains_key(&self.key_name), return ret);
+        ret = self.get_row_key(row);
         return ret;
     }
 
+    // 行的唯一标识, 组合主键为各主键字段用 KEY_SEP 连接
+    pub fn get_row_key(&self, row: &HashMap<String, String>) -> String {
+        if self.key_fields.len() <= 1 {
+            return utils::map_get_string(row, &self.key_name, "");
+        }
+        return self
+            .key_fields
+            .iter()
+            .map(|k| utils::map_get_string(row, k, ""))
+            .join(KEY_SEP);
+    }
+
+    // 批量修改主键字段后不能为空或与其他行重复, changes 为 (行, 字段, 新值)
+    pub fn check_key_change(&self, changes: &Vec<(usize, String, String)>) -> Vec<String> {
+        let mut rows: HashMap<usize, HashMap<String, String>> = HashMap::new();
+        for (row, k, v) in changes {
+            check_if!(!self.key_fields.contains(k), continue);
+            let map = check_some!(self.data.get(*row), continue);
+            rows.entry(*row)
+                .or_insert_with(|| map.clone())
+                .insert(k.clone(), v.clone());
+        }
+        check_if!(rows.is_empty(), return Vec::new());
+        let mut errors = Vec::new();
+        let mut keys: HashMap<String, usize> = HashMap::new();
+        for (i, one) in self.data.iter().enumerate() {
+            let changed = rows.contains_key(&i);
+            let one = rows.get(&i).unwrap_or(one);
+            let key = self.get_row_key(one);
+            let empty = self
+                .key_fields
+                .iter()
+                .any(|k| utils::map_get_string(one, k, "").is_empty());
+            if empty {
+                check_if!(changed, errors.push(format!("第{}行: 主键不能为空", i + 1)));
+                continue;
+            }
+            if changed && self.has_key_sep(one) {
+                errors.push(format!("第{}行: 组合主键不能包含'{}'", i + 1, KEY_SEP));
+                continue;
+            }
+            if let Some(prev) = keys.insert(key.clone(), i) {
+                if changed || rows.contains_key(&prev) {
+                    errors.push(format!(
+                        "第{}行: 主键[{}]与第{}行重复",
+                        i + 1,
+                        key,
+                        prev + 1
+                    ));
+                }
+            }
+        }
+        return errors;
+    }
+
+    // 组合主键用 KEY_SEP 连接, 字段的值中不能有 KEY_SEP
+    fn has_key_sep(&self, row: &HashMap<String, String>) -> bool {
+        check_if!(self.key_fields.len() <= 1, return false);
+        return self
+            .key_fields
+            .iter()
+            .any(|k| utils::map_get_string(row, k, "").contains(KEY_SEP));
+    }
+
+    pub fn set_row_key(&self, row: &mut HashMap<String, String>, key: &str) {
+        if self.key_fields.len() <= 1 {
+            row.insert(self.key_name.clone(), key.to_string());
+            return;
+        }
+        for (k, v) in self.key_fields.iter().zip(key.split(KEY_SEP)) {
+            row.insert(k.clone(), v.to_string());
+        }
+    }
+
     pub fn get_field_val(&self, key: &String) -> String {
         let mut ret = String::new();
         let row = self.data.get(self.cur_row as usize);
@@ -373,17 +712,149 @@ impl DataTable {
         return ret;
     }
 
-    pub fn create_row(&self, master_val: &String, offset: i32) -> HashMap<String, String> {
-        let mut row = HashMap::new();
-        let mut max = 1;
+    // 按 key_alloc 配置得到新行可用的主键段, row 为新行(或用于推算新行)的数据
+    // Ok(None) 表示不限制, Err 为配置不完整的原因, 此时不能新增行
+    pub fn key_range(&self, row: &HashMap<String, String>) -> Result<Option<(i32, i32)>, String> {
+        match &self.key_alloc {
+            EKeyAlloc::Max => return Ok(None),
+            EKeyAlloc::User => match *USER_KEY_RANGE.lock().unwrap() {
+                Some(range) => return Ok(Some(range)),
+                None => return Err("本地用户未设置主键段".to_string()),
+            },
+            EKeyAlloc::Group(list) => {
+                let group = utils::map_get_string(row, "__Group__", "默认分组");
+                let one = check_some!(
+                    list.iter().find(|a| a.0 == group),
+                    return Err(format!("分组[{}]未配置主键段", group))
+                );
+                return Ok(Some((one.1, one.2)));
+            }
+            EKeyAlloc::Pattern(field, step) => {
+                let name = match field.as_str() {
+                    "group" => &self.group_key,
+                    "master" => &self.master_field,
+                    _ => field,
+                };
+                let val = utils::map_get_string(row, name, "");
+                let val = check_some!(
+                    val.parse::<i32>().ok(),
+                    return Err(format!("字段[{}]的值[{}]不是数字", name, val))
+                );
+                let start = val.saturating_mul(*step);
+                return Ok(Some((
+                    start.saturating_add(1),
+                    start.saturating_add(step - 1),
+                )));
+            }
+        }
+    }
+
+    // 字符串主键按默认值中的 %key% 格式编号, 没有配置时为 表名_%key%
+    fn key_pattern(&self, gen_name: &String) -> String {
+        let field = self.info.iter().find(|a| a.name == *gen_name);
+        let field = check_some!(field, return "%key%".to_string());
+        check_if!(
+            field.val_type == EFieldType::Number,
+            return "%key%".to_string()
+        );
+        if field.default_val.contains("%key%") {
+            return field.default_val.clone();
+        }
+        return format!("{}_%key%", self.table_name);
+    }
+
+    // 主键中的编号部分
+    fn key_num(&self, gen_name: &String, key: &str) -> Option<i32> {
+        let pattern = self.key_pattern(gen_name);
+        let (prefix, suffix) = pattern.split_once("%key%").unwrap();
+        return key
+            .strip_prefix(prefix)
+            .and_then(|a| a.strip_suffix(suffix))
+            .and_then(|a| a.parse::<i32>().ok());
+    }
+
+    // 新行的主键: 最后一个主键字段自动生成, 组合主键前面的字段沿用 src 行的值(如同一技能的下一级)
+    // 编号取主键段内(没有配置时不限制)的最大值加一, 主键段配置错误或已用完时返回错误
+    fn gen_key(
+        &self,
+        src: &HashMap<String, String>,
+        offset: i32,
+    ) -> Result<HashMap<String, String>> {
+        let mut ret = HashMap::new();
+        let gen_name = check_some!(self.key_fields.last(), return Ok(ret));
+        for name in &self.key_fields[..self.key_fields.len() - 1] {
+            let field = check_some!(self.info.iter().find(|a| a.name == *name), continue);
+            ret.insert(
+                name.clone(),
+                utils::map_get_string(src, name, &field.default_val),
+            );
+        }
+        let same_prefix = |row: &&HashMap<String, String>| {
+            ret.iter()
+                .all(|(k, v)| utils::map_get_string(row, k, "") == *v)
+        };
+
+        let range = match self.key_range(src) {
+            Ok(range) => range,
+            Err(e) => bail!("[{}]{}", self.show_name, e),
+        };
+        let (start, end) = range.unwrap_or((1, i32::MAX));
+        let mut max = start;
+        for one in self.data.iter().filter(same_prefix) {
+            let key = utils::map_get_string(one, gen_name, "");
+            let num = check_some!(self.key_num(gen_name, &key), continue);
+            check_if!(num < start || num > end, continue);
+            if num >= max {
+                max = num + 1;
+            }
+        }
+        let num = max.saturating_add(offset);
+        check_if!(
+            num > end || num == i32::MAX,
+            bail!("[{}]主键段[{}-{}]已用完", self.show_name, start, end)
+        );
+        let v = self
+            .key_pattern(gen_name)
+            .replacen("%key%", &num.to_string(), 1);
+        ret.insert(gen_name.clone(), v);
+        return Ok(ret);
+    }
+
+    pub fn create_row(&self, master_val: &String, offset: i32) -> Result<HashMap<String, String>> {
+        self.new_row(master_val, offset, None)
+    }
+
+    // 使用指定的主键创建新行, 不分配主键, 不受主键段限制
+    pub fn create_row_with_key(
+        &self,
+        master_val: &String,
+        offset: i32,
+        key: &str,
+    ) -> HashMap<String, String> {
+        let mut key_map = HashMap::new();
+        self.set_row_key(&mut key_map, key);
+        let src = self.row_src(master_val, offset, None);
+        return self.build_row(master_val, offset, &src, key_map);
+    }
+
+    // src 为推算主键和分组的参考行, 为空时使用当前行
+    fn new_row(
+        &self,
+        master_val: &String,
+        offset: i32,
+        src: Option<&HashMap<String, String>>,
+    ) -> Result<HashMap<String, String>> {
+        let src = self.row_src(master_val, offset, src);
+        let key = self.gen_key(&src, offset)?;
+        return Ok(self.build_row(master_val, offset, &src, key));
+    }
+
+    // 新行的分组编号, 同一个主表值下的最大值加一
+    fn next_group(&self, master_val: &String, offset: i32) -> i32 {
         let mut max_group = 1;
         let group_key = self.group_key.clone();
         let master_field = self.master_field.clone();
         for one in &self.data {
-            let key_val = utils::map_get_i32(&one, &self.key_name);
-            if key_val >= max {
-                max = key_val + 1;
-            }
             if !group_key.is_empty() && !master_field.is_empty() {
                 let master = one.get(&master_field);
                 if master.is_some() {
@@ -397,21 +868,66 @@ impl DataTable {
                 }
             }
         }
-        max = max + offset;
-        max_group = max_group + offset;
+        return max_group + offset;
+    }
+
+    // 推算主键和分组的参考行, 主表值和分组换成新行的
+    fn row_src(
+        &self,
+        master_val: &String,
+        offset: i32,
+        src: Option<&HashMap<String, String>>,
+    ) -> HashMap<String, String> {
+        let src = src.or(self.data.get(self.cur_row as usize));
+        let mut src = src.cloned().unwrap_or_default();
+        if !self.master_field.is_empty() && !master_val.is_empty() {
+            src.insert(self.master_field.clone(), master_val.clone());
+        }
+        if !self.group_key.is_empty() && !self.master_field.is_empty() {
+            let group = self.next_group(master_val, offset);
+            src.insert(self.group_key.clone(), group.to_string());
+        }
+        return src;
+    }
+
+    // 按字段默认值生成新行, key 为主键字段的值
+    fn build_row(
+        &self,
+        master_val: &String,
+        offset: i32,
+        src: &HashMap<String, String>,
+        key: HashMap<String, String>,
+    ) -> HashMap<String, String> {
+        let mut row = HashMap::new();
+        let max_group = self.next_group(master_val, offset);
+        let group_key = self.group_key.clone();
+        let master_field = self.master_field.clone();
+        let key_val = utils::map_get_string(
+            &key,
+            check_some!(self.key_fields.last(), &self.key_name),
+            "",
+        );
         for one in &self.info {
             let mut v = one.default_val.clone();
             if group_key == one.name {
                 v = max_group.to_string();
             }
             if one.is_key {
-                v = max.to_string();
+                v = utils::map_get_string(&key, &one.name, "");
             }
             if master_field == one.name {
                 v = master_val.clone();
             }
+            // 新行排在最后
+            if self.order_field == one.name {
+                v = (self.max_order() + 1 + offset).to_string();
+            }
+            // 按分组分配主键时新行放到参考行所在的分组
+            if one.name == "__Group__" && matches!(self.key_alloc, EKeyAlloc::Group(_)) {
+                v = utils::map_get_string(src, &one.name, &v);
+            }
 
-            let v = v.replace("%key%", max.to_string().as_str());
+            let v = v.replace("%key%", key_val.as_str());
             let v = v.replace("%group%", max_group.to_string().as_str());
             let v = v.replace("%master%", master_val.as_str());
             row.insert(one.name.clone(), v);
@@ -426,19 +942,19 @@ impl DataTable {
         idx: usize,
         master_val: &String,
         offset: i32,
-    ) -> Option<HashMap<String, String>> {
+    ) -> Result<Option<HashMap<String, String>>> {
         println!("copy_row {}", idx);
         let len = self.data.len();
         let cur_row = idx;
         if cur_row >= len {
-            return None;
+            return Ok(None);
         }
-        let mut new_row = self.create_row(&master_val, offset);
         let cur_row = self.data.get(cur_row as usize);
         if cur_row.is_none() {
-            return None;
+            return Ok(None);
         }
         let cur_row = cur_row.unwrap();
+        let mut new_row = self.new_row(&master_val, offset, Some(cur_row))?;
 
         let group_key = self.group_key.clone();
         let master_key = self.master_field.clone();
@@ -446,30 +962,218 @@ impl DataTable {
             check_if!(one.is_key, continue);
             check_if!(group_key == one.name, continue);
             check_if!(master_key == one.name, continue);
+            check_if!(self.order_field == one.name, continue);
             let cur = check_some!(cur_row.get(&one.name), continue);
             new_row.insert(one.name.clone(), cur.clone());
         }
-        return Some(new_row);
+        return Ok(Some(new_row));
+    }
+
+    fn max_order(&self) -> i32 {
+        let max = self
+            .data
+            .iter()
+            .map(|a| utils::map_get_i32(a, &self.order_field));
+        return max.max().unwrap_or(0);
+    }
+
+    // rows 为调整后的显示顺序, 返回需要修改的顺序字段 (行, 字段, 值)
+    // 原来的顺序值没有重复时在这些值之间重新分配, 不影响其他分组的行, 否则从最小值开始依次编号
+    pub fn reorder_rows(&self, rows: &Vec<usize>) -> Vec<(usize, String, String)> {
+        let mut ret = Vec::new();
+        check_if!(self.order_field.is_empty(), return ret);
+        let mut vals: Vec<i32> = rows
+            .iter()
+            .filter_map(|a| self.data.get(*a))
+            .map(|a| utils::map_get_i32(a, &self.order_field))
+            .sorted()
+            .collect();
+        check_if!(vals.len() != rows.len(), return ret);
+        if vals.windows(2).any(|a| a[0] == a[1]) {
+            let start = vals.first().cloned().unwrap_or(0).max(1);
+            vals = (0..rows.len() as i32).map(|a| start + a).collect();
+        }
+        for (row, val) in rows.iter().zip(vals) {
+            let val = val.to_string();
+            let old = utils::map_get_string(&self.data[*row], &self.order_field, "");
+            check_if!(old == val, continue);
+            ret.push((*row, self.order_field.clone(), val));
+        }
+        return ret;
     }
 
-    pub fn copy_cur_row(&self, master_val: &String) -> Option<HashMap<String, String>> {
+    pub fn copy_cur_row(&self, master_val: &String) -> Result<Option<HashMap<String, String>>> {
         self.copy_row(self.cur_row as usize, master_val, 0)
     }
 
+    // 复制选中的行为 TSV, 第一行为字段名; fields 为空时复制所有字段, 主键总是复制
+    pub fn to_tsv(&self, rows: &Vec<usize>, fields: &Vec<String>) -> String {
+        let header: Vec<String> = self
+            .info
+            .iter()
+            .filter(|a| fields.is_empty() || a.is_key || fields.contains(&a.name))
+            .map(|a| a.name.clone())
+            .collect();
+        let mut list = vec![header.clone()];
+        for row in rows {
+            let map = check_some!(self.data.get(*row), continue);
+            list.push(
+                header
+                    .iter()
+                    .map(|k| utils::map_get_string(map, k, ""))
+                    .collect(),
+            );
+        }
+        return utils::to_tsv(&list);
+    }
+
+    // 解析粘贴的 TSV, 第一行按字段名或标题对应字段, 主键已存在的更新, 否则新增
+    // 返回 (更新的 (行, 字段, 新值), 新增的行)
+    pub fn parse_tsv(
+        &self,
+        text: &str,
+        master_val: &String,
+    ) -> Result<(Vec<(usize, String, String)>, Vec<HashMap<String, String>>)> {
+        let rows = utils::parse_tsv(text);
+        check_if!(rows.len() < 2, bail!("粘贴内容至少需要标题行和一行数据"));
+        let cols: Vec<Option<&FieldInfo>> = rows[0]
+            .iter()
+            .map(|title| {
+                let title = title.trim();
+                self.info
+                    .iter()
+                    .find(|a| a.name == title)
+                    .or_else(|| self.info.iter().find(|a| a.title == title))
+            })
+            .collect();
+        check_if!(
+            cols.iter().all(|a| a.is_none()),
+            bail!("标题行没有匹配的字段")
+        );
+        // 组合主键需要所有主键字段都有对应的列
+        let key_cols: Option<Vec<usize>> = self
+            .key_fields
+            .iter()
+            .map(|k| cols.iter().position(|a| a.map_or(false, |f| f.name == *k)))
+            .collect();
+        // 只有部分主键列时无法对应已有的行
+        if key_cols.is_none() {
+            let missing: Vec<&String> = self
+                .key_fields
+                .iter()
+                .filter(|k| !cols.iter().any(|a| a.map_or(false, |f| f.name == **k)))
+                .collect();
+            check_if!(
+                missing.len() < self.key_fields.len(),
+                bail!("缺少主键列: {}", missing.iter().join(", "))
+            );
+        }
+
+        let mut keys = HashMap::new();
+        for (idx, one) in self.data.iter().enumerate() {
+            keys.insert(self.get_row_key(one), idx);
+        }
+        let mut added = HashSet::new();
+        let mut errors = Vec::new();
+        let mut update = Vec::new();
+        let mut add = Vec::new();
+        let mut offset = 0;
+        for (line_idx, line) in rows.iter().enumerate().skip(1) {
+            check_if!(line.iter().all(|a| a.trim().is_empty()), continue);
+            let key_vals: Vec<String> = key_cols
+                .iter()
+                .flatten()
+                .map(|c| {
+                    line.get(*c)
+                        .map(|a| a.trim().to_string())
+                        .unwrap_or_default()
+                })
+                .collect();
+            if self.key_fields.len() > 1 && key_vals.iter().any(|a| a.contains(KEY_SEP)) {
+                errors.push(format!(
+                    "第{}行: 组合主键不能包含'{}'",
+                    line_idx + 1,
+                    KEY_SEP
+                ));
+                continue;
+            }
+            let key = if key_cols.is_none() || key_vals.iter().any(|a| a.is_empty()) {
+                String::new()
+            } else {
+                key_vals.join(KEY_SEP)
+            };
+            let mut vals = Vec::new();
+            for (col, field) in cols.iter().enumerate() {
+                let field = check_some!(field, continue);
+                let val = line.get(col).cloned().unwrap_or_default();
+                check_if!(field.is_key && key.is_empty(), continue);
+                let (err, msg) = field.check_data(&val);
+                if err {
+                    errors.push(format!("第{}行[{}]: {}", line_idx + 1, field.title, msg));
+                }
+                vals.push((field.name.clone(), val));
+            }
+
+            if added.contains(&key) {
+                errors.push(format!("第{}行: 主键[{}]重复", line_idx + 1, key));
+                continue;
+            }
+            if let Some(row) = keys.get(&key) {
+                for (k, v) in vals {
+                    update.push((*row, k, v));
+                }
+                continue;
+            }
+
+            // 新增行, 填写了主键的直接使用, 没有填写的自动分配
+            let mut map = if key.is_empty() {
+                self.create_row(master_val, offset)?
+            } else {
+                self.create_row_with_key(master_val, offset, &key)
+            };
+            offset = offset + 1;
+            while key.is_empty() {
+                let new_key = self.get_row_key(&map);
+                check_if!(
+                    !keys.contains_key(&new_key) && !added.contains(&new_key),
+                    break
+                );
+                map = self.create_row(master_val, offset)?;
+                offset = offset + 1;
+            }
+            for (k, v) in vals {
+                map.insert(k, v);
+            }
+            added.insert(self.get_row_key(&map));
+            add.push(map);
+        }
+        if !errors.is_empty() {
+            let more = if errors.len() > 10 {
+                format!("\n...共{}处错误", errors.len())
+            } else {
+                String::new()
+            };
+            bail!("{}{}", errors.iter().take(10).join("\n"), more);
+        }
+        Ok((update, add))
+    }
+
     pub fn get_show_name_list(
         &self,
         master_key: &String,
         id: &String,
         show_all: bool,
         search: &String,
+        view: &ListViewCfg,
     ) -> HashMap<String, HashMap<String, Vec<(String, i32, i32, bool)>>> {
         let mut total: HashMap<String, HashMap<String, Vec<(String, i32, i32, bool)>>> =
             HashMap::new();
 
         let mut idx = 0;
+        let query = data_query::get_query(search);
         let mut key_cnt: HashMap<String, i32> = HashMap::new();
         for one in &self.data {
-            let key = utils::map_get_string(&one, &self.key_name, "");
+            let key = self.get_row_key(one);
             let mut cnt = 0;
             if key_cnt.contains_key(&key) {
                 cnt = *key_cnt.get(&key).unwrap();
@@ -479,7 +1183,7 @@ impl DataTable {
         }
         for one in &self.data {
             let name = self.get_one_show_name(one);
-            let key = utils::map_get_string(&one, &self.key_name, "");
+            let key = self.get_row_key(one);
             idx = idx + 1;
             check_if!(name.is_none(), continue);
             let name = name.unwrap();
@@ -487,12 +1191,9 @@ impl DataTable {
                 let rel_id = check_some!(one.get(master_key), continue);
                 check_if!(rel_id != id, continue);
             }
-            let group = utils::map_get_string(one, "__Group__", "默认分组");
-            let sub_group = utils::map_get_string(one, "__SubGroup__", "默认子分组");
+            let (group, sub_group) = self.get_group_name(one, view);
             let key_num = utils::map_get_i32(one, &self.key_name);
-            if !search.is_empty() && !utils::map_contains_str(one, &search) {
-                continue;
-            }
+            check_if!(!query.matches(&self.info, one), continue);
 
             if !total.contains_key(&group) {
                 total.insert(group.clone(), HashMap::new());
@@ -515,20 +1216,100 @@ impl DataTable {
             // }
             layer2.push((name, idx - 1, key_num, dup));
         }
+        // 没有选择排序字段时按手动顺序排序, 没有配置顺序字段时按主键排序
+        let sort = if view.sort.is_empty() {
+            &self.order_field
+        } else {
+            &view.sort
+        };
+        let field = self.info.iter().find(|a| a.name == *sort);
+        let empty = String::new();
         for (_, one) in &mut total {
             for (_, two) in one {
-                two.sort_by(|a, b| a.2.cmp(&b.2))
+                two.sort_by(|a, b| {
+                    let a = &self.data[a.1 as usize];
+                    let b = &self.data[b.1 as usize];
+                    let ord = if sort.is_empty() {
+                        compare_key(&self.info, a, b)
+                    } else {
+                        let va = a.get(sort).unwrap_or(&empty);
+                        let vb = b.get(sort).unwrap_or(&empty);
+                        compare_by_type(field, view.sort_type, va, vb)
+                            .then_with(|| compare_key(&self.info, a, b))
+                    };
+                    if view.desc {
+                        ord.reverse()
+                    } else {
+                        ord
+                    }
+                })
             }
         }
         return total;
     }
 
-    fn get_one_show_name(&self, map: &HashMap<String, String>) -> Option<String> {
-        let v = map.get(&self.key_name);
-        if v.is_none() {
-            return None;
+    // 返回 (分组, 子分组), 为空时列表中不显示这一层
+    fn get_group_name(
+        &self,
+        row: &HashMap<String, String>,
+        view: &ListViewCfg,
+    ) -> (String, String) {
+        if view.flatten {
+            return (String::new(), String::new());
         }
-        let v = v.unwrap();
+        if view.group.is_empty() {
+            let group = utils::map_get_string(row, "__Group__", "默认分组");
+            let sub_group = utils::map_get_string(row, "__SubGroup__", "默认子分组");
+            return (group, sub_group);
+        }
+        let val = utils::map_get_string(row, &view.group, "");
+        check_if!(val.is_empty(), return ("(空)".to_string(), String::new()));
+        // 枚举分组显示枚举名
+        let field = self.info.iter().find(|a| a.name == view.group);
+        let show = field
+            .and_then(|f| f.get_opt())
+            .and_then(|opts| opts.iter().find(|a| a.val == val).map(|a| a.show.clone()));
+        let group = match show {
+            Some(show) if show != val => format!("{}({})", show, val),
+            _ => val,
+        };
+        return (group, String::new());
+    }
+
+    pub fn get_option_list(
+        &self,
+        show_field: &String,
+        filter: &Vec<(String, String)>,
+    ) -> Vec<EnumOption> {
+        let mut show_field = show_field;
+        if show_field.is_empty() {
+            show_field = &self.show_field;
+        }
+        let mut ret = Vec::new();
+        for one in self
+            .data
+            .iter()
+            .sorted_by(|a, b| compare_key(&self.info, a, b))
+        {
+            let val = self.get_row_key(one);
+            check_if!(val.is_empty(), continue);
+            let pass = filter
+                .iter()
+                .all(|(k, v)| utils::map_get_string(one, k, "") == *v);
+            check_if!(!pass, continue);
+            let show = utils::map_get_string(one, show_field, "");
+            ret.push(EnumOption {
+                show,
+                val,
+                code: String::new(),
+            });
+        }
+        return ret;
+    }
+
+    pub fn get_one_show_name(&self, map: &HashMap<String, String>) -> Option<String> {
+        check_if!(!map.contains_key(&self.key_name), return None);
+        let v = self.get_row_key(map);
         let name = match map.get(&self.show_field) {
             None => String::new(),
             Some(s) => s.clone(),
@@ -548,7 +1329,13 @@ impl DataTable {
     }
 
     pub fn update_cur_row(&mut self, master_val: &String) {
-        let list = self.get_show_name_list(&self.master_field, master_val, false, &String::new());
+        let list = self.get_show_name_list(
+            &self.master_field,
+            master_val,
+            false,
+            &String::new(),
+            &ListViewCfg::default(),
+        );
         for (_, one) in list {
             for (_, two) in one {
                 for (_, idx, _, _) in two {
@@ -588,11 +1375,7 @@ impl DataTable {
         }
 
         let mut row_idx = 3;
-        for row in self
-            .data
-            .iter()
-            .sorted_by_key(|a| utils::map_get_i32(a, &self.export_sort))
-        {
+        for row in saver::sort_rows(&self.info, &self.data, &self.export_sort) {
             row_idx = row_idx + 1;
             let mut col = 0;
             for one in &self.info {
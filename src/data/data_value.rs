@@ -0,0 +1,769 @@
+use anyhow::{anyhow, bail, Result};
+use eframe::{egui, epaint::Color32};
+use serde_json::json;
+use std::cmp::Ordering;
+
+use crate::marco::check_if;
+
+use super::data_check;
+
+// 表格(M)字段的键
+#[derive(Debug, Clone, PartialEq)]
+pub enum ETableKey {
+    // 数组部分, 按出现顺序从 1 开始编号
+    Index,
+    Int(i64),
+    Name(String),
+}
+
+impl ETableKey {
+    // 非数组部分的键排序: 整数键按数值在前, 字符串键在后
+    fn cmp_key(&self, other: &ETableKey) -> Ordering {
+        match (self, other) {
+            (ETableKey::Index, ETableKey::Index) => Ordering::Equal,
+            (ETableKey::Index, _) => Ordering::Less,
+            (_, ETableKey::Index) => Ordering::Greater,
+            (ETableKey::Int(a), ETableKey::Int(b)) => a.cmp(b),
+            (ETableKey::Int(_), ETableKey::Name(_)) => Ordering::Less,
+            (ETableKey::Name(_), ETableKey::Int(_)) => Ordering::Greater,
+            (ETableKey::Name(a), ETableKey::Name(b)) => a.cmp(b),
+        }
+    }
+}
+
+// 表格(M)字段的值, 保留类型和书写顺序
+#[derive(Debug, Clone, PartialEq)]
+pub enum ELuaValue {
+    Bool(bool),
+    // 整数单独保存, 超过 2^53 的整数不丢失精度
+    Int(i64),
+    Number(f64),
+    Str(String),
+    Table(Vec<(ETableKey, ELuaValue)>),
+}
+
+const LUA_KEYWORDS: [&str; 22] = [
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if", "in",
+    "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    let first = match chars.next() {
+        Some(c) => c,
+        None => return false,
+    };
+    check_if!(!(first.is_ascii_alphabetic() || first == '_'), return false);
+    check_if!(
+        !chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        return false
+    );
+    return !LUA_KEYWORDS.contains(&s);
+}
+
+// 字符串转为 lua 字面量, 对引号/反斜杠/控制字符转义
+pub fn lua_str_literal(s: &str) -> String {
+    let mut ret = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            '\n' => ret.push_str("\\n"),
+            '\r' => ret.push_str("\\r"),
+            '\t' => ret.push_str("\\t"),
+            c if c.is_control() => ret.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => ret.push(c),
+        }
+    }
+    ret.push('"');
+    return ret;
+}
+
+// 小数保留小数点或指数, 再次解析时仍然是小数, 如 2.0, 1e20
+// inf/nan 没有字面量, 写成 lua 表达式, 编辑时由 check_value 拒绝
+fn number_str(v: f64) -> String {
+    check_if!(v.is_nan(), return "0/0".to_string());
+    check_if!(v == f64::INFINITY, return "math.huge".to_string());
+    check_if!(v == f64::NEG_INFINITY, return "-math.huge".to_string());
+    return format!("{:?}", v);
+}
+
+impl ELuaValue {
+    pub fn new_table() -> ELuaValue {
+        ELuaValue::Table(Vec::new())
+    }
+
+    // 解析 lua 表格字符串, 空字符串视为空表
+    // 只支持字面量的表格构造, 其余写法交给 lua 求值(此时键的顺序无法保留)
+    pub fn parse(text: &str) -> Result<ELuaValue> {
+        let mut text = text.trim();
+        check_if!(text.is_empty(), return Ok(ELuaValue::new_table()));
+        if let Some(s) = text.strip_prefix("return") {
+            text = s.trim();
+        }
+        let mut parser = LuaParser {
+            chars: text.chars().collect(),
+            pos: 0,
+        };
+        let ret = parser.parse_all();
+        if ret.is_ok() {
+            return ret;
+        }
+        let value = data_check::with_sandbox(|lua, env| {
+            let value: mlua::Value = lua.load(text).set_environment(env)?.eval()?;
+            ELuaValue::from_lua(value)
+        });
+        let value = match value {
+            Ok(v) => v,
+            Err(_) => return ret,
+        };
+        check_if!(!value.is_table(), bail!("内容不是表格"));
+        return Ok(value);
+    }
+
+    pub fn from_lua(value: mlua::Value) -> Result<ELuaValue> {
+        let ret = match value {
+            mlua::Value::Boolean(b) => ELuaValue::Bool(b),
+            mlua::Value::Integer(i) => ELuaValue::Int(i),
+            mlua::Value::Number(n) => ELuaValue::Number(n),
+            mlua::Value::String(s) => ELuaValue::Str(s.to_str()?.to_string()),
+            mlua::Value::Table(t) => {
+                let mut list = Vec::new();
+                let len = t.raw_len();
+                for v in t.clone().sequence_values::<mlua::Value>() {
+                    list.push((ETableKey::Index, ELuaValue::from_lua(v?)?));
+                }
+                let mut other = Vec::new();
+                for pair in t.pairs::<mlua::Value, mlua::Value>() {
+                    let (k, v) = pair?;
+                    let key = match k {
+                        mlua::Value::Integer(i) if i >= 1 && i <= len => continue,
+                        mlua::Value::Integer(i) => ETableKey::Int(i),
+                        mlua::Value::String(s) => ETableKey::Name(s.to_str()?.to_string()),
+                        _ => bail!("不支持的表格键[{:?}]", k),
+                    };
+                    other.push((key, ELuaValue::from_lua(v)?));
+                }
+                other.sort_by(|a, b| a.0.cmp_key(&b.0));
+                list.append(&mut other);
+                ELuaValue::Table(list)
+            }
+            _ => bail!("不支持的值类型[{}]", value.type_name()),
+        };
+        return Ok(ret);
+    }
+
+    // 检查数字是否有效, 以及同一层中是否有重复的键
+    // 整数键和数组部分的序号相同时也算重复
+    pub fn check_value(&self) -> Result<()> {
+        let list = match self {
+            ELuaValue::Number(n) if !n.is_finite() => bail!("数字[{}]无效", n),
+            ELuaValue::Table(list) => list,
+            _ => return Ok(()),
+        };
+        let len = list.iter().filter(|(k, _)| *k == ETableKey::Index).count() as i64;
+        let mut keys = Vec::new();
+        for (k, v) in list {
+            match k {
+                ETableKey::Index => {}
+                ETableKey::Int(i) if *i >= 1 && *i <= len => bail!("键[{}]和数组元素重复", i),
+                ETableKey::Int(i) => {
+                    check_if!(keys.contains(&k), bail!("键[{}]重复", i));
+                    keys.push(k);
+                }
+                ETableKey::Name(n) => {
+                    check_if!(keys.contains(&k), bail!("键[{}]重复", n));
+                    keys.push(k);
+                }
+            }
+            v.check_value()?;
+        }
+        return Ok(());
+    }
+
+    pub fn is_table(&self) -> bool {
+        matches!(self, ELuaValue::Table(_))
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            ELuaValue::Bool(_) => "布尔",
+            ELuaValue::Int(_) | ELuaValue::Number(_) => "数字",
+            ELuaValue::Str(_) => "字符串",
+            ELuaValue::Table(_) => "表格",
+        }
+    }
+
+    fn default_of(type_name: &str) -> ELuaValue {
+        match type_name {
+            "布尔" => ELuaValue::Bool(false),
+            "字符串" => ELuaValue::Str(String::new()),
+            "表格" => ELuaValue::new_table(),
+            _ => ELuaValue::Int(0),
+        }
+    }
+
+    pub fn to_lua(&self) -> String {
+        match self {
+            ELuaValue::Bool(b) => b.to_string(),
+            ELuaValue::Int(i) => i.to_string(),
+            ELuaValue::Number(n) => number_str(*n),
+            ELuaValue::Str(s) => lua_str_literal(s),
+            ELuaValue::Table(list) => {
+                check_if!(list.is_empty(), return "{}".to_string());
+                let mut ret = Vec::new();
+                for (k, v) in list {
+                    let one = match k {
+                        ETableKey::Index => v.to_lua(),
+                        ETableKey::Int(i) => format!("[{}] = {}", i, v.to_lua()),
+                        ETableKey::Name(n) if is_identifier(n) => format!("{} = {}", n, v.to_lua()),
+                        ETableKey::Name(n) => format!("[{}] = {}", lua_str_literal(n), v.to_lua()),
+                    };
+                    ret.push(one);
+                }
+                format!("{{{}}}", ret.join(", "))
+            }
+        }
+    }
+
+    // 只有数组部分的表格导出为 json 数组, 其余导出为对象
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            ELuaValue::Bool(b) => json!(b),
+            ELuaValue::Int(i) => json!(i),
+            ELuaValue::Number(n) => json!(n),
+            ELuaValue::Str(s) => json!(s),
+            ELuaValue::Table(list) => {
+                let is_array = !list.is_empty() && list.iter().all(|(k, _)| *k == ETableKey::Index);
+                if is_array {
+                    let arr: Vec<serde_json::Value> =
+                        list.iter().map(|(_, v)| v.to_json()).collect();
+                    return json!(arr);
+                }
+                let mut ret = json!({});
+                let map = ret.as_object_mut().unwrap();
+                let mut idx = 0;
+                for (k, v) in list {
+                    let key = match k {
+                        ETableKey::Index => {
+                            idx = idx + 1;
+                            idx.to_string()
+                        }
+                        ETableKey::Int(i) => i.to_string(),
+                        ETableKey::Name(n) => n.clone(),
+                    };
+                    map.insert(key, v.to_json());
+                }
+                ret
+            }
+        }
+    }
+
+    // 标量转为单元格中的文本, 表格转为 lua 字符串
+    pub fn to_cell(&self) -> String {
+        match self {
+            ELuaValue::Str(s) => s.clone(),
+            _ => self.to_lua(),
+        }
+    }
+}
+
+struct LuaParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl LuaParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).cloned()
+    }
+
+    fn err(&self, msg: &str) -> anyhow::Error {
+        let line = self.chars[..self.pos.min(self.chars.len())]
+            .iter()
+            .filter(|c| **c == '\n')
+            .count();
+        anyhow!("第{}行: {}", line + 1, msg)
+    }
+
+    fn skip_ws(&mut self) {
+        loop {
+            while self.peek().map_or(false, |c| c.is_whitespace()) {
+                self.pos += 1;
+            }
+            if self.peek() == Some('-') && self.peek_at(1) == Some('-') {
+                self.pos += 2;
+                if self.peek() == Some('[') {
+                    let save = self.pos;
+                    if self.read_long_bracket().is_ok() {
+                        continue;
+                    }
+                    self.pos = save;
+                }
+                while self.peek().map_or(false, |c| c != '\n') {
+                    self.pos += 1;
+                }
+                continue;
+            }
+            break;
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        self.skip_ws();
+        if self.peek() != Some(c) {
+            return Err(self.err(&format!("此处应为'{}'", c)));
+        }
+        self.pos += 1;
+        return Ok(());
+    }
+
+    fn parse_all(&mut self) -> Result<ELuaValue> {
+        self.skip_ws();
+        check_if!(
+            self.peek() != Some('{'),
+            return Err(self.err("内容不是表格"))
+        );
+        let ret = self.parse_value()?;
+        self.skip_ws();
+        check_if!(
+            self.pos < self.chars.len(),
+            return Err(self.err("表格后有多余内容"))
+        );
+        return Ok(ret);
+    }
+
+    fn read_ident(&mut self) -> String {
+        let mut ret = String::new();
+        while let Some(c) = self.peek() {
+            check_if!(!(c.is_ascii_alphanumeric() || c == '_'), break);
+            ret.push(c);
+            self.pos += 1;
+        }
+        return ret;
+    }
+
+    fn parse_value(&mut self) -> Result<ELuaValue> {
+        self.skip_ws();
+        let c = match self.peek() {
+            Some(c) => c,
+            None => return Err(self.err("内容不完整")),
+        };
+        match c {
+            '{' => self.parse_table(),
+            '"' | '\'' => Ok(ELuaValue::Str(self.read_string()?)),
+            '[' => Ok(ELuaValue::Str(self.read_long_bracket()?)),
+            '-' | '.' | '0'..='9' => self.read_number(),
+            _ if c.is_ascii_alphabetic() || c == '_' => {
+                let ident = self.read_ident();
+                match ident.as_str() {
+                    "true" => Ok(ELuaValue::Bool(true)),
+                    "false" => Ok(ELuaValue::Bool(false)),
+                    _ => Err(self.err(&format!("不支持的值[{}]", ident))),
+                }
+            }
+            _ => Err(self.err(&format!("无法识别的字符'{}'", c))),
+        }
+    }
+
+    fn parse_table(&mut self) -> Result<ELuaValue> {
+        self.expect('{')?;
+        let mut list = Vec::new();
+        loop {
+            self.skip_ws();
+            if self.peek() == Some('}') {
+                self.pos += 1;
+                break;
+            }
+            let key = self.parse_key()?;
+            self.skip_ws();
+            // nil 表示没有值, 直接跳过
+            let save = self.pos;
+            if self.read_ident() != "nil" {
+                self.pos = save;
+                let value = self.parse_value()?;
+                list.push((key, value));
+            }
+            self.skip_ws();
+            match self.peek() {
+                Some(',') | Some(';') => self.pos += 1,
+                Some('}') => {}
+                _ => return Err(self.err("此处应为','或'}'")),
+            }
+        }
+        return Ok(ELuaValue::Table(list));
+    }
+
+    fn parse_key(&mut self) -> Result<ETableKey> {
+        let save = self.pos;
+        if self.peek() == Some('[') && !matches!(self.peek_at(1), Some('[') | Some('=')) {
+            self.pos += 1;
+            let key = match self.parse_value()? {
+                ELuaValue::Str(s) => ETableKey::Name(s),
+                ELuaValue::Int(i) => ETableKey::Int(i),
+                ELuaValue::Number(n) if n.fract() == 0.0 => ETableKey::Int(n as i64),
+                _ => return Err(self.err("表格的键只支持字符串或整数")),
+            };
+            self.expect(']')?;
+            self.expect('=')?;
+            return Ok(key);
+        }
+        let ident = self.read_ident();
+        if !ident.is_empty() && !ident.chars().next().unwrap().is_ascii_digit() {
+            self.skip_ws();
+            if self.peek() == Some('=') && self.peek_at(1) != Some('=') {
+                self.pos += 1;
+                return Ok(ETableKey::Name(ident));
+            }
+        }
+        self.pos = save;
+        return Ok(ETableKey::Index);
+    }
+
+    // 没有小数点和指数的数字保存为整数
+    fn read_number(&mut self) -> Result<ELuaValue> {
+        let mut neg = false;
+        if self.peek() == Some('-') {
+            neg = true;
+            self.pos += 1;
+            self.skip_ws();
+        }
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            let exp_sign = (c == '+' || c == '-')
+                && matches!(text.chars().last(), Some('e') | Some('E'))
+                && !text.starts_with("0x");
+            check_if!(!(c.is_ascii_alphanumeric() || c == '.' || exp_sign), break);
+            text.push(c);
+            self.pos += 1;
+        }
+        let lower = text.to_lowercase();
+        let int = if let Some(hex) = lower.strip_prefix("0x") {
+            // lua 的十六进制整数按 64 位回绕
+            u64::from_str_radix(hex, 16).map(|v| v as i64).ok()
+        } else {
+            text.parse::<i64>().ok()
+        };
+        if let Some(i) = int {
+            return Ok(ELuaValue::Int(if neg { i.wrapping_neg() } else { i }));
+        }
+        let num = match text.parse::<f64>() {
+            Ok(n) => n,
+            Err(_) => return Err(self.err(&format!("数字格式错误[{}]", text))),
+        };
+        return Ok(ELuaValue::Number(if neg { -num } else { num }));
+    }
+
+    // 转义按 lua 的规则处理, \ddd 和 \xXX 是字节, 最后按 UTF-8 转为字符串
+    fn read_string(&mut self) -> Result<String> {
+        let quote = self.peek().unwrap();
+        self.pos += 1;
+        let mut ret: Vec<u8> = Vec::new();
+        let mut buf = [0u8; 4];
+        loop {
+            let c = match self.peek() {
+                Some(c) => c,
+                None => return Err(self.err("字符串未结束")),
+            };
+            self.pos += 1;
+            if c == quote {
+                break;
+            }
+            check_if!(c == '\n', return Err(self.err("字符串未结束")));
+            if c != '\\' {
+                ret.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                continue;
+            }
+            let e = match self.peek() {
+                Some(e) => e,
+                None => return Err(self.err("字符串未结束")),
+            };
+            self.pos += 1;
+            match e {
+                'n' => ret.push(b'\n'),
+                't' => ret.push(b'\t'),
+                'r' => ret.push(b'\r'),
+                'a' => ret.push(0x07),
+                'b' => ret.push(0x08),
+                'f' => ret.push(0x0c),
+                'v' => ret.push(0x0b),
+                '\n' => ret.push(b'\n'),
+                'z' => {
+                    while self.peek().map_or(false, |c| c.is_whitespace()) {
+                        self.pos += 1;
+                    }
+                }
+                'x' => {
+                    let hex: String = self.chars[self.pos..].iter().take(2).collect();
+                    self.pos += hex.chars().count();
+                    let v = u8::from_str_radix(&hex, 16).map_err(|_| self.err("\\x 转义错误"))?;
+                    ret.push(v);
+                }
+                'u' => {
+                    self.expect('{')?;
+                    let mut hex = String::new();
+                    while self.peek().map_or(false, |c| c.is_ascii_hexdigit()) {
+                        hex.push(self.peek().unwrap());
+                        self.pos += 1;
+                    }
+                    self.expect('}')?;
+                    let v = u32::from_str_radix(&hex, 16).map_err(|_| self.err("\\u 转义错误"))?;
+                    let c = char::from_u32(v).unwrap_or('?');
+                    ret.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+                '0'..='9' => {
+                    let mut dec = e.to_string();
+                    while dec.len() < 3 && self.peek().map_or(false, |c| c.is_ascii_digit()) {
+                        dec.push(self.peek().unwrap());
+                        self.pos += 1;
+                    }
+                    let v = dec
+                        .parse::<u8>()
+                        .map_err(|_| self.err("\\ddd 转义超出范围"))?;
+                    ret.push(v);
+                }
+                _ => ret.extend_from_slice(e.encode_utf8(&mut buf).as_bytes()),
+            }
+        }
+        return String::from_utf8(ret).map_err(|_| self.err("字符串不是有效的UTF-8"));
+    }
+
+    // [[...]] 或 [==[...]==] 形式的长字符串
+    fn read_long_bracket(&mut self) -> Result<String> {
+        self.pos += 1;
+        let mut level = 0;
+        while self.peek() == Some('=') {
+            level += 1;
+            self.pos += 1;
+        }
+        check_if!(
+            self.peek() != Some('['),
+            return Err(self.err("长字符串格式错误"))
+        );
+        self.pos += 1;
+        if self.peek() == Some('\n') {
+            self.pos += 1;
+        }
+        let close: Vec<char> = format!("]{}]", "=".repeat(level)).chars().collect();
+        let start = self.pos;
+        while self.pos + close.len() <= self.chars.len() {
+            if self.chars[self.pos..self.pos + close.len()] == close[..] {
+                let ret: String = self.chars[start..self.pos].iter().collect();
+                self.pos += close.len();
+                return Ok(ret);
+            }
+            self.pos += 1;
+        }
+        return Err(self.err("长字符串未结束"));
+    }
+}
+
+const VALUE_TYPES: [&str; 4] = ["数字", "字符串", "布尔", "表格"];
+
+// 键值树编辑, 返回是否有点击/获得焦点
+pub fn draw_table_tree(ui: &mut egui::Ui, value: &mut ELuaValue, id: egui::Id) -> bool {
+    let list = match value {
+        ELuaValue::Table(list) => list,
+        _ => return draw_scalar(ui, value),
+    };
+    let mut flag = false;
+    let mut remove = None;
+    let mut index = 0;
+    for (i, (key, v)) in list.iter_mut().enumerate() {
+        let id = id.with(i);
+        ui.horizontal(|ui| {
+            match key {
+                ETableKey::Index => {
+                    index = index + 1;
+                    ui.label(format!("[{}]", index));
+                }
+                ETableKey::Int(k) => {
+                    let resp = ui.add(egui::DragValue::new(k).prefix("[").suffix("]"));
+                    if resp.gained_focus() {
+                        flag = true;
+                    }
+                }
+                ETableKey::Name(k) => {
+                    let txt = egui::TextEdit::singleline(k).desired_width(80.0);
+                    if ui.add(txt).gained_focus() {
+                        flag = true;
+                    }
+                }
+            }
+            let mut type_name = v.type_name();
+            egui::ComboBox::from_id_source(id.with("type"))
+                .width(50.0)
+                .selected_text(type_name)
+                .show_ui(ui, |ui| {
+                    for one in VALUE_TYPES {
+                        ui.selectable_value(&mut type_name, one, one);
+                    }
+                });
+            if type_name != v.type_name() {
+                *v = ELuaValue::default_of(type_name);
+                flag = true;
+            }
+            if !v.is_table() && draw_scalar(ui, v) {
+                flag = true;
+            }
+            if ui.small_button("✖").on_hover_text("删除").clicked() {
+                remove = Some(i);
+            }
+        });
+        if v.is_table() {
+            let count = match v {
+                ELuaValue::Table(l) => l.len(),
+                _ => 0,
+            };
+            egui::CollapsingHeader::new(format!("{{...}} {}项", count))
+                .id_source(id.with("child"))
+                .show(ui, |ui| {
+                    if draw_table_tree(ui, v, id.with("child")) {
+                        flag = true;
+                    }
+                });
+        }
+    }
+    if let Some(i) = remove {
+        list.remove(i);
+        flag = true;
+    }
+    ui.horizontal(|ui| {
+        if ui
+            .small_button("+元素")
+            .on_hover_text("添加数组元素")
+            .clicked()
+        {
+            list.push((ETableKey::Index, ELuaValue::Int(0)));
+            flag = true;
+        }
+        if ui
+            .small_button("+键值")
+            .on_hover_text("添加键值对")
+            .clicked()
+        {
+            let mut n = list.len() + 1;
+            while list
+                .iter()
+                .any(|(k, _)| *k == ETableKey::Name(format!("key{}", n)))
+            {
+                n = n + 1;
+            }
+            list.push((ETableKey::Name(format!("key{}", n)), ELuaValue::Int(0)));
+            flag = true;
+        }
+    });
+    return flag;
+}
+
+fn draw_scalar(ui: &mut egui::Ui, value: &mut ELuaValue) -> bool {
+    let resp = match value {
+        ELuaValue::Bool(b) => ui.checkbox(b, ""),
+        ELuaValue::Int(i) => ui.add(egui::DragValue::new(i)),
+        ELuaValue::Number(n) => ui.add(egui::DragValue::new(n).speed(0.1)),
+        ELuaValue::Str(s) => ui.add(egui::TextEdit::singleline(s).desired_width(120.0)),
+        ELuaValue::Table(_) => ui.label(egui::RichText::new("表格").color(Color32::GRAY)),
+    };
+    return resp.gained_focus() || resp.changed() && !matches!(value, ELuaValue::Str(_));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(text: &str) -> ELuaValue {
+        let value = ELuaValue::parse(text).unwrap();
+        let again = ELuaValue::parse(&value.to_lua()).unwrap();
+        assert_eq!(value, again, "{}", value.to_lua());
+        return value;
+    }
+
+    #[test]
+    fn numbers() {
+        let value = round_trip("{1, -2, 2.0, 0.5, 1e20, 0x10, 9007199254740993}");
+        assert_eq!(
+            value,
+            ELuaValue::Table(vec![
+                (ETableKey::Index, ELuaValue::Int(1)),
+                (ETableKey::Index, ELuaValue::Int(-2)),
+                (ETableKey::Index, ELuaValue::Number(2.0)),
+                (ETableKey::Index, ELuaValue::Number(0.5)),
+                (ETableKey::Index, ELuaValue::Number(1e20)),
+                (ETableKey::Index, ELuaValue::Int(16)),
+                (ETableKey::Index, ELuaValue::Int(9007199254740993)),
+            ])
+        );
+        assert_eq!(
+            value.to_lua(),
+            "{1, -2, 2.0, 0.5, 1e20, 16, 9007199254740993}"
+        );
+        assert_eq!(ELuaValue::Number(f64::INFINITY).to_lua(), "math.huge");
+        assert_eq!(ELuaValue::Number(f64::NAN).to_lua(), "0/0");
+    }
+
+    #[test]
+    fn strings() {
+        let value = round_trip(
+            r#"{"a\"b\\c\n", 'x\ty', "\65\x42\u{4e2d}", [[long
+text]], "中文"}"#,
+        );
+        let list = match value {
+            ELuaValue::Table(list) => list,
+            _ => panic!(),
+        };
+        assert_eq!(list[0].1, ELuaValue::Str("a\"b\\c\n".to_string()));
+        assert_eq!(list[1].1, ELuaValue::Str("x\ty".to_string()));
+        assert_eq!(list[2].1, ELuaValue::Str("AB中".to_string()));
+        assert_eq!(list[3].1, ELuaValue::Str("long\ntext".to_string()));
+        assert_eq!(list[4].1, ELuaValue::Str("中文".to_string()));
+    }
+
+    #[test]
+    fn nested_and_mixed_keys() {
+        let text = r#"{10, name = "a", [5] = true, ["a b"] = {x = 1, {2, 3}}, ["end"] = 1.5}"#;
+        let value = round_trip(text);
+        assert_eq!(
+            value.to_lua(),
+            r#"{10, name = "a", [5] = true, ["a b"] = {x = 1, {2, 3}}, ["end"] = 1.5}"#
+        );
+        assert_eq!(
+            value.to_json(),
+            json!({"1": 10, "name": "a", "5": true, "a b": {"x": 1, "1": [2, 3]}, "end": 1.5})
+        );
+        // 空表没有数组部分, 导出为对象
+        assert_eq!(round_trip("{{1}, {}}").to_json(), json!([[1], {}]));
+        assert!(value.check_value().is_ok());
+    }
+
+    #[test]
+    fn check_value() {
+        let dup = ELuaValue::Table(vec![
+            (ETableKey::Name("a".to_string()), ELuaValue::Int(1)),
+            (ETableKey::Name("a".to_string()), ELuaValue::Int(2)),
+        ]);
+        assert!(dup.check_value().is_err());
+        let dup = ELuaValue::Table(vec![
+            (ETableKey::Index, ELuaValue::Int(1)),
+            (ETableKey::Int(1), ELuaValue::Int(2)),
+        ]);
+        assert!(dup.check_value().is_err());
+        let nested = ELuaValue::Table(vec![(
+            ETableKey::Index,
+            ELuaValue::Table(vec![
+                (ETableKey::Int(3), ELuaValue::Int(1)),
+                (ETableKey::Int(3), ELuaValue::Int(2)),
+            ]),
+        )]);
+        assert!(nested.check_value().is_err());
+        let inf = ELuaValue::Table(vec![(ETableKey::Index, ELuaValue::Number(f64::INFINITY))]);
+        assert!(inf.check_value().is_err());
+        assert!(ELuaValue::parse("{1, [2] = 3}")
+            .unwrap()
+            .check_value()
+            .is_ok());
+    }
+}
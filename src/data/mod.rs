@@ -1,2 +1,8 @@
+pub mod data_check;
+pub mod data_expr;
 pub mod data_field;
+pub mod data_option;
+pub mod data_query;
 pub mod data_table;
+pub mod data_value;
+pub mod row_meta;
@@ -0,0 +1,107 @@
+use anyhow::Result;
+use eframe::epaint::Color32;
+use std::collections::HashMap;
+
+use crate::{data::data_field::FieldInfo, utils};
+
+// 编辑器内部的行标记: 标签, 颜色, 收藏
+// 和 __Group__ 一样保存在编辑器数据中, 不导出
+pub const FIELD_TAGS: &str = "__Tags__";
+pub const FIELD_COLOR: &str = "__Color__";
+pub const FIELD_FAV: &str = "__Fav__";
+
+// 多个标签用 ; 分隔, 标签中可以有空格, 如 "WIP;待审核;平衡3"
+pub const TAG_SEP: char = ';';
+
+// 颜色标记 (值, 显示名, 颜色)
+pub const ROW_COLORS: [(&str, &str, Color32); 6] = [
+    ("red", "红", Color32::from_rgb(231, 76, 60)),
+    ("orange", "橙", Color32::from_rgb(230, 126, 34)),
+    ("yellow", "黄", Color32::from_rgb(241, 196, 15)),
+    ("green", "绿", Color32::from_rgb(46, 204, 113)),
+    ("blue", "蓝", Color32::from_rgb(52, 152, 219)),
+    ("purple", "紫", Color32::from_rgb(155, 89, 182)),
+];
+
+pub fn meta_fields() -> Result<Vec<FieldInfo>> {
+    let mut color_opt = vec![":无".to_string()];
+    for (val, show, _) in ROW_COLORS {
+        color_opt.push(format!("{}:{}", val, show));
+    }
+    let tags = FieldInfo::parse(
+        FIELD_TAGS.to_string(),
+        "标签".to_string(),
+        "编辑器标签, 多个用;分隔".to_string(),
+        "标记".to_string(),
+        "S".to_string(),
+        "Text".to_string(),
+        Vec::new(),
+        String::new(),
+        String::new(),
+        false,
+        Vec::new(),
+    )?;
+    let color = FieldInfo::parse(
+        FIELD_COLOR.to_string(),
+        "颜色".to_string(),
+        "编辑器颜色标记".to_string(),
+        "标记".to_string(),
+        "S".to_string(),
+        "Enum".to_string(),
+        color_opt,
+        String::new(),
+        String::new(),
+        false,
+        Vec::new(),
+    )?;
+    let fav = FieldInfo::parse(
+        FIELD_FAV.to_string(),
+        "收藏".to_string(),
+        "编辑器收藏".to_string(),
+        "标记".to_string(),
+        "B".to_string(),
+        "Check".to_string(),
+        Vec::new(),
+        "false".to_string(),
+        String::new(),
+        false,
+        Vec::new(),
+    )?;
+    return Ok(vec![tags, color, fav]);
+}
+
+pub fn get_tags(row: &HashMap<String, String>) -> Vec<String> {
+    let tags = utils::map_get_string(row, FIELD_TAGS, "");
+    return split_tags(&tags);
+}
+
+pub fn split_tags(tags: &str) -> Vec<String> {
+    return tags
+        .split(TAG_SEP)
+        .map(|a| a.trim())
+        .filter(|a| !a.is_empty())
+        .map(|a| a.to_string())
+        .collect();
+}
+
+pub fn join_tags(tags: &Vec<String>) -> String {
+    return tags.join(&TAG_SEP.to_string());
+}
+
+pub fn is_favourite(row: &HashMap<String, String>) -> bool {
+    return utils::map_get_string(row, FIELD_FAV, "").to_lowercase() == "true";
+}
+
+// 颜色标记的值, 也可以用显示名查找, 如 "red" 或 "红"
+pub fn find_color(name: &str) -> Option<(&'static str, &'static str, Color32)> {
+    let name = name.trim().to_lowercase();
+    return ROW_COLORS
+        .iter()
+        .find(|a| a.0 == name || a.1 == name)
+        .cloned();
+}
+
+pub fn get_color(row: &HashMap<String, String>) -> Option<Color32> {
+    let val = utils::map_get_string(row, FIELD_COLOR, "");
+    return find_color(&val).map(|a| a.2);
+}
@@ -1,5 +1,4 @@
 use anyhow::Result;
-use itertools::Itertools;
 use std::{collections::HashMap, fs, path::PathBuf};
 
 use crate::{
@@ -15,7 +14,7 @@ impl DataSaver for CsvSaver {
     fn output(
         info: &Vec<FieldInfo>,
         data: &Vec<HashMap<String, String>>,
-        key: &String,
+        sort: &String,
         _table_name: &String,
         path: PathBuf,
         all: bool,
@@ -87,16 +86,23 @@ impl DataSaver for CsvSaver {
         }
 
         // 内容
-        for row in data.iter().sorted_by_key(|a| utils::map_get_i32(*a, key)) {
+        for row in super::sort_rows(info, data, sort) {
             let mut one_line = Vec::new();
             for one in info {
                 if !one.export && !all {
                     continue;
                 }
-                let v = match row.get(&one.name) {
+                let mut v = match row.get(&one.name) {
                     Some(s) => s.clone(),
                     None => String::new(),
                 };
+                if !all {
+                    v = one.get_export_val(&v);
+                }
+                // 数组元素去掉编辑器中的转义
+                if one.is_array {
+                    v = utils::export_array(&v, one.array_depth);
+                }
 
                 let mut tmp;
                 let mut flag = false;
@@ -1,5 +1,4 @@
 use anyhow::Result;
-use itertools::Itertools;
 use std::{collections::HashMap, path::PathBuf};
 
 use crate::{data::data_field::FieldInfo, utils};
@@ -12,7 +11,7 @@ impl DataSaver for ExcelSaver {
     fn output(
         info: &Vec<FieldInfo>,
         data: &Vec<HashMap<String, String>>,
-        key: &String,
+        sort: &String,
         table_name: &String,
         path: PathBuf,
         all: bool,
@@ -55,7 +54,7 @@ impl DataSaver for ExcelSaver {
 
         // 内容
         let mut row = 3;
-        for one in data.iter().sorted_by_key(|a| utils::map_get_i32(*a, key)) {
+        for one in super::sort_rows(info, data, sort) {
             row = row + 1;
             let mut col = 0;
             for field in info {
@@ -64,10 +63,13 @@ impl DataSaver for ExcelSaver {
                 }
                 col = col + 1;
 
-                let v = match one.get(&field.name) {
+                let mut v = match one.get(&field.name) {
                     Some(s) => s.clone(),
                     None => String::new(),
                 };
+                if !all {
+                    v = field.get_export_val(&v);
+                }
                 let cell_name =
                     umya_spreadsheet::helper::coordinate::coordinate_from_index(&col, &row);
                 let cell = sheet.get_cell_mut(cell_name.as_str());
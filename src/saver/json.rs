@@ -1,10 +1,12 @@
 use anyhow::Result;
-use itertools::Itertools;
 use serde_json::json;
 use std::{collections::HashMap, fs, path::PathBuf};
 
 use crate::{
-    data::data_field::{EFieldType, FieldInfo},
+    data::{
+        data_field::{EFieldType, FieldInfo},
+        data_value::ELuaValue,
+    },
     utils,
 };
 
@@ -14,6 +16,9 @@ pub struct JsonSaver {}
 
 impl JsonSaver {
     pub fn parse_one(field: &FieldInfo, data: &str) -> Result<serde_json::Value> {
+        if field.is_export_name() {
+            return Ok(json!(data));
+        }
         match field.val_type {
             EFieldType::Bool => return Ok(serde_json::Value::Bool(data.to_lowercase() == "true")),
             EFieldType::Number => {
@@ -27,12 +32,8 @@ impl JsonSaver {
                 return Ok(json!(data));
             }
             EFieldType::Table => {
-                let mut v = "{}";
-                if !data.is_empty() {
-                    v = data;
-                }
-                let map = utils::tablestr2map(&v.to_string())?;
-                return Ok(json!(map));
+                let table = ELuaValue::parse(data)?;
+                return Ok(table.to_json());
             }
         }
     }
@@ -41,13 +42,13 @@ impl JsonSaver {
         if field.is_array {
             let mut ret = json!([]);
             let list = ret.as_array_mut().unwrap();
-            let mut arr: Vec<&str> = Vec::new();
-            if !data.is_empty() {
-                arr = data.split(";").collect();
-            }
-
-            for one in arr {
-                let tmp = JsonSaver::parse_one(field, one)?;
+            let element = field.get_element_field();
+            for one in utils::split_array(data) {
+                let tmp = if element.is_array {
+                    JsonSaver::get_one(&element, &utils::unwrap_nested_array(&one))?
+                } else {
+                    JsonSaver::parse_one(field, &one)?
+                };
                 list.push(tmp);
             }
 
@@ -62,7 +63,7 @@ impl DataSaver for JsonSaver {
     fn output(
         info: &Vec<FieldInfo>,
         data: &Vec<HashMap<String, String>>,
-        key: &String,
+        sort: &String,
         _table_name: &String,
         path: PathBuf,
         all: bool,
@@ -72,7 +73,7 @@ impl DataSaver for JsonSaver {
         // 表头
 
         // 内容
-        for row in data.iter().sorted_by_key(|a| utils::map_get_i32(*a, key)) {
+        for row in super::sort_rows(info, data, sort) {
             let mut one = json!({});
             let map = one.as_object_mut().unwrap();
 
@@ -80,10 +81,13 @@ impl DataSaver for JsonSaver {
                 if !field.export && !all {
                     continue;
                 }
-                let v = match row.get(&field.name) {
+                let mut v = match row.get(&field.name) {
                     Some(s) => s.clone(),
                     None => String::new(),
                 };
+                if !all {
+                    v = field.get_export_val(&v);
+                }
 
                 let one_data = JsonSaver::get_one(field, &v)?;
                 map.insert(field.name.clone(), one_data);
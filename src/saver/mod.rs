@@ -1,20 +1,46 @@
 use std::{collections::HashMap, path::PathBuf};
 
 use anyhow::Result;
+use itertools::Itertools;
 
-use crate::data::data_field::FieldInfo;
+use crate::data::{
+    data_field::FieldInfo,
+    data_table::{self, ESortType},
+};
 
 pub trait DataSaver {
     fn output(
         info: &Vec<FieldInfo>,
         data: &Vec<HashMap<String, String>>,
-        key: &String,
+        sort: &String,
         table_name: &String,
         writer: PathBuf,
         all: bool,
     ) -> Result<()>;
 }
 
+// 按字段排序输出, 排序字段是主键时按所有主键字段排序(组合主键), 其他字段值相同时按主键
+pub fn sort_rows<'a>(
+    info: &Vec<FieldInfo>,
+    data: &'a Vec<HashMap<String, String>>,
+    sort: &String,
+) -> Vec<&'a HashMap<String, String>> {
+    let field = info.iter().find(|a| a.name == *sort);
+    let empty = String::new();
+    return data
+        .iter()
+        .sorted_by(|a, b| match field {
+            Some(f) if !f.is_key => {
+                let va = a.get(sort).unwrap_or(&empty);
+                let vb = b.get(sort).unwrap_or(&empty);
+                data_table::compare_by_type(field, ESortType::Auto, va, vb)
+                    .then_with(|| data_table::compare_key(info, a, b))
+            }
+            _ => data_table::compare_key(info, a, b),
+        })
+        .collect();
+}
+
 pub mod csv;
 pub mod excel;
 pub mod json;
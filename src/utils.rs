@@ -1,15 +1,14 @@
-use std::{collections::HashMap, path::PathBuf, process::Command};
+use std::{cmp::Ordering, collections::HashMap, path::PathBuf, process::Command};
 
 use anyhow::{bail, Result};
 use calamine::{DataType, Range};
-use itertools::Itertools;
 
 use serde_json::json;
 use umya_spreadsheet::{Spreadsheet, Worksheet};
 use walkdir::WalkDir;
 
 use crate::{
-    data::data_field::EFieldType,
+    data::{data_field::EFieldType, data_value::ELuaValue},
     error,
     marco::{check_if, check_some},
 };
@@ -31,6 +30,15 @@ pub fn msg(content: String, title: String) {
         .show();
 }
 
+// 确认对话框, 点击确定时返回 true
+pub fn confirm(content: String, title: String) -> bool {
+    return rfd::MessageDialog::new()
+        .set_title(title.as_str())
+        .set_description(content.as_str())
+        .set_buttons(rfd::MessageButtons::OkCancel)
+        .show();
+}
+
 pub fn toast(toast: &mut egui_notify::Toasts, icon: &str, msg: impl Into<String>) {
     match icon {
         "SHORT" => {
@@ -81,30 +89,17 @@ pub fn map_get_string(map: &HashMap<String, String>, key: &str, default: &str) -
     return ret;
 }
 
-pub fn map_contains_str(map: &HashMap<String, String>, search: &String) -> bool {
-    for (_k, v) in map {
-        if v.contains(search) {
-            return true;
+// 模糊匹配: pattern 的字符按顺序出现在 text 中即可, 忽略大小写
+pub fn fuzzy_match(text: &str, pattern: &str) -> bool {
+    let text = text.to_lowercase();
+    let mut chars = text.chars();
+    for p in pattern.to_lowercase().chars() {
+        check_if!(p.is_whitespace(), continue);
+        if !chars.any(|c| c == p) {
+            return false;
         }
     }
-    return false;
-}
-
-pub fn tablestr2map(table: &String) -> Result<HashMap<String, String>> {
-    let lua = mlua::Lua::new();
-    let table: mlua::Table = lua.load(table).eval()?;
-    let json = serde_json::to_string(&table)?;
-    let map: HashMap<String, String> = serde_json::from_str(&json)?;
-    return Ok(map);
-}
-
-pub fn map2tablestr(map: &HashMap<String, String>) -> Result<String> {
-    let mut ret = Vec::new();
-    for (k, v) in map.iter().sorted() {
-        ret.push(format!("{}=\"{}\"", k, v.as_str()));
-    }
-    let s = format!("{{{}}}", ret.join(", "));
-    return Ok(s);
+    return true;
 }
 
 pub fn translate_key(key: &str) -> Option<eframe::egui::Key> {
@@ -231,19 +226,23 @@ fn get_cell_value(sheet: &Worksheet, col: u32, row: u32) -> String {
     return value.to_string();
 }
 
-pub fn parse_data_type(field_type: &String) -> Result<(bool, bool, EFieldType, String)> {
-    let mut tmp = field_type.clone();
-    let mut prefix = String::new();
-    let arr: Vec<&str> = tmp.split("<").collect();
-    if arr.len() == 2 {
-        prefix = arr[0].to_string();
-        tmp = arr[1].to_string();
-    } else {
-        tmp = arr[0].to_string();
+// 解析字段类型, 返回 (是否主键, 数组层数, 元素类型, 后缀)
+// 如 K<N>, A<S>, A<A<N>>, A<E>参数
+pub fn parse_data_type(field_type: &String) -> Result<(bool, i32, EFieldType, String)> {
+    let mut tmp = field_type.as_str();
+    let mut is_key = false;
+    let mut array_depth = 0;
+    while let Some(pos) = tmp.find("<") {
+        let prefix = &tmp[..pos];
+        if prefix == "K" {
+            is_key = true;
+        }
+        if prefix == "A" {
+            array_depth = array_depth + 1;
+        }
+        tmp = &tmp[pos + 1..];
     }
-    let mut suffix = String::new();
-    let arr: Vec<&str> = tmp.split(">").collect();
-    let field = arr[0];
+    let field = tmp.split(">").next().unwrap();
     let data_type = match field {
         "B" => EFieldType::Bool,
         "N" => EFieldType::Number,
@@ -254,19 +253,259 @@ pub fn parse_data_type(field_type: &String) -> Result<(bool, bool, EFieldType, S
             bail!(error::AppError::FieldTypeNotSupport(field.to_string()));
         }
     };
-    if arr.len() == 2 {
-        suffix = arr[1].to_string();
+    let suffix = tmp[field.len()..].trim_start_matches(">").to_string();
+    return Ok((is_key, array_depth, data_type, suffix));
+}
+
+// 数组单元格的文本格式:
+// 元素之间用 ; 分隔, {} 和 [] 内的 ; 不作为分隔符 (lua 表, 嵌套数组)
+// \ 可以转义 \ ; [ ] { } 这几个字符, 括号外的 \ 都需要转义
+// 嵌套数组的每个元素写成 [..], 如 A<A<N>> 的 [1;2];[3;4]
+// 旧数据中括号不配对或括号外有未转义的 \ 时, 按旧的格式直接用 ; 分隔
+const ARRAY_SPECIAL: &str = "\\;[]{}";
+
+pub fn split_array(data: &str) -> Vec<String> {
+    if data.is_empty() {
+        return Vec::new();
     }
+    return match split_array_escaped(data) {
+        Some(ret) => ret,
+        None => data.split(';').map(|a| a.to_string()).collect(),
+    };
+}
 
-    let mut is_key = false;
-    if prefix == "K" {
-        is_key = true;
+// 按转义和括号解析, 不符合格式时返回 None
+fn split_array_escaped(data: &str) -> Option<Vec<String>> {
+    let mut ret = Vec::new();
+    let mut cur = String::new();
+    let mut depth = 0;
+    let mut chars = data.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            let next = chars.peek().cloned();
+            let special = next.map_or(false, |a| ARRAY_SPECIAL.contains(a));
+            if depth == 0 {
+                check_if!(!special, return None);
+                cur.push(next.unwrap());
+                chars.next();
+                continue;
+            }
+            // 括号内的转义留给下一层解析
+            cur.push(c);
+            if special {
+                cur.push(next.unwrap());
+                chars.next();
+            }
+            continue;
+        }
+        match c {
+            '{' | '[' => depth = depth + 1,
+            '}' | ']' if depth > 0 => depth = depth - 1,
+            ';' if depth == 0 => {
+                ret.push(cur);
+                cur = String::new();
+                continue;
+            }
+            _ => {}
+        }
+        cur.push(c);
+    }
+    check_if!(depth != 0, return None);
+    ret.push(cur);
+    return Some(ret);
+}
+
+// 元素不需要转义即可原样写入
+fn is_plain_element(data: &str) -> bool {
+    let mut depth = 0;
+    let mut chars = data.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            check_if!(depth == 0, return false);
+            let next = check_some!(chars.peek().cloned(), continue);
+            check_if!(!ARRAY_SPECIAL.contains(next), continue);
+            chars.next();
+            continue;
+        }
+        match c {
+            '{' | '[' => depth = depth + 1,
+            '}' | ']' => {
+                check_if!(depth == 0, return false);
+                depth = depth - 1;
+            }
+            ';' if depth == 0 => return false,
+            _ => {}
+        }
     }
-    let mut is_array = false;
-    if prefix == "A" {
-        is_array = true;
+    return depth == 0;
+}
+
+// 自然排序, 连续的数字按数值比较: skill_2 < skill_10
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+    loop {
+        match (a.peek().cloned(), b.peek().cloned()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let mut na = String::new();
+                while let Some(c) = a.next_if(|c| c.is_ascii_digit()) {
+                    na.push(c);
+                }
+                let mut nb = String::new();
+                while let Some(c) = b.next_if(|c| c.is_ascii_digit()) {
+                    nb.push(c);
+                }
+                let na = na.trim_start_matches('0');
+                let nb = nb.trim_start_matches('0');
+                let ord = na.len().cmp(&nb.len()).then_with(|| na.cmp(nb));
+                check_if!(ord != Ordering::Equal, return ord);
+            }
+            (Some(x), Some(y)) => {
+                check_if!(x != y, return x.cmp(&y));
+                a.next();
+                b.next();
+            }
+        }
     }
-    return Ok((is_key, is_array, data_type, suffix));
+}
+
+// 按行比较两段文本, 返回 (' ' | '-' | '+', 行内容)
+pub fn diff_lines(old: &str, new: &str) -> Vec<(char, String)> {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+    // lcs[i][j]: a[i..] 与 b[j..] 的最长公共子序列长度
+    let mut lcs = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let mut ret = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            ret.push((' ', a[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if j < b.len() && (i >= a.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            ret.push(('+', b[j].to_string()));
+            j += 1;
+        } else {
+            ret.push(('-', a[i].to_string()));
+            i += 1;
+        }
+    }
+    return ret;
+}
+
+// 生成 excel 可以直接粘贴的 TSV, 含有制表符/换行/引号的单元格用引号包起来
+pub fn to_tsv(rows: &Vec<Vec<String>>) -> String {
+    let mut ret = String::new();
+    for row in rows {
+        let line = row
+            .iter()
+            .map(|cell| {
+                if cell.contains(['\t', '\n', '\r', '"']) {
+                    format!("\"{}\"", cell.replace('"', "\"\""))
+                } else {
+                    cell.clone()
+                }
+            })
+            .collect::<Vec<String>>()
+            .join("\t");
+        ret.push_str(&line);
+        ret.push_str("\r\n");
+    }
+    return ret;
+}
+
+// 解析 excel 复制出来的 TSV, 兼容引号包起来的多行单元格
+pub fn parse_tsv(text: &str) -> Vec<Vec<String>> {
+    let mut ret = Vec::new();
+    let mut row = Vec::new();
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted {
+            if c != '"' {
+                cell.push(c);
+            } else if chars.peek() == Some(&'"') {
+                cell.push('"');
+                chars.next();
+            } else {
+                quoted = false;
+            }
+            continue;
+        }
+        match c {
+            '"' if cell.is_empty() => quoted = true,
+            '\t' => row.push(std::mem::take(&mut cell)),
+            '\r' => {}
+            '\n' => {
+                row.push(std::mem::take(&mut cell));
+                ret.push(std::mem::take(&mut row));
+            }
+            _ => cell.push(c),
+        }
+    }
+    if !cell.is_empty() || !row.is_empty() {
+        row.push(cell);
+        ret.push(row);
+    }
+    return ret;
+}
+
+pub fn join_array(list: &Vec<String>) -> String {
+    let mut ret = Vec::new();
+    for one in list {
+        if is_plain_element(one) {
+            ret.push(one.clone());
+            continue;
+        }
+        let mut s = String::new();
+        for c in one.chars() {
+            if ARRAY_SPECIAL.contains(c) {
+                s.push('\\');
+            }
+            s.push(c);
+        }
+        ret.push(s);
+    }
+    return ret.join(";");
+}
+
+// 导出为 csv 等纯文本时去掉转义, 元素直接用 ; 连接, 嵌套数组保留外层括号
+pub fn export_array(data: &str, depth: i32) -> String {
+    check_if!(depth <= 0, return data.to_string());
+    return split_array(data)
+        .iter()
+        .map(|a| match depth > 1 {
+            true => wrap_nested_array(&export_array(&unwrap_nested_array(a), depth - 1)),
+            false => a.clone(),
+        })
+        .collect::<Vec<String>>()
+        .join(";");
+}
+
+// 嵌套数组元素 [..] 去掉外层括号
+pub fn unwrap_nested_array(data: &str) -> String {
+    let data = data.trim();
+    if data.starts_with("[") && data.ends_with("]") && data.len() >= 2 {
+        return data[1..data.len() - 1].to_string();
+    }
+    return data.to_string();
+}
+
+pub fn wrap_nested_array(data: &str) -> String {
+    return format!("[{}]", data);
 }
 
 fn parse_one_data(data: &String, data_type: &EFieldType) -> Result<serde_json::Value> {
@@ -285,38 +524,35 @@ fn parse_one_data(data: &String, data_type: &EFieldType) -> Result<serde_json::V
         EFieldType::Str | EFieldType::Expr => {
             json!(data)
         }
-        EFieldType::Table => {
-            if data.is_empty() {
-                json!({})
-            } else {
-                let lua = mlua::Lua::new();
-                let table: mlua::Table = lua.load(data).eval()?;
-                let json = serde_json::to_string(&table)?;
-                serde_json::from_str(&json)?
-            }
-        }
+        EFieldType::Table => ELuaValue::parse(data)?.to_json(),
     };
     return Ok(ret);
 }
 
-pub fn load_one_cell(data: &String, data_type: &String) -> Result<serde_json::Value> {
-    let (_, is_array, data_type, _) = parse_data_type(&data_type)?;
-    if is_array {
-        let mut ret = json!([]);
-        if data.is_empty() {
-            return Ok(ret);
-        }
-        let list = ret.as_array_mut().unwrap();
-        let arr: Vec<&str> = data.split(";").collect();
-        for v in arr {
-            let v = v.trim();
-            let v = parse_one_data(&v.to_string(), &data_type)?;
-            list.push(v);
-        }
-        return Ok(ret);
-    } else {
+fn load_array_cell(
+    data: &String,
+    array_depth: i32,
+    data_type: &EFieldType,
+) -> Result<serde_json::Value> {
+    if array_depth <= 0 {
         return parse_one_data(&data, &data_type);
     }
+    let mut ret = json!([]);
+    let list = ret.as_array_mut().unwrap();
+    for v in split_array(data) {
+        let v = if array_depth > 1 {
+            unwrap_nested_array(&v)
+        } else {
+            v.trim().to_string()
+        };
+        list.push(load_array_cell(&v, array_depth - 1, data_type)?);
+    }
+    return Ok(ret);
+}
+
+pub fn load_one_cell(data: &String, data_type: &String) -> Result<serde_json::Value> {
+    let (_, array_depth, data_type, _) = parse_data_type(&data_type)?;
+    return load_array_cell(data, array_depth, &data_type);
 }
 
 pub fn load_excel_sheet<T>(path: &PathBuf, sheet_name: &str) -> Result<T>
@@ -417,6 +653,52 @@ pub fn load_excel2map(path: &PathBuf, sheet_name: &str) -> Result<Vec<HashMap<St
     return Ok(list);
 }
 
+// load_excel2map 的反向操作, 保留原有的三行表头, 按字段名写回数据行
+pub fn save_map2excel(
+    path: &PathBuf,
+    sheet_name: &str,
+    list: &Vec<HashMap<String, String>>,
+) -> Result<()> {
+    let mut book = read_or_create_excel(path);
+    let mut header = Vec::new();
+    if let Ok(sheet) = book.get_sheet_by_name(sheet_name) {
+        let (col, _) = sheet.get_highest_column_and_row();
+        for c in 1..col + 1 {
+            let head: Vec<String> = (1..4).map(|r| get_cell_value(&sheet, c, r)).collect();
+            header.push(head);
+        }
+    }
+    if header.is_empty() {
+        bail!(format!("表格[{}]没有页签[{}]", path.display(), sheet_name));
+    }
+
+    let _ = book.remove_sheet_by_name(sheet_name);
+    let sheet = match book.new_sheet(sheet_name) {
+        Ok(s) => s,
+        Err(e) => bail!(format!("creat sheet[{}] failed: {:?}", sheet_name, e)),
+    };
+    for (c, head) in header.iter().enumerate() {
+        let col = c as u32 + 1;
+        for (r, v) in head.iter().enumerate() {
+            let row = r as u32 + 1;
+            sheet
+                .get_cell_by_column_and_row_mut(&col, &row)
+                .set_value(v);
+        }
+        let key = &head[2];
+        check_if!(key.is_empty(), continue);
+        for (r, one) in list.iter().enumerate() {
+            let row = r as u32 + 4;
+            let v = check_some!(one.get(key), continue);
+            sheet
+                .get_cell_by_column_and_row_mut(&col, &row)
+                .set_value(v);
+        }
+    }
+    umya_spreadsheet::writer::xlsx::write(&book, path)?;
+    return Ok(());
+}
+
 pub fn read_or_create_excel(path: &PathBuf) -> Spreadsheet {
     let book = umya_spreadsheet::reader::xlsx::read(path.clone());
     let book = match book {
@@ -430,3 +712,146 @@ pub fn read_or_create_excel(path: &PathBuf) -> Spreadsheet {
 
     return book;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strs(list: &[&str]) -> Vec<String> {
+        return list.iter().map(|a| a.to_string()).collect();
+    }
+
+    #[test]
+    fn split_array_plain() {
+        assert_eq!(split_array(""), Vec::<String>::new());
+        assert_eq!(split_array("1;2;3"), strs(&["1", "2", "3"]));
+        assert_eq!(split_array("a;;b"), strs(&["a", "", "b"]));
+    }
+
+    #[test]
+    fn split_array_nested() {
+        assert_eq!(split_array("[1;2];[3;4]"), strs(&["[1;2]", "[3;4]"]));
+        assert_eq!(
+            split_array("{a=1;b=2};{c=3}"),
+            strs(&["{a=1;b=2}", "{c=3}"])
+        );
+        assert_eq!(
+            split_array("[[1;2];[3]];[4]"),
+            strs(&["[[1;2];[3]]", "[4]"])
+        );
+        // 括号内的转义留给下一层
+        assert_eq!(split_array(r"[a\;b;c]"), strs(&[r"[a\;b;c]"]));
+        assert_eq!(
+            split_array(&unwrap_nested_array(r"[a\;b;c]")),
+            strs(&["a;b", "c"])
+        );
+    }
+
+    #[test]
+    fn split_array_escaped() {
+        assert_eq!(split_array(r"a\;b;c"), strs(&["a;b", "c"]));
+        assert_eq!(split_array(r"\[x;\\"), strs(&["[x", r"\"]));
+    }
+
+    #[test]
+    fn split_array_legacy() {
+        // 括号不配对, 按 ; 分隔
+        assert_eq!(split_array("a[b;c;d"), strs(&["a[b", "c", "d"]));
+        assert_eq!(split_array("x{;y"), strs(&["x{", "y"]));
+        // 括号外未转义的 \
+        assert_eq!(split_array(r"C:\dir;x"), strs(&[r"C:\dir", "x"]));
+        assert_eq!(split_array(r"end\"), strs(&[r"end\"]));
+    }
+
+    #[test]
+    fn join_array_round_trip() {
+        let cases = vec![
+            strs(&["1", "2", "3"]),
+            strs(&["a;b", "c"]),
+            strs(&[r"C:\dir", "x;y"]),
+            strs(&[r"C:\dir", "x"]),
+            strs(&["[x", "y]", "{z"]),
+            strs(&[r"\;", r"\\", ";"]),
+            strs(&["[1;2]", "[3;4]"]),
+            strs(&[r#"{a=1;b="\n"}"#, "plain"]),
+            strs(&["", "a", ""]),
+        ];
+        for list in cases {
+            let text = join_array(&list);
+            assert_eq!(split_array(&text), list, "{}", text);
+        }
+    }
+
+    #[test]
+    fn join_array_nested_round_trip() {
+        let inner = vec![strs(&["1", "a;b"]), strs(&["[x"]), strs(&["2"])];
+        let outer: Vec<String> = inner
+            .iter()
+            .map(|a| wrap_nested_array(&join_array(a)))
+            .collect();
+        let text = join_array(&outer);
+        let back: Vec<Vec<String>> = split_array(&text)
+            .iter()
+            .map(|a| split_array(&unwrap_nested_array(a)))
+            .collect();
+        assert_eq!(back, inner, "{}", text);
+    }
+
+    #[test]
+    fn export_array_unescaped() {
+        assert_eq!(export_array("1;2;3", 1), "1;2;3");
+        assert_eq!(export_array(r"a\;b;C:\\dir", 1), r"a;b;C:\dir");
+        // 括号外有未转义的 \ 时按旧格式原样导出
+        assert_eq!(export_array(r"C:\dir;x", 1), r"C:\dir;x");
+        assert_eq!(export_array(r"\[x;y\]", 1), "[x;y]");
+        assert_eq!(export_array(r"[1;a\;b];[2]", 2), "[1;a;b];[2]");
+        assert_eq!(export_array("{a=1;b=2};{c=3}", 1), "{a=1;b=2};{c=3}");
+        assert_eq!(export_array("", 1), "");
+    }
+
+    #[test]
+    fn export_array_legacy_bracket() {
+        // 旧数据中带括号的字符串元素按嵌套解析, 导出后与原文本相同
+        assert_eq!(split_array("[a;b];c"), strs(&["[a;b]", "c"]));
+        assert_eq!(export_array("[a;b];c", 1), "[a;b];c");
+        assert_eq!(export_array("[tag];x", 1), "[tag];x");
+    }
+
+    #[test]
+    fn parse_tsv_plain() {
+        let rows = parse_tsv("id\tname\r\n1\ta\r\n2\t\r\n");
+        assert_eq!(
+            rows,
+            vec![strs(&["id", "name"]), strs(&["1", "a"]), strs(&["2", ""])]
+        );
+        // 最后一行没有换行
+        assert_eq!(parse_tsv("1\ta"), vec![strs(&["1", "a"])]);
+    }
+
+    #[test]
+    fn parse_tsv_quoted() {
+        // 引号内的制表符和换行原样保留
+        let text = "1\t\"a\tb\"\t\"x\r\ny\"\n2\t\"say \"\"hi\"\"\"\tz\n";
+        assert_eq!(
+            parse_tsv(text),
+            vec![
+                strs(&["1", "a\tb", "x\r\ny"]),
+                strs(&["2", "say \"hi\"", "z"])
+            ]
+        );
+        // 不在开头的引号按普通字符处理
+        assert_eq!(parse_tsv("a\"b\tc\n"), vec![strs(&["a\"b", "c"])]);
+    }
+
+    #[test]
+    fn tsv_round_trip() {
+        let rows = vec![
+            strs(&["id", "name", "desc"]),
+            strs(&["1", "a\tb", "line1\nline2"]),
+            strs(&["2", "\"quoted\"", ""]),
+            strs(&["3", "{a=1;b=\"x\"}", "\r\n"]),
+        ];
+        let text = to_tsv(&rows);
+        assert_eq!(parse_tsv(&text), rows, "{}", text);
+    }
+}